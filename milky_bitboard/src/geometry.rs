@@ -0,0 +1,64 @@
+//! Dimensions of the board. Conversions between square indices and their rank and file go
+//! through a [`BoardGeometry`], instead of spreading `rank * 8 + file` around, so supporting a
+//! board of a different size starts with a new geometry.
+//!
+//! Ranks are counted from the top of the board, the same way [`crate::Square`] is laid out, so
+//! rank index 0 holds the squares from A8 to H8.
+
+pub trait BoardGeometry {
+    const FILES: usize;
+    const RANKS: usize;
+    const SQUARES: usize = Self::FILES * Self::RANKS;
+
+    /// Index of the square on the given rank and file.
+    fn index(rank: usize, file: usize) -> usize {
+        rank * Self::FILES + file
+    }
+
+    fn rank_of(index: usize) -> usize {
+        index / Self::FILES
+    }
+
+    fn file_of(index: usize) -> usize {
+        index % Self::FILES
+    }
+
+    /// Index of the square on the same file as seen from the other side of the board.
+    fn mirror(index: usize) -> usize {
+        Self::index(Self::RANKS - 1 - Self::rank_of(index), Self::file_of(index))
+    }
+
+    /// Whether signed coordinates, like the ones produced by walking from a square in some
+    /// direction, are still on the board.
+    fn contains(rank: i8, file: i8) -> bool {
+        (0..Self::RANKS as i8).contains(&rank) && (0..Self::FILES as i8).contains(&file)
+    }
+}
+
+/// The standard chess board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Board8x8;
+
+impl BoardGeometry for Board8x8 {
+    const FILES: usize = 8;
+    const RANKS: usize = 8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Square;
+
+    #[test]
+    fn test_board_8x8_matches_square_layout() {
+        assert_eq!(Board8x8::SQUARES, 64);
+        assert_eq!(Board8x8::index(0, 0), Square::A8 as usize);
+        assert_eq!(Board8x8::index(7, 7), Square::H1 as usize);
+        assert_eq!(Board8x8::rank_of(Square::E4 as usize), 4);
+        assert_eq!(Board8x8::file_of(Square::E4 as usize), 4);
+        assert_eq!(Board8x8::mirror(Square::E2 as usize), Square::E7 as usize);
+        assert!(Board8x8::contains(7, 0));
+        assert!(!Board8x8::contains(8, 0));
+        assert!(!Board8x8::contains(0, -1));
+    }
+}
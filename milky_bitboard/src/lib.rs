@@ -1,11 +1,14 @@
 use std::num::Wrapping;
 
+use geometry::{Board8x8, BoardGeometry};
+
 mod error;
+pub mod geometry;
 mod moves;
 mod square;
 
 pub use error::Error;
-pub use moves::{Move, MoveFlags, PromotionPieces};
+pub use moves::{Move, MoveBuilder, MoveFlags, PromotionPieces};
 pub use square::Square;
 
 pub trait IntoU64 {
@@ -611,29 +614,158 @@ impl std::ops::ShrAssign<u64> for BitBoard {
     }
 }
 
+/// Writes the board rank by rank, with the symbol of every square given by `symbol`.
+fn write_board(
+    f: &mut std::fmt::Formatter<'_>,
+    symbol: impl Fn(Square) -> char,
+) -> std::fmt::Result {
+    writeln!(f)?;
+
+    for rank in 0..Board8x8::RANKS {
+        let mut line = String::with_capacity(20);
+        line.push_str(&format!("  {} ", Board8x8::RANKS - rank));
+
+        for file in 0..Board8x8::FILES {
+            let square = Square::from_rank_file(rank, file);
+            line.push(' ');
+            line.push(symbol(square));
+        }
+
+        writeln!(f, "{line}")?;
+    }
+
+    writeln!(f)?;
+    writeln!(f, "     a b c d e f g h")?;
+    writeln!(f)
+}
+
+/// Prints the board as a grid, the alternate flag (`{:#}`) prints it in a single line instead,
+/// with ranks written like in a FEN: runs of empty squares as a number and set squares as `x`.
 impl std::fmt::Display for BitBoard {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f)?;
+        if f.alternate() {
+            return write!(f, "{}", self.to_fen_ranks());
+        }
+
+        write_board(f, |square| match self.get_bit(square).is_set() {
+            true => '1',
+            false => '0',
+        })?;
+        writeln!(f, "     Bitboard: {}", self.0)?;
+
+        Ok(())
+    }
+}
+
+impl std::fmt::LowerHex for BitBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0.0, f)
+    }
+}
+
+impl std::fmt::UpperHex for BitBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::UpperHex::fmt(&self.0.0, f)
+    }
+}
 
-        for rank in 0..8 {
-            let mut line = String::with_capacity(20);
-            line.push_str(&format!("  {} ", 8 - rank));
+impl BitBoard {
+    fn to_fen_ranks(self) -> String {
+        let mut ranks = Vec::with_capacity(Board8x8::RANKS);
+
+        for rank in 0..Board8x8::RANKS {
+            let mut line = String::new();
+            let mut empty = 0;
+
+            for file in 0..Board8x8::FILES {
+                let square = Square::from_rank_file(rank, file);
+                if self.get_bit(square).is_empty() {
+                    empty += 1;
+                    continue;
+                }
+
+                if empty > 0 {
+                    line.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                line.push('x');
+            }
 
-            for file in 0..8 {
-                let square = Square::from_u64_unchecked(rank * 8 + file);
-                let bit = if !self.get_bit(square).is_empty() { '1' } else { '0' };
-                line.push(' ');
-                line.push(bit);
+            if empty > 0 {
+                line.push_str(&empty.to_string());
             }
 
-            writeln!(f, "{line}")?;
+            ranks.push(line);
         }
 
-        writeln!(f)?;
-        writeln!(f, "     a b c d e f g h")?;
-        writeln!(f)?;
-        writeln!(f, "     Bitboard: {}", self.0)?;
+        ranks.join("/")
+    }
 
-        Ok(())
+    /// Displays this board with another one laid over it, useful to see how a mask relates to
+    /// the squares it is applied to.
+    ///
+    /// Squares only on this board are printed as `1`, only on the overlay as `x`, and on both as
+    /// `*`.
+    pub fn with_overlay(self, overlay: BitBoard) -> BitBoardOverlay {
+        BitBoardOverlay {
+            board: self,
+            overlay,
+        }
+    }
+}
+
+/// A bitboard displayed with another one over it, see [`BitBoard::with_overlay`].
+#[derive(Debug, Clone, Copy)]
+pub struct BitBoardOverlay {
+    board: BitBoard,
+    overlay: BitBoard,
+}
+
+impl std::fmt::Display for BitBoardOverlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_board(f, |square| {
+            match (
+                self.board.get_bit(square).is_set(),
+                self.overlay.get_bit(square).is_set(),
+            ) {
+                (true, true) => '*',
+                (true, false) => '1',
+                (false, true) => 'x',
+                (false, false) => '0',
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitboard_hex_format() {
+        let board = BitBoard::from(&[Square::A8, Square::H1][..]);
+
+        assert_eq!(format!("{board:x}"), "8000000000000001");
+        assert_eq!(
+            format!("{:#018X}", BitBoard::from_square(Square::E8)),
+            "0x0000000000000010"
+        );
+    }
+
+    #[test]
+    fn test_bitboard_compact_format() {
+        let board = BitBoard::from(&[Square::A8, Square::E4, Square::F4, Square::H1][..]);
+
+        assert_eq!(format!("{board:#}"), "x7/8/8/8/4xx2/8/8/7x");
+        assert_eq!(format!("{:#}", BitBoard::empty()), "8/8/8/8/8/8/8/8");
+    }
+
+    #[test]
+    fn test_bitboard_overlay_format() {
+        let board = BitBoard::from(&[Square::A8, Square::B8][..]);
+        let overlay = BitBoard::from(&[Square::B8, Square::C8][..]);
+
+        let display = board.with_overlay(overlay).to_string();
+        assert!(display.contains("  8  1 * x 0 0 0 0 0"), "{display}");
     }
 }
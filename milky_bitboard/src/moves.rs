@@ -207,6 +207,106 @@ impl Move {
     pub fn is_castling(&self) -> bool {
         (self.0 & 0x800000) != 0
     }
+
+    /// King side castling move of the given side.
+    pub fn castle_kingside(side: Side) -> Self {
+        match side {
+            Side::White => MoveBuilder::new(Square::E1, Square::G1).piece(Pieces::WhiteKing),
+            Side::Black => MoveBuilder::new(Square::E8, Square::G8).piece(Pieces::BlackKing),
+            _ => unreachable!(),
+        }
+        .castling()
+        .build()
+    }
+
+    /// Queen side castling move of the given side.
+    pub fn castle_queenside(side: Side) -> Self {
+        match side {
+            Side::White => MoveBuilder::new(Square::E1, Square::C1).piece(Pieces::WhiteKing),
+            Side::Black => MoveBuilder::new(Square::E8, Square::C8).piece(Pieces::BlackKing),
+            _ => unreachable!(),
+        }
+        .castling()
+        .build()
+    }
+}
+
+/// Builds a [`Move`] without having to know its encoding.
+///
+/// ```
+/// use milky_bitboard::{MoveBuilder, Pieces, Square};
+///
+/// let capture = MoveBuilder::new(Square::E4, Square::D5)
+///     .piece(Pieces::WhitePawn)
+///     .capture()
+///     .build();
+///
+/// assert!(capture.is_capture());
+/// assert_eq!(capture.to_string(), "e4d5");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveBuilder {
+    source: Square,
+    target: Square,
+    piece: Option<Pieces>,
+    promotion: PromotionPieces,
+    flags: MoveFlags,
+}
+
+impl MoveBuilder {
+    pub fn new(source: Square, target: Square) -> Self {
+        Self {
+            source,
+            target,
+            piece: None,
+            promotion: PromotionPieces::NoPromotion,
+            flags: MoveFlags::empty(),
+        }
+    }
+
+    /// The piece being moved, pawns promoting are still pawns.
+    pub fn piece(mut self, piece: Pieces) -> Self {
+        self.piece = Some(piece);
+        self
+    }
+
+    pub fn promotion(mut self, promotion: PromotionPieces) -> Self {
+        self.promotion = promotion;
+        self
+    }
+
+    pub fn capture(mut self) -> Self {
+        self.flags |= MoveFlags::CAPTURE;
+        self
+    }
+
+    pub fn double_push(mut self) -> Self {
+        self.flags |= MoveFlags::DOUBLE_PUSH;
+        self
+    }
+
+    /// Marks the move as an en passant capture, which is also a capture.
+    pub fn en_passant(mut self) -> Self {
+        self.flags |= MoveFlags::EN_PASSANT | MoveFlags::CAPTURE;
+        self
+    }
+
+    pub fn castling(mut self) -> Self {
+        self.flags |= MoveFlags::CASTLING;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics when no piece was given, as a move can't be played without knowing which piece is
+    /// moving.
+    pub fn build(self) -> Move {
+        let Some(piece) = self.piece else {
+            panic!("MoveBuilder requires the moving piece, set it with `MoveBuilder::piece`");
+        };
+
+        Move::new(self.source, self.target, piece, self.promotion, self.flags)
+    }
 }
 
 impl std::fmt::Display for Move {
@@ -228,3 +328,54 @@ impl std::ops::Deref for Move {
         &self.0
     }
 }
+
+/// Reads back a move from its encoding, as given by dereferencing it.
+impl From<u32> for Move {
+    fn from(encoded: u32) -> Self {
+        Self(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_matches_move_encoding() {
+        let built = MoveBuilder::new(Square::B7, Square::A8)
+            .piece(Pieces::WhitePawn)
+            .promotion(PromotionPieces::Queen)
+            .capture()
+            .build();
+        let expected = Move::new(
+            Square::B7,
+            Square::A8,
+            Pieces::WhitePawn,
+            PromotionPieces::Queen,
+            MoveFlags::CAPTURE,
+        );
+        assert_eq!(built, expected);
+
+        let en_passant = MoveBuilder::new(Square::E5, Square::D6)
+            .piece(Pieces::WhitePawn)
+            .en_passant()
+            .build();
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+    }
+
+    #[test]
+    fn test_castling_constructors() {
+        let castle = Move::castle_kingside(Side::Black);
+        assert!(castle.is_castling());
+        assert_eq!(castle.piece(), Pieces::BlackKing);
+        assert_eq!(castle.to_string(), "e8g8");
+
+        assert_eq!(Move::castle_queenside(Side::White).to_string(), "e1c1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_builder_requires_piece() {
+        MoveBuilder::new(Square::E2, Square::E4).build();
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::{Error, Result};
+use crate::geometry::{Board8x8, BoardGeometry};
 use crate::{File, IntoU64, Rank};
 
 #[derive(Debug)]
@@ -42,23 +43,24 @@ impl Square {
         unsafe { std::mem::transmute(value) }
     }
 
+    /// Square on the given rank and file, ranks counted from the 8th rank.
+    pub fn from_rank_file(rank: usize, file: usize) -> Self {
+        Square::from_u64_unchecked(Board8x8::index(rank, file) as u64)
+    }
+
     pub fn one_forward(&self) -> Option<Self> {
         (*self as u64)
-            .checked_sub(8)
+            .checked_sub(Board8x8::FILES as u64)
             .map(Square::from_u64_unchecked)
     }
 
     pub fn one_backward(&self) -> Option<Self> {
-        let value = (*self as u64) + 8;
+        let value = (*self as u64) + Board8x8::FILES as u64;
         if value > Square::H1 as u64 { None } else { Some(Square::from_u64_unchecked(value)) }
     }
 
     pub fn mirror(&self) -> Square {
-        let index = *self as u64;
-        let rank = index / 8;
-        let file = index % 8;
-        let mirrored_index = (7 - rank) * 8 + file;
-        unsafe { std::mem::transmute(mirrored_index) }
+        Square::from_u64_unchecked(Board8x8::mirror(*self as usize) as u64)
     }
 
     #[rustfmt::skip]
@@ -80,7 +82,7 @@ impl Square {
     }
 
     pub fn file(&self) -> File {
-        match *self as usize % 8 {
+        match Board8x8::file_of(*self as usize) {
             0 => File::A,
             1 => File::B,
             2 => File::C,
@@ -94,7 +96,7 @@ impl Square {
     }
 
     pub fn rank(&self) -> Rank {
-        match 7 - (*self as usize / 8) {
+        match Board8x8::RANKS - 1 - Board8x8::rank_of(*self as usize) {
             0 => Rank::First,
             1 => Rank::Second,
             2 => Rank::Third,
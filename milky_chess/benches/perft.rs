@@ -128,6 +128,40 @@ fn perft_italian_game(b: divan::Bencher, depth: u8) {
     });
 }
 
+#[divan::bench(args = [0, 1, 2, 3, 4, 5], sample_count = 1, sample_size = 1)]
+fn perft_complex_position_mirrored(b: divan::Bencher, depth: u8) {
+    let fen = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    let fen = milky_fen::parse_fen_string(fen).unwrap();
+    let expected_nodes = [1, 6, 264, 9467, 422_333, 15_833_292];
+
+    b.bench_local(|| {
+        let mut milky = Milky::new();
+        milky.load_position(fen.clone());
+
+        let mut nodes = 0;
+        perft_driver(&mut milky, &mut nodes, depth);
+
+        assert_eq!(nodes, expected_nodes[depth as usize]);
+    });
+}
+
+#[divan::bench(args = [0, 1, 2, 3, 4, 5], sample_count = 1, sample_size = 1)]
+fn perft_promotions(b: divan::Bencher, depth: u8) {
+    let fen = "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1";
+    let fen = milky_fen::parse_fen_string(fen).unwrap();
+    let expected_nodes = [1, 24, 496, 9483, 182_838, 3_605_103];
+
+    b.bench_local(|| {
+        let mut milky = Milky::new();
+        milky.load_position(fen.clone());
+
+        let mut nodes = 0;
+        perft_driver(&mut milky, &mut nodes, depth);
+
+        assert_eq!(nodes, expected_nodes[depth as usize]);
+    });
+}
+
 fn main() {
     milky_chess::init_static_members();
     divan::main();
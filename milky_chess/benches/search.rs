@@ -0,0 +1,37 @@
+use milky_chess::Milky;
+use milky_chess::time_manager::TimeControl;
+
+/// Positions searched by the benchmarks, mixing an opening, a middlegame full of tactics and an
+/// endgame, where the transposition table gets most of its hits.
+const POSITIONS: [&str; 3] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+];
+
+/// Every search starts with an empty transposition table, which is allocated outside of the
+/// measured section.
+#[divan::bench(args = [6, 7, 8], sample_count = 5, sample_size = 1)]
+fn search_fixed_depth(b: divan::Bencher, depth: u8) {
+    b.with_inputs(|| {
+        POSITIONS
+            .iter()
+            .map(|fen| {
+                let mut milky = Milky::new();
+                milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+                milky
+            })
+            .collect::<Vec<_>>()
+    })
+    .bench_local_values(|mut engines| {
+        for milky in engines.iter_mut() {
+            milky.think(TimeControl::FixedDepth(depth));
+        }
+        engines
+    });
+}
+
+fn main() {
+    milky_chess::init_static_members();
+    divan::main();
+}
@@ -0,0 +1,14 @@
+//! Regenerates the magic numbers used for slider attack lookups, verifies them and prints the
+//! tables as rust source, ready to replace the ones in `milky_chess/src/lib.rs`.
+use milky_chess::magic::MagicNumbers;
+
+fn main() {
+    let magics = MagicNumbers::generate();
+
+    if let Err(error) = magics.verify() {
+        eprintln!("generated magics are invalid: {error}");
+        std::process::exit(1);
+    }
+
+    print!("{}", magics.to_source());
+}
@@ -1,10 +1,11 @@
-use milky_bitboard::{BitBoard, CastlingRights, Pieces, Side, Square};
+use milky_bitboard::{BitBoard, CastlingRights, Move, PieceKind, Pieces, Rank, Side, Square};
+use milky_fen::FenParts;
 
 use crate::zobrist::{Zobrist, ZobristKey};
 use crate::{
     BISHOP_ATTACKS, BISHOP_BLOCKERS, BISHOP_MAGIC_BITBOARDS, BISHOP_RELEVANT_OCCUPANCIES,
-    KING_ATTACKS, KNIGHT_ATTACKS, MAX_REPETITIONS, PAWN_ATTACKS, ROOK_ATTACKS, ROOK_BLOCKERS,
-    ROOK_MAGIC_BITBOARDS, ROOK_RELEVANT_OCCUPANCIES, attacks,
+    KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, ROOK_ATTACKS, ROOK_BLOCKERS, ROOK_MAGIC_BITBOARDS,
+    ROOK_RELEVANT_OCCUPANCIES, attacks,
 };
 
 pub fn get_bishop_attacks(square: Square, mut occupancy: BitBoard) -> BitBoard {
@@ -33,7 +34,11 @@ pub fn get_queen_attacks(square: Square, occupancy: BitBoard) -> BitBoard {
     queen_attacks
 }
 
-#[derive(Debug)]
+/// Rough value of each piece kind used to resolve exchanges, the king is worth more than anything
+/// it could ever win.
+static EXCHANGE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20_000];
+
+#[derive(Debug, Clone)]
 pub struct BoardSnapshot {
     pub boards: [BitBoard; 12],
     pub occupancies: [BitBoard; 3],
@@ -41,6 +46,7 @@ pub struct BoardSnapshot {
     pub en_passant: Square,
     pub castling_rights: CastlingRights,
     pub position_key: ZobristKey,
+    pub pawn_key: ZobristKey,
     pub fifty_move_counter: u8,
 }
 
@@ -53,11 +59,13 @@ impl Default for BoardSnapshot {
             en_passant: Square::OffBoard,
             castling_rights: CastlingRights::all(),
             position_key: ZobristKey::default(),
+            pawn_key: ZobristKey::default(),
             fifty_move_counter: 0,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct BoardState {
     pub pieces: [BitBoard; 12],
     pub occupancies: [BitBoard; 3],
@@ -67,8 +75,9 @@ pub struct BoardState {
     pub snapshots: Vec<BoardSnapshot>,
     pub fifty_move_counter: u8,
     pub ply: usize,
-    pub repetition_table: [ZobristKey; MAX_REPETITIONS],
-    pub repetition_index: usize,
+    /// Hash of the pawns alone, keying the pawn hash table. Moves keep it up to date, but loading
+    /// a FEN doesn't, it is set from [`Zobrist::hash_pawns`] along with the position key.
+    pub pawn_key: ZobristKey,
 }
 
 impl Default for BoardState {
@@ -87,9 +96,8 @@ impl BoardState {
             en_passant: Square::OffBoard,
             snapshots: vec![],
             ply: 0,
-            repetition_table: [ZobristKey::default(); MAX_REPETITIONS],
-            repetition_index: 0,
             fifty_move_counter: 0,
+            pawn_key: ZobristKey::default(),
         }
     }
 
@@ -101,6 +109,7 @@ impl BoardState {
             en_passant: self.en_passant,
             castling_rights: self.castling_rights,
             position_key: zobrist.position,
+            pawn_key: self.pawn_key,
             fifty_move_counter: self.fifty_move_counter,
         });
     }
@@ -116,18 +125,49 @@ impl BoardState {
         self.en_passant = snapshot.en_passant;
         self.castling_rights = snapshot.castling_rights;
         self.fifty_move_counter = snapshot.fifty_move_counter;
+        self.pawn_key = snapshot.pawn_key;
         snapshot.position_key
     }
 
-    pub fn record_repetition(&mut self, zobrist: &mut Zobrist) {
-        self.repetition_index += 1;
-        self.repetition_table[self.repetition_index] = zobrist.position;
+    /// Sets up the pieces, side to move, castling rights, en passant square and fifty move counter
+    /// of a FEN, discarding the moves played before.
+    pub fn load_fen(&mut self, fen_parts: &FenParts) {
+        self.reset();
+
+        self.pieces = fen_parts.positions;
+        self.occupancies = [
+            fen_parts.white_occupancy,
+            fen_parts.black_occupancy,
+            fen_parts.both_occupancy,
+        ];
+        self.side_to_move = fen_parts.side_to_move;
+        self.castling_rights = fen_parts.castling_rights;
+        self.en_passant = match fen_parts.en_passant.is_available()
+            && self.can_capture_en_passant(fen_parts.en_passant, self.side_to_move)
+        {
+            true => fen_parts.en_passant,
+            false => Square::OffBoard,
+        };
+        self.fifty_move_counter = fen_parts.half_move_clock.min(u8::MAX as u32) as u8;
     }
 
+    /// Forgets the moves played, leaving the board ready for a new position to be loaded.
     pub fn reset(&mut self) {
+        self.snapshots.clear();
         self.ply = 0;
-        self.repetition_table = [ZobristKey::default(); MAX_REPETITIONS];
-        self.repetition_index = 0;
+    }
+
+    /// Whether a pawn of `side` attacks the en passant square. Only such squares are kept on the
+    /// board, as a square nobody can capture on would make the position hash differently from
+    /// the same position reached without a double push.
+    pub fn can_capture_en_passant(&self, square: Square, side: Side) -> bool {
+        let pawn = match side {
+            Side::White => Pieces::WhitePawn,
+            Side::Black => Pieces::BlackPawn,
+            _ => unreachable!(),
+        };
+
+        attacks!(PAWN_ATTACKS)[side.enemy()][square].is_attacked(self.pieces[pawn])
     }
 
     pub fn is_square_attacked(&self, square: Square, side: Side) -> bool {
@@ -189,4 +229,624 @@ impl BoardState {
 
         false
     }
+
+    /// Whether the king of the side to move is attacked.
+    pub fn in_check(&self) -> bool {
+        let king_square = match self.side_to_move {
+            Side::White => self.pieces[Pieces::WhiteKing].trailing_zeros(),
+            Side::Black => self.pieces[Pieces::BlackKing].trailing_zeros(),
+            _ => unreachable!(),
+        };
+
+        self.is_square_attacked(king_square, self.side_to_move.enemy())
+    }
+
+    /// Whether the side has any piece besides its pawns and king.
+    pub fn has_non_pawn_material(&self, side: Side) -> bool {
+        let (pawns, king) = match side {
+            Side::White => (Pieces::WhitePawn, Pieces::WhiteKing),
+            Side::Black => (Pieces::BlackPawn, Pieces::BlackKing),
+            _ => unreachable!(),
+        };
+
+        !(self.occupancies[side] & !self.pieces[pawns] & !self.pieces[king]).is_empty()
+    }
+
+    /// Whether neither side has the material left to ever checkmate, whatever is played: bare
+    /// kings, a single minor piece, or only bishops all standing on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        // a8 is light, and colors alternate along ranks and files
+        const LIGHT_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+
+        let both = |white: Pieces, black: Pieces| *(self.pieces[white] | self.pieces[black]);
+        let mating_material = both(Pieces::WhitePawn, Pieces::BlackPawn)
+            | both(Pieces::WhiteRook, Pieces::BlackRook)
+            | both(Pieces::WhiteQueen, Pieces::BlackQueen);
+        if mating_material != 0 {
+            return false;
+        }
+
+        let knights = both(Pieces::WhiteKnight, Pieces::BlackKnight);
+        let bishops = both(Pieces::WhiteBishop, Pieces::BlackBishop);
+        match (knights.count_ones(), bishops.count_ones()) {
+            (0, 0) | (1, 0) | (0, 1) => true,
+            (0, _) => bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0,
+            _ => false,
+        }
+    }
+
+    /// Whether the move attacks the enemy king, either with the moving piece or by uncovering a
+    /// slider behind it. Castling is never reported as a check.
+    pub fn gives_check(&self, piece_move: Move) -> bool {
+        if piece_move.is_castling() {
+            return false;
+        }
+
+        let side = self.side_to_move;
+        let source = piece_move.source();
+        let target = piece_move.target();
+        let (enemy_king, first_piece) = match side {
+            Side::White => (Pieces::BlackKing, Pieces::WhitePawn as usize),
+            _ => (Pieces::WhiteKing, Pieces::BlackPawn as usize),
+        };
+        let king_square = self.pieces[enemy_king].trailing_zeros();
+
+        let mut occupancy = self.occupancies[Side::Both];
+        occupancy.clear_bit(source);
+        occupancy.set_bit(target);
+
+        let promotion = piece_move.promotion();
+        let piece =
+            if promotion.is_promoting() { promotion.into_piece(side) } else { piece_move.piece() };
+
+        let direct = match piece.kind() {
+            PieceKind::Pawn => attacks!(PAWN_ATTACKS)[side][target],
+            PieceKind::Knight => attacks!(KNIGHT_ATTACKS)[target],
+            PieceKind::Bishop => get_bishop_attacks(target, occupancy),
+            PieceKind::Rook => get_rook_attacks(target, occupancy),
+            PieceKind::Queen => get_queen_attacks(target, occupancy),
+            PieceKind::King => BitBoard::empty(),
+        };
+        if direct.get_bit(king_square).is_set() {
+            return true;
+        }
+
+        let own = |kind: PieceKind| self.pieces[first_piece + kind as usize];
+        let mut bishops = own(PieceKind::Bishop) | own(PieceKind::Queen);
+        let mut rooks = own(PieceKind::Rook) | own(PieceKind::Queen);
+        bishops.clear_bit(source);
+        rooks.clear_bit(source);
+
+        (get_bishop_attacks(king_square, occupancy) & bishops).is_set()
+            || (get_rook_attacks(king_square, occupancy) & rooks).is_set()
+    }
+
+    /// Pieces of both sides attacking the square when only the squares in `occupancy` are
+    /// occupied, removing pieces from the occupancy reveals the sliders behind them.
+    pub fn attackers_to(&self, square: Square, occupancy: BitBoard) -> BitBoard {
+        let bishops = self.pieces[Pieces::WhiteBishop]
+            | self.pieces[Pieces::BlackBishop]
+            | self.pieces[Pieces::WhiteQueen]
+            | self.pieces[Pieces::BlackQueen];
+        let rooks = self.pieces[Pieces::WhiteRook]
+            | self.pieces[Pieces::BlackRook]
+            | self.pieces[Pieces::WhiteQueen]
+            | self.pieces[Pieces::BlackQueen];
+
+        let attackers = (attacks!(PAWN_ATTACKS)[Side::Black][square]
+            & self.pieces[Pieces::WhitePawn])
+            | (attacks!(PAWN_ATTACKS)[Side::White][square] & self.pieces[Pieces::BlackPawn])
+            | (attacks!(KNIGHT_ATTACKS)[square]
+                & (self.pieces[Pieces::WhiteKnight] | self.pieces[Pieces::BlackKnight]))
+            | (attacks!(KING_ATTACKS)[square]
+                & (self.pieces[Pieces::WhiteKing] | self.pieces[Pieces::BlackKing]))
+            | (get_bishop_attacks(square, occupancy) & bishops)
+            | (get_rook_attacks(square, occupancy) & rooks);
+
+        attackers & occupancy
+    }
+
+    /// Material the side to move wins (or loses, when negative) by playing the move and letting
+    /// both sides keep recapturing on the target square with their least valuable piece, each
+    /// side being free to stop whenever continuing would lose material.
+    pub fn static_exchange(&self, piece_move: Move) -> i32 {
+        let source = piece_move.source();
+        let target = piece_move.target();
+        let mut occupancy = self.occupancies[Side::Both];
+
+        let captured = if piece_move.is_en_passant() {
+            let captured_square = match self.side_to_move {
+                Side::White => target.one_backward(),
+                _ => target.one_forward(),
+            };
+            if let Some(square) = captured_square {
+                occupancy.clear_bit(square);
+            }
+            EXCHANGE_VALUES[PieceKind::Pawn as usize]
+        } else {
+            self.piece_kind_at(target)
+                .map_or(0, |kind| EXCHANGE_VALUES[kind as usize])
+        };
+
+        let promotion = piece_move.promotion();
+        let mut attacker_value = if promotion.is_promoting() {
+            EXCHANGE_VALUES[promotion.into_piece(self.side_to_move).kind() as usize]
+        } else {
+            EXCHANGE_VALUES[piece_move.piece().kind() as usize]
+        };
+
+        let mut gain = [0; 32];
+        gain[0] = captured + attacker_value - EXCHANGE_VALUES[piece_move.piece().kind() as usize];
+        occupancy.clear_bit(source);
+
+        let mut side = self.side_to_move.enemy();
+        let mut depth = 0;
+        loop {
+            depth += 1;
+            // what the side capturing next would end up with if the piece it takes isn't
+            // recaptured
+            gain[depth] = attacker_value - gain[depth - 1];
+            if depth == gain.len() - 1 {
+                break;
+            }
+
+            let attackers = self.attackers_to(target, occupancy);
+            let Some((square, kind)) = self.least_valuable_attacker(attackers, side) else {
+                break;
+            };
+
+            // the king can't recapture into a square the other side still defends
+            if kind == PieceKind::King {
+                occupancy.clear_bit(square);
+                let defenders =
+                    self.attackers_to(target, occupancy) & self.occupancies[side.enemy()];
+                if defenders.is_set() {
+                    break;
+                }
+            }
+
+            occupancy.clear_bit(square);
+            attacker_value = EXCHANGE_VALUES[kind as usize];
+            side = side.enemy();
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+
+        gain[0]
+    }
+
+    /// Kind of piece taken by a capture, which for en passant isn't on the target square.
+    pub fn captured_kind(&self, piece_move: Move) -> PieceKind {
+        match piece_move.is_en_passant() {
+            true => PieceKind::Pawn,
+            false => self
+                .piece_kind_at(piece_move.target())
+                .unwrap_or(PieceKind::Pawn),
+        }
+    }
+
+    fn piece_kind_at(&self, square: Square) -> Option<PieceKind> {
+        Pieces::range()
+            .find(|&idx| self.pieces[idx].get_bit(square).is_set())
+            .map(|idx| Pieces::from_usize_unchecked(idx).kind())
+    }
+
+    fn least_valuable_attacker(
+        &self,
+        attackers: BitBoard,
+        side: Side,
+    ) -> Option<(Square, PieceKind)> {
+        let pieces = match side {
+            Side::White => Pieces::white_pieces_range(),
+            _ => Pieces::black_pieces_range(),
+        };
+
+        pieces.into_iter().find_map(|idx| {
+            let candidates = attackers & self.pieces[idx];
+            candidates.is_set().then(|| {
+                (
+                    candidates.trailing_zeros(),
+                    Pieces::from_usize_unchecked(idx).kind(),
+                )
+            })
+        })
+    }
+
+    /// Whether the move could have been generated on the current position, without checking if
+    /// it leaves the king in check.
+    ///
+    /// Moves that don't come from move generation, like the best move of a transposition table
+    /// entry or a killer move, may belong to a different position, and making them would corrupt
+    /// the board.
+    pub fn is_pseudo_legal(&self, piece_move: Move) -> bool {
+        let source = piece_move.source();
+        let target = piece_move.target();
+        let piece = piece_move.piece();
+        let side = self.side_to_move;
+
+        if piece.side() != side || self.pieces[piece].get_bit(source).is_empty() {
+            return false;
+        }
+
+        if self.occupancies[side].get_bit(target).is_set() {
+            return false;
+        }
+
+        let is_pawn = piece.kind() == PieceKind::Pawn;
+        if !is_pawn && (piece_move.promotion().is_promoting() || piece_move.is_double_push()) {
+            return false;
+        }
+
+        if piece_move.is_en_passant() {
+            return is_pawn
+                && piece_move.is_capture()
+                && target == self.en_passant
+                && attacks!(PAWN_ATTACKS)[side][source]
+                    .get_bit(target)
+                    .is_set();
+        }
+
+        if piece_move.is_capture() != self.occupancies[side.enemy()].get_bit(target).is_set() {
+            return false;
+        }
+
+        if piece_move.is_castling() {
+            return piece.kind() == PieceKind::King && self.can_castle(source, target);
+        }
+
+        let occupancy = self.occupancies[Side::Both];
+        match piece.kind() {
+            PieceKind::Pawn => self.is_pawn_move_pseudo_legal(piece_move),
+            PieceKind::Knight => attacks!(KNIGHT_ATTACKS)[source].get_bit(target).is_set(),
+            PieceKind::King => attacks!(KING_ATTACKS)[source].get_bit(target).is_set(),
+            PieceKind::Bishop => get_bishop_attacks(source, occupancy)
+                .get_bit(target)
+                .is_set(),
+            PieceKind::Rook => get_rook_attacks(source, occupancy).get_bit(target).is_set(),
+            PieceKind::Queen => get_queen_attacks(source, occupancy)
+                .get_bit(target)
+                .is_set(),
+        }
+    }
+
+    fn is_pawn_move_pseudo_legal(&self, piece_move: Move) -> bool {
+        let source = piece_move.source();
+        let target = piece_move.target();
+
+        let (initial_rank, last_rank) = match self.side_to_move {
+            Side::White => (Rank::Second, Rank::Eighth),
+            Side::Black => (Rank::Seventh, Rank::First),
+            _ => unreachable!(),
+        };
+
+        if piece_move.promotion().is_promoting() != target.is_on_rank(last_rank) {
+            return false;
+        }
+
+        if piece_move.is_capture() {
+            return attacks!(PAWN_ATTACKS)[self.side_to_move][source]
+                .get_bit(target)
+                .is_set();
+        }
+
+        let forward = |square: Square| match self.side_to_move {
+            Side::White => square.one_forward(),
+            Side::Black => square.one_backward(),
+            _ => unreachable!(),
+        };
+
+        let Some(one_forward) = forward(source) else {
+            return false;
+        };
+
+        if !piece_move.is_double_push() {
+            return one_forward == target;
+        }
+
+        source.is_on_rank(initial_rank)
+            && forward(one_forward) == Some(target)
+            && self.occupancies[Side::Both].get_bit(one_forward).is_empty()
+    }
+
+    fn can_castle(&self, source: Square, target: Square) -> bool {
+        // The squares between the king and the rook must be empty, and the king can't castle out
+        // of, or through a check.
+        let (king_square, right, free_squares, passing_square) = match (self.side_to_move, target) {
+            (Side::White, Square::G1) => (
+                Square::E1,
+                CastlingRights::WHITE_K,
+                &[Square::F1, Square::G1][..],
+                Square::F1,
+            ),
+            (Side::White, Square::C1) => (
+                Square::E1,
+                CastlingRights::WHITE_Q,
+                &[Square::D1, Square::C1, Square::B1][..],
+                Square::D1,
+            ),
+            (Side::Black, Square::G8) => (
+                Square::E8,
+                CastlingRights::BLACK_K,
+                &[Square::F8, Square::G8][..],
+                Square::F8,
+            ),
+            (Side::Black, Square::C8) => (
+                Square::E8,
+                CastlingRights::BLACK_Q,
+                &[Square::D8, Square::C8, Square::B8][..],
+                Square::D8,
+            ),
+            _ => return false,
+        };
+
+        let enemy = self.side_to_move.enemy();
+
+        source == king_square
+            && self.castling_rights.contains(right)
+            && free_squares
+                .iter()
+                .all(|&square| self.occupancies[Side::Both].get_bit(square).is_empty())
+            && !self.is_square_attacked(king_square, enemy)
+            && !self.is_square_attacked(passing_square, enemy)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use milky_bitboard::{PromotionPieces, Side, Square};
+    use milky_fen::parse_fen_string;
+
+    use crate::Milky;
+    use crate::moves::{Movable, MoveKind, generate_moves};
+
+    pub(crate) struct QuietMove(pub Square, pub Square);
+
+    impl Movable for QuietMove {
+        fn source(&self) -> Square {
+            self.0
+        }
+
+        fn target(&self) -> Square {
+            self.1
+        }
+
+        fn promotion(&self) -> PromotionPieces {
+            PromotionPieces::NoPromotion
+        }
+    }
+
+    /// Loads the starting position followed by the given moves, like a UCI `position` command.
+    pub(crate) fn load_startpos(milky: &mut Milky, moves: &str) {
+        let fen = parse_fen_string("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        milky.load_position(fen.unwrap());
+        milky.load_moves(moves.split_whitespace().map(|mv| {
+            QuietMove(
+                Square::from_algebraic_str(&mv[0..2]).unwrap(),
+                Square::from_algebraic_str(&mv[2..4]).unwrap(),
+            )
+        }));
+    }
+
+    fn play(moves: &str) -> Milky {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, moves);
+        milky
+    }
+
+    fn is_repetition(milky: &Milky) -> bool {
+        milky.is_repetition()
+    }
+
+    #[test]
+    fn test_pseudo_legal_matches_move_generation() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1",
+            "rnbqkb1r/pp1p1ppp/5n2/2pPp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 4",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+
+        let mut generated = vec![];
+        for fen in fens {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            generate_moves(&mut milky.move_ctx(), MoveKind::AllMoves);
+            generated.push(milky.search_state().moves().copied().collect::<Vec<_>>());
+        }
+
+        // moves from every position are checked against every other position, only the ones
+        // generated for a position can be pseudo legal on it
+        let every_move = generated.concat();
+        for (fen, moves) in fens.iter().zip(&generated) {
+            milky.load_position(parse_fen_string(fen).unwrap());
+
+            for piece_move in &every_move {
+                assert_eq!(
+                    milky.board_state().is_pseudo_legal(*piece_move),
+                    moves.contains(piece_move),
+                    "{fen} {piece_move}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_detects_repetition() {
+        assert!(is_repetition(&play("g1f3 g8f6 f3g1 f6g8")));
+        assert!(is_repetition(&play("e2e3 e7e6 g1f3 g8f6 f3g1 f6g8")));
+        assert!(!is_repetition(&play("g1f3 g8f6 f3g1")));
+    }
+
+    #[test]
+    #[cfg(feature = "search")]
+    fn test_detects_upcoming_repetition() {
+        // the game is searched from the position after `root_moves` of its moves
+        let upcoming = |moves: &str, root_moves: usize| {
+            let milky = play(moves);
+            let search_path = &milky.game_history().positions()[root_moves..];
+            crate::cuckoo::has_upcoming_repetition(
+                milky.board_state(),
+                search_path,
+                milky.zobrist().position,
+            )
+        };
+
+        // black can go back to the position after b8c6 with the knight
+        assert!(upcoming("b1c3 b8c6 g1f3 g8f6 f3g1", 1));
+        // cycles reaching the root or the game history before it are not considered
+        assert!(!upcoming("b1c3 b8c6 g1f3 g8f6 f3g1", 2));
+
+        // black can still go back with the knight, but a pawn move in the way stops the cycle
+        assert!(!upcoming("b1c3 b8c6 g1f3 g8f6 f3g1 e7e6", 1));
+    }
+
+    #[test]
+    fn test_en_passant_square_is_only_kept_when_capturable() {
+        let milky = play("e2e4");
+        assert_eq!(milky.board_state().en_passant, Square::OffBoard);
+
+        // the same position loaded from a FEN naming the square hashes the same
+        let mut loaded = Milky::new();
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        loaded.load_position(parse_fen_string(fen).unwrap());
+        assert_eq!(loaded.board_state().en_passant, Square::OffBoard);
+        assert_eq!(loaded.zobrist().position, milky.zobrist().position);
+
+        let milky = play("e2e4 a7a6 e4e5 d7d5");
+        assert_eq!(milky.board_state().en_passant, Square::D6);
+    }
+
+    #[test]
+    fn test_non_pawn_material() {
+        let mut milky = Milky::new();
+        let fen = "8/5k2/4pp2/8/8/2N5/4PP2/5K2 w - - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        assert!(milky.board_state().has_non_pawn_material(Side::White));
+        assert!(!milky.board_state().has_non_pawn_material(Side::Black));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let mut milky = Milky::new();
+        let mut insufficient = |fen| {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            milky.board_state().is_insufficient_material()
+        };
+
+        assert!(insufficient("8/5k2/8/8/8/8/8/5K2 w - - 0 1"));
+        assert!(insufficient("8/5k2/8/8/8/2N5/8/5K2 w - - 0 1"));
+        assert!(insufficient("8/5k2/8/8/8/8/8/2B2K2 w - - 0 1"));
+        // every bishop on a dark square
+        assert!(insufficient("8/b4k2/8/8/8/8/8/2B2K2 w - - 0 1"));
+        assert!(insufficient("b7/5k2/8/8/8/8/6B1/5K1B w - - 0 1"));
+
+        // bishops of both colors, two knights or a pawn can still mate
+        assert!(!insufficient("8/3b1k2/8/8/8/8/8/2B2K2 w - - 0 1"));
+        assert!(!insufficient("8/5k2/8/8/8/2NN4/8/5K2 w - - 0 1"));
+        assert!(!insufficient("8/5k2/8/8/8/2N5/8/2B2K2 w - - 0 1"));
+        assert!(!insufficient("8/5k2/8/8/8/8/4P3/5K2 w - - 0 1"));
+        assert!(!insufficient("8/5k2/8/8/8/8/8/4RK2 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_repetition_scan_stops_at_irreversible_moves() {
+        // the knights return home, but the pawn moves in between make it a different position
+        assert!(!is_repetition(&play("g1f3 g8f6 f3g1 e7e6 e2e3 f6g8")));
+
+        let mut milky = play("g1f3 g8f6 f3g1 f6g8");
+        milky.board_state_mut().fifty_move_counter = 3;
+        assert!(!is_repetition(&milky));
+    }
+
+    #[test]
+    fn test_static_exchange() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        let cases = [
+            // undefended pawn
+            (
+                "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+                "e1e5",
+                100,
+            ),
+            // the knight is lost for a pawn after the long exchange on e5
+            (
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5",
+                -200,
+            ),
+            // queen takes a pawn defended by a pawn
+            ("4k3/8/3p4/4p3/8/8/8/4Q1K1 w - - 0 1", "e1e5", -800),
+            // the king can recapture, unless the square is still defended
+            ("3r3k/8/8/8/8/8/8/3RK3 b - - 0 1", "d8d1", 0),
+            ("3r3k/8/8/8/6b1/8/8/3RK3 b - - 0 1", "d8d1", 500),
+            // en passant and promotions
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 1300),
+            ("rr2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 400),
+        ];
+
+        for (fen, uci, expected) in cases {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            generate_moves(&mut milky.move_ctx(), MoveKind::AllMoves);
+            let piece_move = *milky
+                .search_state()
+                .moves()
+                .find(|m| m.to_string() == uci)
+                .unwrap();
+
+            assert_eq!(
+                milky.board_state().static_exchange(piece_move),
+                expected,
+                "{fen} {uci}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_gives_check() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        let cases = [
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8", true),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a7", false),
+            ("4k3/8/8/8/8/3N4/8/4K3 w - - 0 1", "d3f4", false),
+            ("4k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "b5d6", true),
+            ("4k3/8/8/1N6/8/8/8/4K3 w - - 0 1", "b5c7", true),
+            ("4k3/3P4/8/8/8/8/8/4K3 w - - 0 1", "d7d8q", true),
+            ("4k3/8/5P2/8/8/8/8/4K3 w - - 0 1", "f6f7", true),
+            // discovered checks
+            ("4k3/8/8/4N3/8/8/8/4RK2 w - - 0 1", "e5c4", true),
+            ("4k3/8/8/8/8/4P3/8/4RK2 w - - 0 1", "e3e4", false),
+            ("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1", false),
+        ];
+
+        for (fen, uci, expected) in cases {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            generate_moves(&mut milky.move_ctx(), MoveKind::AllMoves);
+            let piece_move = *milky
+                .search_state()
+                .moves()
+                .find(|m| m.to_string() == uci)
+                .unwrap();
+
+            assert_eq!(
+                milky.board_state().gives_check(piece_move),
+                expected,
+                "{fen} {uci}"
+            );
+        }
+    }
 }
@@ -0,0 +1,170 @@
+use std::sync::OnceLock;
+
+use milky_bitboard::{BitBoard, PieceKind, Pieces, Side, Square};
+
+use crate::board::BoardState;
+use crate::zobrist::{Zobrist, ZobristKey};
+use crate::{
+    compute_bishop_attacks, compute_king_attacks, compute_knight_attacks, compute_rook_attacks,
+};
+
+const CUCKOO_SIZE: usize = 8192;
+
+static CUCKOO_TABLE: OnceLock<Box<[CuckooEntry; CUCKOO_SIZE]>> = OnceLock::new();
+
+/// A reversible move stored by the zobrist difference between the positions before and after it.
+#[derive(Debug, Default, Clone, Copy)]
+struct CuckooEntry {
+    key: ZobristKey,
+    /// Squares strictly between source and target, which must be empty for the move to be
+    /// playable.
+    between: BitBoard,
+}
+
+fn first_hash(key: ZobristKey) -> usize {
+    (key.inner() & (CUCKOO_SIZE as u64 - 1)) as usize
+}
+
+fn second_hash(key: ZobristKey) -> usize {
+    ((key.inner() >> 16) & (CUCKOO_SIZE as u64 - 1)) as usize
+}
+
+/// Squares a piece attacks on an otherwise empty board, except for the optional blocker.
+fn piece_attacks(kind: PieceKind, square: Square, blockers: BitBoard) -> BitBoard {
+    match kind {
+        PieceKind::Knight => compute_knight_attacks(square),
+        PieceKind::King => compute_king_attacks(square),
+        PieceKind::Bishop => compute_bishop_attacks(square, blockers),
+        PieceKind::Rook => compute_rook_attacks(square, blockers),
+        PieceKind::Queen => {
+            compute_bishop_attacks(square, blockers) | compute_rook_attacks(square, blockers)
+        }
+        PieceKind::Pawn => unreachable!(),
+    }
+}
+
+fn squares_between(kind: PieceKind, source: Square, target: Square) -> BitBoard {
+    // a queen move is either a rook or a bishop move, mixing both would also match squares that
+    // are only attacked through different lines
+    let orthogonal = source.rank() == target.rank() || source.file() == target.file();
+    let kind = match kind {
+        PieceKind::Queen if orthogonal => PieceKind::Rook,
+        PieceKind::Queen => PieceKind::Bishop,
+        kind => kind,
+    };
+
+    match kind {
+        PieceKind::Bishop | PieceKind::Rook => {
+            piece_attacks(kind, source, BitBoard::from(&[target][..]))
+                & piece_attacks(kind, target, BitBoard::from(&[source][..]))
+        }
+        _ => BitBoard::default(),
+    }
+}
+
+/// Builds the cuckoo table holding every reversible (non-pawn) move on an empty board.
+///
+/// The zobrist keys are deterministic, so the table built from a fresh [`Zobrist`] matches the
+/// keys of every board.
+pub(crate) fn init_cuckoo_table() {
+    CUCKOO_TABLE.get_or_init(|| {
+        let zobrist = Zobrist::new();
+        let mut table = Box::new([CuckooEntry::default(); CUCKOO_SIZE]);
+
+        for piece in Pieces::iter().filter(|piece| piece.kind() != PieceKind::Pawn) {
+            for source in Square::iter() {
+                let attacks = piece_attacks(piece.kind(), source, BitBoard::default());
+
+                for target in attacks.into_iter().filter(|&target| target > source) {
+                    let mut entry = CuckooEntry {
+                        key: zobrist.pieces_table[piece][source],
+                        between: squares_between(piece.kind(), source, target),
+                    };
+                    entry.key ^= zobrist.pieces_table[piece][target];
+                    entry.key ^= zobrist.side_key;
+
+                    // Keep kicking entries to their other slot until one lands on an empty slot
+                    let mut index = first_hash(entry.key);
+                    loop {
+                        std::mem::swap(&mut table[index], &mut entry);
+
+                        if entry.key == ZobristKey::default() {
+                            break;
+                        }
+
+                        index = match index == first_hash(entry.key) {
+                            true => second_hash(entry.key),
+                            false => first_hash(entry.key),
+                        };
+                    }
+                }
+            }
+        }
+
+        table
+    });
+}
+
+fn find_move(key: ZobristKey) -> Option<&'static CuckooEntry> {
+    let table = CUCKOO_TABLE.get().unwrap();
+
+    [first_hash(key), second_hash(key)]
+        .into_iter()
+        .map(|index| &table[index])
+        .find(|entry| entry.key == key)
+}
+
+/// Whether the side to move has a single reversible move that goes back to a position already
+/// seen in the search tree, which lets the search claim a draw before playing the cycle out.
+///
+/// `search_path` holds the positions played from the root, the root being the first one. Cycles
+/// reaching the root or positions played before it are ignored, as those need the position to
+/// have been repeated before to be an actual draw.
+pub(crate) fn has_upcoming_repetition(
+    board: &BoardState,
+    search_path: &[ZobristKey],
+    position: ZobristKey,
+) -> bool {
+    let reversible_plies = board.fifty_move_counter as usize;
+    let occupancy = board.occupancies[Side::Both];
+
+    // only an odd distance has the other side to move, and the closest cycle takes three plies
+    (3..=reversible_plies.min(search_path.len().saturating_sub(1)))
+        .step_by(2)
+        .any(|plies| {
+            let mut move_key = position;
+            move_key ^= search_path[search_path.len() - plies];
+
+            find_move(move_key).is_some_and(|entry| (entry.between & occupancy).is_empty())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cuckoo_table_holds_every_reversible_move() {
+        crate::init_static_members();
+        let table = CUCKOO_TABLE.get().unwrap();
+
+        let moves = table
+            .iter()
+            .filter(|entry| entry.key != ZobristKey::default())
+            .count();
+
+        assert_eq!(moves, 3668);
+    }
+
+    #[test]
+    fn test_squares_between() {
+        let between = squares_between(PieceKind::Queen, Square::A1, Square::D4);
+        assert_eq!(between, BitBoard::from(&[Square::B2, Square::C3][..]));
+
+        let between = squares_between(PieceKind::Queen, Square::A1, Square::A4);
+        assert_eq!(between, BitBoard::from(&[Square::A2, Square::A3][..]));
+
+        let between = squares_between(PieceKind::Knight, Square::B1, Square::C3);
+        assert_eq!(between, BitBoard::default());
+    }
+}
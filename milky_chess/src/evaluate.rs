@@ -1,19 +1,48 @@
-use milky_bitboard::{Move, Pieces, Side};
+#[cfg(feature = "search")]
+use milky_bitboard::Move;
+use milky_bitboard::geometry::{Board8x8, BoardGeometry};
+use milky_bitboard::{PieceKind, Pieces, Side, Square};
 
-use crate::board::{BoardState, get_bishop_attacks, get_queen_attacks};
-use crate::search::SearchState;
+use crate::board::{BoardState, get_bishop_attacks, get_queen_attacks, get_rook_attacks};
+use crate::pawn_hash::PawnEntry;
+use crate::search_state::SearchState;
 use crate::{
-    BLACK_PASSED_PAWNS_MASKS, FILE_MASKS, GamePhase, ISOLATED_PAWNS_MASKS, KING_ATTACKS,
-    WHITE_PASSED_PAWNS_MASKS, attacks,
+    BLACK_PASSED_PAWNS_MASKS, FILE_MASKS, GamePhase, ISOLATED_PAWNS_MASKS, KNIGHT_ATTACKS,
+    PAWN_ATTACKS, WHITE_PASSED_PAWNS_MASKS, attacks,
 };
 
-static PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 200];
+/// Bonus of a passed pawn in the opening and in the endgame, by the rank it stands on counted
+/// from its own side. Passed pawns are worth far more once the pieces that could stop them are
+/// traded off.
+#[rustfmt::skip]
+static PASSED_PAWN_BONUS: [[i32; 8]; 2] = [
+    [0, 0, 5, 10, 15, 25, 40, 0],
+    [0, 10, 15, 25, 45, 75, 120, 0],
+];
+
+/// Penalty of a pawn sharing its file with a friendly pawn, in the opening and in the endgame.
+static DOUBLED_PAWN_PENALTY: [i32; 2] = [-5, -10];
 
-static DOUBLE_PAWN_PENALTY: i32 = -10;
+/// Penalty of a pawn without friendly pawns on the files next to it, in the opening and in the
+/// endgame.
+static ISOLATED_PAWN_PENALTY: [i32; 2] = [-5, -10];
+
+/// Bonus of every safe square a piece can move to in the opening and in the endgame, by piece
+/// kind. Rooks and queens need open lines more once the board empties.
+#[rustfmt::skip]
+static MOBILITY_BONUS: [[i32; 6]; 2] = [
+    [0, 4, 5, 2, 1, 0],
+    [0, 4, 5, 4, 2, 0],
+];
 
-static ISOLATED_PAWN_PENALTY: i32 = -10;
+/// Safe squares a piece of each kind has on an average square, fewer are penalized and more are
+/// rewarded, so mobility doesn't add up to an extra piece value.
+static MOBILITY_BASELINE: [i32; 6] = [0, 4, 6, 7, 13, 0];
+#[allow(dead_code)]
 static SEMI_OPEN_FILE_SCORE: i32 = 10;
+#[allow(dead_code)]
 static OPEN_FILE_SCORE: i32 = 15;
+#[allow(dead_code)]
 static KING_SAFETY_BONUS: i32 = 5;
 
 /// # Most Valuable Victim / Less Valuable Attacker table
@@ -34,6 +63,7 @@ static KING_SAFETY_BONUS: i32 = 5;
 /// The table contains twice the size above to enable indexing with `Pieces`.
 ///
 #[rustfmt::skip]
+#[cfg(feature = "search")]
 static MVV_LVA: [[i32; 12]; 6] = [
     [105, 205, 305, 405, 505, 605,  105, 205, 305, 405, 505, 605],
     [104, 204, 304, 404, 504, 604,  104, 204, 304, 404, 504, 604],
@@ -54,12 +84,12 @@ static MATERIAL_SCORE: [[i32; 12]; 2] = [
 
 #[derive(Debug)]
 pub struct PositionalScore {
-    early: [i32; 64],
-    late: [i32; 64],
+    early: [i32; Board8x8::SQUARES],
+    late: [i32; Board8x8::SQUARES],
 }
 
 impl std::ops::Index<GamePhase> for PositionalScore {
-    type Output = [i32; 64];
+    type Output = [i32; Board8x8::SQUARES];
 
     fn index(&self, index: GamePhase) -> &Self::Output {
         match index {
@@ -210,12 +240,77 @@ static TAPERED_KING_SCORE: PositionalScore = PositionalScore {
         -18,  -4,  21,  24,  27,  23,   9, -11,
         -19,  -3,  11,  21,  23,  16,   7,  -9,
         -27, -11,   4,  13,  14,   4,  -5, -17,
-        -53, -34, -21, -11, -28, -14, -24, -430
+        -53, -34, -21, -11, -28, -14, -24, -43,
     ],
 };
 
+/// Largest bonus (or penalty) a piece-square table entry is expected to hold, anything above it
+/// is very likely a typo.
+const MAX_POSITIONAL_SCORE: i32 = 200;
+
+/// Checks the evaluation tables for values that are very likely typos, like an entry with an
+/// extra digit, or tables that stopped mirroring each other between sides.
+pub(crate) fn validate_eval_tables() -> Result<(), String> {
+    let tables = [
+        ("pawn", &TAPERED_PAWN_SCORE),
+        ("knight", &TAPERED_KNIGHT_SCORE),
+        ("bishop", &TAPERED_BISHOP_SCORE),
+        ("rook", &TAPERED_ROOK_SCORE),
+        ("queen", &TAPERED_QUEEN_SCORE),
+        ("king", &TAPERED_KING_SCORE),
+    ];
+
+    for (name, table) in tables {
+        for phase in [GamePhase::Opening, GamePhase::Endgame] {
+            let outlier = table[phase]
+                .iter()
+                .position(|score| score.abs() > MAX_POSITIONAL_SCORE);
+
+            if let Some(square) = outlier {
+                return Err(format!(
+                    "{name} {phase:?} table has an outlier {} on index {square}",
+                    table[phase][square]
+                ));
+            }
+        }
+    }
+
+    // pawns can never stand on the first or last ranks
+    for phase in [GamePhase::Opening, GamePhase::Endgame] {
+        let table = &TAPERED_PAWN_SCORE[phase];
+        if table[..Board8x8::FILES]
+            .iter()
+            .chain(&table[Board8x8::SQUARES - Board8x8::FILES..])
+            .any(|&score| score != 0)
+        {
+            return Err(format!("pawn {phase:?} table must be zero on back ranks"));
+        }
+    }
+
+    for (phase, material) in MATERIAL_SCORE.iter().enumerate() {
+        let (white, black) = material.split_at(6);
+
+        if white
+            .iter()
+            .zip(black)
+            .any(|(white, black)| *white != -black)
+        {
+            return Err(format!(
+                "material scores for phase {phase} are not symmetric"
+            ));
+        }
+
+        if white.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(format!("material scores for phase {phase} are not ordered"));
+        }
+    }
+
+    Ok(())
+}
+
 pub struct EvalContext<'ctx> {
     pub board: &'ctx BoardState,
+    #[cfg_attr(not(feature = "search"), allow(dead_code))]
     pub search: &'ctx mut SearchState,
 }
 
@@ -259,8 +354,16 @@ pub fn evaluate_position(ctx: &mut EvalContext<'_>) -> i32 {
     let game_phase_score = get_game_phase_score(ctx);
     let game_phase = GamePhase::from_score(game_phase_score);
 
-    let mut score_opening = 0;
-    let mut score_endgame = 0;
+    let pawns = match ctx.search.pawn_hash.probe(ctx.board.pawn_key) {
+        Some(pawns) => pawns,
+        None => {
+            let pawns = evaluate_pawns(ctx.board);
+            ctx.search.pawn_hash.store(pawns);
+            pawns
+        }
+    };
+
+    let [mut score_opening, mut score_endgame] = pawns.score;
 
     for (idx, board) in ctx.board.pieces.into_iter().enumerate() {
         let piece = Pieces::from_usize_unchecked(idx);
@@ -277,65 +380,39 @@ pub fn evaluate_position(ctx: &mut EvalContext<'_>) -> i32 {
                 Pieces::WhitePawn | Pieces::BlackPawn => {
                     score_opening += sign * TAPERED_PAWN_SCORE[GamePhase::Opening][square_idx];
                     score_endgame += sign * TAPERED_PAWN_SCORE[GamePhase::Endgame][square_idx];
-
-                    // when there are more than one pawn on the same file, a small penalty is given
-                    // based on how many pawns are on that file, due to doubled pawns
-                    // let pawns_on_file = (board & FILE_MASKS[square.file() as usize]).count_ones();
-                    // if pawns_on_file > 1 {
-                    //     score += sign * DOUBLE_PAWN_PENALTY * pawns_on_file as i32;
-                    // }
-
-                    // when theres no adjacent friendly pawn, a small penalty is given to the
-                    // isolated pawn
-                    // let mask = board & ISOLATED_PAWNS_MASKS[square.file() as usize];
-                    // if mask.is_empty() {
-                    //     score += sign * ISOLATED_PAWN_PENALTY;
-                    // }
-
-                    // when there is no enemy pawn in the same or adjacent files in front of this
-                    // pawn, it is considered a passed pawn and gains a small bonus based on how
-                    // close it is from queening
-                    // let enemy_pawn_board = match ctx.board.side_to_move {
-                    //     Side::White => ctx.board.pieces[Pieces::BlackPawn],
-                    //     Side::Black => ctx.board.pieces[Pieces::WhitePawn],
-                    //     _ => unreachable!(),
-                    // };
-                    // let passed_pawn_mask = match ctx.board.side_to_move {
-                    //     Side::White => WHITE_PASSED_PAWNS_MASKS.get().unwrap()[square_idx],
-                    //     Side::Black => BLACK_PASSED_PAWNS_MASKS.get().unwrap()[square_idx],
-                    //     _ => unreachable!(),
-                    // };
-                    // let mask = enemy_pawn_board & passed_pawn_mask;
-                    // let bonus_rank = if is_white { square.rank() } else { square.mirror().rank() };
-                    // if mask.is_empty() {
-                    //     score += sign * PASSED_PAWN_BONUS[bonus_rank as usize];
-                    // }
                 }
                 Pieces::WhiteKnight | Pieces::BlackKnight => {
                     score_opening += sign * TAPERED_KNIGHT_SCORE[GamePhase::Opening][square_idx];
                     score_endgame += sign * TAPERED_KNIGHT_SCORE[GamePhase::Endgame][square_idx];
+
+                    let [opening, endgame] = mobility_score(ctx.board, &pawns, piece, square);
+                    score_opening += sign * opening;
+                    score_endgame += sign * endgame;
                 }
                 Pieces::WhiteBishop | Pieces::BlackBishop => {
                     score_opening += sign * TAPERED_BISHOP_SCORE[GamePhase::Opening][square_idx];
                     score_endgame += sign * TAPERED_BISHOP_SCORE[GamePhase::Endgame][square_idx];
 
-                    // small bonus to bishop mobility based on the amount of squares it control.
-                    // let occupancies = ctx.board.occupancies[Side::Both];
-                    // let available_squares = get_bishop_attacks(square, occupancies).count_ones();
-                    // score += sign * available_squares as i32;
+                    let [opening, endgame] = mobility_score(ctx.board, &pawns, piece, square);
+                    score_opening += sign * opening;
+                    score_endgame += sign * endgame;
                 }
                 Pieces::WhiteQueen | Pieces::BlackQueen => {
                     score_opening += sign * TAPERED_QUEEN_SCORE[GamePhase::Opening][square_idx];
                     score_endgame += sign * TAPERED_QUEEN_SCORE[GamePhase::Endgame][square_idx];
-                    // small bonus to queen mobility based on the amount of squares it control.
-                    // let occupancies = ctx.board.occupancies[Side::Both];
-                    // let available_squares = get_queen_attacks(square, occupancies).count_ones();
-                    // score += sign * available_squares as i32;
+
+                    let [opening, endgame] = mobility_score(ctx.board, &pawns, piece, square);
+                    score_opening += sign * opening;
+                    score_endgame += sign * endgame;
                 }
                 Pieces::WhiteRook | Pieces::BlackRook => {
                     score_opening += sign * TAPERED_ROOK_SCORE[GamePhase::Opening][square_idx];
                     score_endgame += sign * TAPERED_ROOK_SCORE[GamePhase::Endgame][square_idx];
 
+                    let [opening, endgame] = mobility_score(ctx.board, &pawns, piece, square);
+                    score_opening += sign * opening;
+                    score_endgame += sign * endgame;
+
                     // let pawn_board = match ctx.board.side_to_move {
                     //     Side::White => ctx.board.pieces[Pieces::WhitePawn],
                     //     Side::Black => ctx.board.pieces[Pieces::BlackPawn],
@@ -409,19 +486,124 @@ pub fn evaluate_position(ctx: &mut EvalContext<'_>) -> i32 {
     }
 }
 
+/// Evaluates the structure of every pawn on the board, which only depends on the pawns, so the
+/// result is cached by the pawn key of the board.
+pub(crate) fn evaluate_pawns(board: &BoardState) -> PawnEntry {
+    let mut entry = PawnEntry {
+        key: board.pawn_key,
+        ..Default::default()
+    };
+
+    for (side, pawn, sign) in [
+        (Side::White, Pieces::WhitePawn, 1),
+        (Side::Black, Pieces::BlackPawn, -1),
+    ] {
+        for square in board.pieces[pawn] {
+            let (score, passed) = pawn_structure_score(board, side, square);
+            entry.score[0] += sign * score[0];
+            entry.score[1] += sign * score[1];
+
+            if passed {
+                entry.passed[side].set_bit(square);
+            }
+            entry.attacks[side] |= attacks!(PAWN_ATTACKS)[side][square];
+        }
+    }
+
+    entry
+}
+
+/// Opening and endgame bonus of the knight, bishop, rook or queen on `square` for the squares it
+/// can safely move to: the ones without a friendly piece that no enemy pawn attacks, as a piece
+/// landing there is lost for a pawn.
+fn mobility_score(
+    board: &BoardState,
+    pawns: &PawnEntry,
+    piece: Pieces,
+    square: Square,
+) -> [i32; 2] {
+    let side = piece.side();
+    let occupancy = board.occupancies[Side::Both];
+    let attacked = match piece.kind() {
+        PieceKind::Knight => attacks!(KNIGHT_ATTACKS)[square],
+        PieceKind::Bishop => get_bishop_attacks(square, occupancy),
+        PieceKind::Rook => get_rook_attacks(square, occupancy),
+        PieceKind::Queen => get_queen_attacks(square, occupancy),
+        PieceKind::Pawn | PieceKind::King => unreachable!(),
+    };
+
+    let kind = piece.kind() as usize;
+    let safe = attacked & !board.occupancies[side] & !pawns.attacks[side.enemy()];
+    let squares = safe.count_ones() as i32 - MOBILITY_BASELINE[kind];
+    [
+        squares * MOBILITY_BONUS[0][kind],
+        squares * MOBILITY_BONUS[1][kind],
+    ]
+}
+
+/// Opening and endgame scores of the structure around the pawn of `side` on `square`, and
+/// whether the pawn is passed.
+///
+/// - Doubled pawns, sharing a file with a friendly pawn, block each other
+/// - Isolated pawns, without friendly pawns on the files next to them, can't be defended by pawns
+/// - Passed pawns, without enemy pawns in front of them on their file or the files next to it,
+///   only have pieces left to stop them, and are worth more the closer they are to promoting
+fn pawn_structure_score(board: &BoardState, side: Side, square: Square) -> ([i32; 2], bool) {
+    let (pawns, enemy_pawns, passed_masks, rank) = match side {
+        Side::White => (
+            board.pieces[Pieces::WhitePawn],
+            board.pieces[Pieces::BlackPawn],
+            attacks!(WHITE_PASSED_PAWNS_MASKS),
+            square.rank(),
+        ),
+        Side::Black => (
+            board.pieces[Pieces::BlackPawn],
+            board.pieces[Pieces::WhitePawn],
+            attacks!(BLACK_PASSED_PAWNS_MASKS),
+            square.mirror().rank(),
+        ),
+        _ => unreachable!(),
+    };
+    let file = square.file() as usize;
+
+    let doubled = (pawns & FILE_MASKS[file]).count_ones() > 1;
+    let isolated = (pawns & ISOLATED_PAWNS_MASKS[file]).is_empty();
+    let passed = (enemy_pawns & passed_masks[square as usize]).is_empty();
+
+    let score = [GamePhase::Opening, GamePhase::Endgame].map(|phase| {
+        let phase = phase as usize;
+        doubled as i32 * DOUBLED_PAWN_PENALTY[phase]
+            + isolated as i32 * ISOLATED_PAWN_PENALTY[phase]
+            + passed as i32 * PASSED_PAWN_BONUS[phase][rank as usize]
+    });
+
+    (score, passed)
+}
+
 /// Scores a move based on the following heuristics:
 ///
+/// - Hash move, the best move the transposition table holds for the position
 /// - PV move
-/// - Captures in MVV/LVA
+/// - Captures in MVV/LVA, refined by the capture history
 /// - 1st killer move
 /// - 2nd killer move
 /// - History moves
 /// - Unsorted moves
-pub fn score_move(ctx: &mut EvalContext<'_>, piece_move: Move) -> i32 {
+#[cfg(feature = "search")]
+pub fn score_move(ctx: &mut EvalContext<'_>, piece_move: Move, hash_move: Move) -> i32 {
+    const HASH_MOVE_SCORE: i32 = 30_000;
     const PV_MOVE_SCORE: i32 = 20_000;
     const MVV_LVA_BONUS: i32 = 10_000;
     const FIRST_KILLER_MOVE: i32 = 9_000;
     const SECOND_KILLER_MOVE: i32 = 8_000;
+    // MVV/LVA scores of different victims are 100 apart and the attackers of a victim span 5,
+    // so bounding the capture history below half the gap only reorders the captures of a same
+    // victim, the most valuable victims are still tried first.
+    const CAPTURE_HISTORY_LIMIT: i32 = 47;
+
+    if piece_move == hash_move {
+        return HASH_MOVE_SCORE;
+    }
 
     if ctx.search.score_pv && ctx.search.pv_table[0][ctx.board.ply] == piece_move {
         ctx.search.score_pv = false;
@@ -447,14 +629,403 @@ pub fn score_move(ctx: &mut EvalContext<'_>, piece_move: Move) -> i32 {
             .map(Pieces::from_usize_unchecked)
             .unwrap_or(Pieces::WhitePawn);
 
-        return MVV_LVA[attacker.kind()][victim] + MVV_LVA_BONUS;
+        let history = ctx.search.capture_history(piece_move, victim.kind());
+        return MVV_LVA[attacker.kind()][victim]
+            + MVV_LVA_BONUS
+            + history.clamp(-CAPTURE_HISTORY_LIMIT, CAPTURE_HISTORY_LIMIT);
     }
 
-    if ctx.search.killer_moves[0][ctx.board.ply] == piece_move {
+    if ctx.search.stack[ctx.board.ply].killers[0] == piece_move {
         FIRST_KILLER_MOVE
-    } else if ctx.search.killer_moves[1][ctx.board.ply] == piece_move {
+    } else if ctx.search.stack[ctx.board.ply].killers[1] == piece_move {
         SECOND_KILLER_MOVE
     } else {
-        ctx.search.history_moves[piece_move.piece()][piece_move.target()]
+        ctx.search.quiet_history(ctx.board.ply, piece_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use milky_bitboard::BitBoard;
+    use milky_fen::parse_fen_string;
+
+    use super::*;
+    use crate::Milky;
+    use crate::board::tests::QuietMove;
+
+    /// Positions covering openings, middlegames and endgames. Whenever the evaluation changes on
+    /// purpose, the golden values must be regenerated with `cargo insta review`.
+    static EVAL_CORPUS: &str = include_str!("../data/eval_corpus.epd");
+
+    fn evaluate_fen(milky: &mut Milky, fen: &str) -> i32 {
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.evaluate()
+    }
+
+    #[rustfmt::skip]
+    static PESTO_PAWN_EARLY: [i32; 64] = [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         98, 134,  61,  95,  68, 126,  34, -11,
+         -6,   7,  26,  31,  65,  56,  25, -20,
+        -14,  13,   6,  21,  23,  12,  17, -23,
+        -27,  -2,  -5,  12,  17,   6,  10, -25,
+        -26,  -4,  -4, -10,   3,   3,  33, -12,
+        -35,  -1, -20, -23, -15,  24,  38, -22,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_PAWN_LATE: [i32; 64] = [
+          0,   0,   0,   0,   0,   0,   0,   0,
+        178, 173, 158, 134, 147, 132, 165, 187,
+         94, 100,  85,  67,  56,  53,  82,  84,
+         32,  24,  13,   5,  -2,   4,  17,  17,
+         13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+          4,   7,  -6,   1,   0,  -5,  -1,  -8,
+         13,   8,   8,  10,  13,   0,   2,  -7,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_KNIGHT_EARLY: [i32; 64] = [
+        -167,  -89,  -34,  -49,   61,  -97,  -15, -107,
+         -73,  -41,   72,   36,   23,   62,    7,  -17,
+         -47,   60,   37,   65,   84,  129,   73,   44,
+          -9,   17,   19,   53,   37,   69,   18,   22,
+         -13,    4,   16,   13,   28,   19,   21,   -8,
+         -23,   -9,   12,   10,   19,   17,   25,  -16,
+         -29,  -53,  -12,   -3,   -1,   18,  -14,  -19,
+        -105,  -21,  -58,  -33,  -17,  -28,  -19,  -23,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_KNIGHT_LATE: [i32; 64] = [
+        -58, -38, -13, -28, -31, -27, -63, -99,
+        -25,  -8, -25,  -2,  -9, -25, -24, -52,
+        -24, -20,  10,   9,  -1,  -9, -19, -41,
+        -17,   3,  22,  22,  22,  11,   8, -18,
+        -18,  -6,  16,  25,  16,  17,   4, -18,
+        -23,  -3,  -1,  15,  10,  -3, -20, -22,
+        -42, -20, -10,  -5,  -2, -20, -23, -44,
+        -29, -51, -23, -15, -22, -18, -50, -64,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_BISHOP_EARLY: [i32; 64] = [
+        -29,   4, -82, -37, -25, -42,   7,  -8,
+        -26,  16, -18, -13,  30,  59,  18, -47,
+        -16,  37,  43,  40,  35,  50,  37,  -2,
+         -4,   5,  19,  50,  37,  37,   7,  -2,
+         -6,  13,  13,  26,  34,  12,  10,   4,
+          0,  15,  15,  15,  14,  27,  18,  10,
+          4,  15,  16,   0,   7,  21,  33,   1,
+        -33,  -3, -14, -21, -13, -12, -39, -21,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_BISHOP_LATE: [i32; 64] = [
+        -14, -21, -11,  -8,  -7,  -9, -17, -24,
+         -8,  -4,   7, -12,  -3, -13,  -4, -14,
+          2,  -8,   0,  -1,  -2,   6,   0,   4,
+         -3,   9,  12,   9,  14,  10,   3,   2,
+         -6,   3,  13,  19,   7,  10,  -3,  -9,
+        -12,  -3,   8,  10,  13,   3,  -7, -15,
+        -14, -18,  -7,  -1,   4,  -9, -15, -27,
+        -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_ROOK_EARLY: [i32; 64] = [
+         32,  42,  32,  51,  63,   9,  31,  43,
+         27,  32,  58,  62,  80,  67,  26,  44,
+         -5,  19,  26,  36,  17,  45,  61,  16,
+        -24, -11,   7,  26,  24,  35,  -8, -20,
+        -36, -26, -12,  -1,   9,  -7,   6, -23,
+        -45, -25, -16, -17,   3,   0,  -5, -33,
+        -44, -16, -20,  -9,  -1,  11,  -6, -71,
+        -19, -13,   1,  17,  16,   7, -37, -26,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_ROOK_LATE: [i32; 64] = [
+         13,  10,  18,  15,  12,  12,   8,   5,
+         11,  13,  13,  11,  -3,   3,   8,   3,
+          7,   7,   7,   5,   4,  -3,  -5,  -3,
+          4,   3,  13,   1,   2,   1,  -1,   2,
+          3,   5,   8,   4,  -5,  -6,  -8, -11,
+         -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+         -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+         -9,   2,   3,  -1,  -5, -13,   4, -20,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_QUEEN_EARLY: [i32; 64] = [
+        -28,   0,  29,  12,  59,  44,  43,  45,
+        -24, -39,  -5,   1, -16,  57,  28,  54,
+        -13, -17,   7,   8,  29,  56,  47,  57,
+        -27, -27, -16, -16,  -1,  17,  -2,   1,
+         -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+        -14,   2, -11,  -2,  -5,   2,  14,   5,
+        -35,  -8,  11,   2,   8,  15,  -3,   1,
+         -1, -18,  -9,  10, -15, -25, -31, -50,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_QUEEN_LATE: [i32; 64] = [
+         -9,  22,  22,  27,  27,  19,  10,  20,
+        -17,  20,  32,  41,  58,  25,  30,   0,
+        -20,   6,   9,  49,  47,  35,  19,   9,
+          3,  22,  24,  45,  57,  40,  57,  36,
+        -18,  28,  19,  47,  31,  34,  39,  23,
+        -16, -27,  15,   6,   9,  17,  10,   5,
+        -22, -23, -30, -16, -16, -23, -36, -32,
+        -33, -28, -22, -43,  -5, -32, -20, -41,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_KING_EARLY: [i32; 64] = [
+        -65,  23,  16, -15, -56, -34,   2,  13,
+         29,  -1, -20,  -7,  -8,  -4, -38, -29,
+         -9,  24,   2, -16, -20,   6,  22, -22,
+        -17, -20, -12, -27, -30, -25, -14, -36,
+        -49,  -1, -27, -39, -46, -44, -33, -51,
+        -14, -14, -22, -46, -44, -30, -15, -27,
+          1,   7,  -8, -64, -43, -16,   9,   8,
+        -15,  36,  12, -54,   8, -28,  24,  14,
+    ];
+
+    #[rustfmt::skip]
+    static PESTO_KING_LATE: [i32; 64] = [
+        -74, -35, -18, -18, -11,  15,   4, -17,
+        -12,  17,  14,  17,  17,  38,  23,  11,
+         10,  17,  23,  15,  20,  45,  44,  13,
+         -8,  22,  24,  27,  26,  33,  26,   3,
+        -18,  -4,  21,  24,  27,  23,   9, -11,
+        -19,  -3,  11,  21,  23,  16,   7,  -9,
+        -27, -11,   4,  13,  14,   4,  -5, -17,
+        -53, -34, -21, -11, -28, -14, -24, -43,
+    ];
+
+    fn flip_fen(fen: &str) -> String {
+        let mut parts = fen.split_whitespace();
+        let swap_case = |ch: char| match ch.is_ascii_uppercase() {
+            true => ch.to_ascii_lowercase(),
+            false => ch.to_ascii_uppercase(),
+        };
+
+        let board = parts
+            .next()
+            .unwrap()
+            .split('/')
+            .rev()
+            .collect::<Vec<_>>()
+            .join("/");
+        let board = board.chars().map(swap_case).collect::<String>();
+        let side = if parts.next().unwrap() == "w" { "b" } else { "w" };
+        let castling = parts
+            .next()
+            .unwrap()
+            .chars()
+            .map(swap_case)
+            .collect::<String>();
+        let en_passant = match parts.next().unwrap() {
+            "-" => String::from("-"),
+            square => square
+                .chars()
+                .map(|ch| match ch {
+                    '3' => '6',
+                    '6' => '3',
+                    ch => ch,
+                })
+                .collect::<String>(),
+        };
+
+        format!("{board} {side} {castling} {en_passant}")
+    }
+
+    #[test]
+    fn test_tables_match_pesto_reference() {
+        let tables = [
+            (&TAPERED_PAWN_SCORE, &PESTO_PAWN_EARLY, &PESTO_PAWN_LATE),
+            (
+                &TAPERED_KNIGHT_SCORE,
+                &PESTO_KNIGHT_EARLY,
+                &PESTO_KNIGHT_LATE,
+            ),
+            (
+                &TAPERED_BISHOP_SCORE,
+                &PESTO_BISHOP_EARLY,
+                &PESTO_BISHOP_LATE,
+            ),
+            (&TAPERED_ROOK_SCORE, &PESTO_ROOK_EARLY, &PESTO_ROOK_LATE),
+            (&TAPERED_QUEEN_SCORE, &PESTO_QUEEN_EARLY, &PESTO_QUEEN_LATE),
+            (&TAPERED_KING_SCORE, &PESTO_KING_EARLY, &PESTO_KING_LATE),
+        ];
+
+        for (table, early, late) in tables {
+            assert_eq!(&table[GamePhase::Opening], early);
+            assert_eq!(&table[GamePhase::Endgame], late);
+        }
+
+        assert_eq!(MATERIAL_SCORE[0][0..6], [82, 337, 365, 477, 1025, 12000]);
+        assert_eq!(MATERIAL_SCORE[1][0..6], [94, 281, 297, 512, 936, 12000]);
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let mut structure = |fen: &str, side, square| {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            pawn_structure_score(milky.board_state(), side, square)
+        };
+
+        // isolated, and stopped by the pawn on the file next to it
+        let fen = "4k3/p7/8/8/8/8/1P6/4K3 w - - 0 1";
+        assert_eq!(structure(fen, Side::White, Square::B2), ([-5, -10], false));
+        assert_eq!(structure(fen, Side::Black, Square::A7), ([-5, -10], false));
+
+        // isolated but passed, on its fifth rank
+        let fen = "4k3/8/8/3P4/8/8/8/4K3 w - - 0 1";
+        assert_eq!(structure(fen, Side::White, Square::D5), ([10, 35], true));
+
+        // doubled and passed, but defended from the file next to it
+        let fen = "4k3/8/8/8/8/2P5/2PP4/4K3 w - - 0 1";
+        assert_eq!(structure(fen, Side::White, Square::C3), ([0, 5], true));
+        assert_eq!(structure(fen, Side::White, Square::D2), ([0, 10], true));
+    }
+
+    #[test]
+    fn test_mobility() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let mut mobility = |fen: &str, piece, square| {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            let pawns = evaluate_pawns(milky.board_state());
+            mobility_score(milky.board_state(), &pawns, piece, square)
+        };
+
+        // d6 and f6 are attacked by the pawn on e7, f2 holds a friendly pawn
+        let fen = "4k3/4p3/8/8/4N3/8/5P2/4K3 w - - 0 1";
+        assert_eq!(mobility(fen, Pieces::WhiteKnight, Square::E4), [4, 4]);
+
+        // the whole a-file and the first rank up to the king
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(mobility(fen, Pieces::WhiteRook, Square::A1), [6, 12]);
+
+        // a bishop shut in by its own pawns
+        let fen = "2b1k3/1p1p4/8/8/8/8/8/4K3 b - - 0 1";
+        assert_eq!(mobility(fen, Pieces::BlackBishop, Square::C8), [-30, -30]);
+    }
+
+    #[test]
+    fn test_pawn_structures_are_cached_by_pawn_key() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let fen = "4k3/p4p2/8/8/8/8/4PP2/4K3 w - - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        let key = milky.board_state().pawn_key;
+
+        let pawns = evaluate_pawns(milky.board_state());
+        assert_eq!(pawns.key, key);
+        assert_eq!(pawns.passed[Side::White], BitBoard::default());
+        let mut passed = BitBoard::default();
+        passed.set_bit(Square::A7);
+        assert_eq!(pawns.passed[Side::Black], passed);
+
+        let score = milky.evaluate();
+        assert_eq!(milky.search_state().pawn_hash.probe(key), Some(pawns));
+
+        // a king move keeps the pawn key, and the structure is read back
+        milky.load_moves([QuietMove(Square::E1, Square::D1)].into_iter());
+        assert_eq!(milky.board_state().pawn_key, key);
+        assert_ne!(milky.evaluate(), score);
+
+        milky.load_moves(
+            [
+                QuietMove(Square::E8, Square::D8),
+                QuietMove(Square::E2, Square::E4),
+            ]
+            .into_iter(),
+        );
+        assert_ne!(milky.board_state().pawn_key, key);
+    }
+
+    #[test]
+    fn test_eval_tables_are_sane() {
+        assert_eq!(validate_eval_tables(), Ok(()));
+    }
+
+    #[test]
+    fn test_eval_is_color_symmetric() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        for fen in EVAL_CORPUS.lines() {
+            let flipped = flip_fen(fen);
+            assert_eq!(
+                evaluate_fen(&mut milky, fen),
+                evaluate_fen(&mut milky, &flipped),
+                "{fen} / {flipped}"
+            );
+        }
+
+        let fen = "4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1";
+        assert_eq!(flip_fen(fen), "4k3/8/8/4pP2/8/8/8/4K3 w - e6");
+    }
+
+    #[test]
+    fn test_eval_corpus() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let mut result = String::new();
+
+        for fen in EVAL_CORPUS.lines() {
+            writeln!(result, "{fen} => {}", evaluate_fen(&mut milky, fen)).unwrap();
+        }
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[test]
+    #[cfg(feature = "search")]
+    fn test_hash_move_is_scored_first() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        let mut ctx = milky.move_ctx();
+        crate::generate_moves(&mut ctx, crate::moves::MoveKind::AllMoves);
+        let moves = ctx.search.moves().copied().collect::<Vec<_>>();
+        let hash_move = *moves.iter().find(|m| !m.is_capture()).unwrap();
+
+        let mut eval_ctx = EvalContext {
+            board: ctx.board,
+            search: ctx.search,
+        };
+        let hash_score = score_move(&mut eval_ctx, hash_move, hash_move);
+        for piece_move in moves.into_iter().filter(|&m| m != hash_move) {
+            assert!(score_move(&mut eval_ctx, piece_move, hash_move) < hash_score);
+        }
+    }
+
+    #[test]
+    fn test_eval_is_history_independent() {
+        crate::init_static_members();
+        let fens = EVAL_CORPUS.lines().collect::<Vec<_>>();
+        let fresh_scores = fens
+            .iter()
+            .map(|fen| evaluate_fen(&mut Milky::new(), fen))
+            .collect::<Vec<_>>();
+
+        // evaluating every position on the same engine, in reverse order, must not be affected
+        // by whatever was evaluated before
+        let mut milky = Milky::new();
+        for (fen, expected) in fens.iter().zip(fresh_scores).rev() {
+            assert_eq!(evaluate_fen(&mut milky, fen), expected, "{fen}");
+        }
     }
 }
@@ -0,0 +1,70 @@
+//! Whether the game on the board is still going, or how it ended, so programs built on the crate
+//! don't need to track the rules themselves and positions without a move are never searched.
+
+use milky_bitboard::Side;
+
+use crate::history::GameHistory;
+use crate::moves::MoveContext;
+use crate::san::has_legal_move;
+
+/// Plies without a capture or a pawn move after which the game is drawn.
+pub(crate) const FIFTY_MOVE_PLIES: u8 = 100;
+
+/// State of the game in the current position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    /// The side to move is checkmated, holding the side that lost.
+    Checkmate(Side),
+    /// The side to move has no legal move but isn't in check.
+    Stalemate,
+    /// The position happened for the third time with the same side to move.
+    DrawByRepetition,
+    /// A hundred plies went by without a capture or a pawn move.
+    DrawByFiftyMove,
+    /// Neither side has the material to ever checkmate.
+    DrawByInsufficientMaterial,
+}
+
+impl GameStatus {
+    pub fn is_over(&self) -> bool {
+        *self != GameStatus::Ongoing
+    }
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameStatus::Ongoing => write!(f, "ongoing"),
+            GameStatus::Checkmate(Side::White) => write!(f, "white is checkmated"),
+            GameStatus::Checkmate(_) => write!(f, "black is checkmated"),
+            GameStatus::Stalemate => write!(f, "stalemate"),
+            GameStatus::DrawByRepetition => write!(f, "draw by threefold repetition"),
+            GameStatus::DrawByFiftyMove => write!(f, "draw by the fifty move rule"),
+            GameStatus::DrawByInsufficientMaterial => write!(f, "draw by insufficient material"),
+        }
+    }
+}
+
+pub(crate) fn game_status(ctx: &mut MoveContext<'_>, game_history: &GameHistory) -> GameStatus {
+    if has_legal_move(ctx) {
+        if ctx.board.fifty_move_counter >= FIFTY_MOVE_PLIES {
+            return GameStatus::DrawByFiftyMove;
+        }
+
+        let fifty_move_counter = ctx.board.fifty_move_counter;
+        if game_history.repetitions(ctx.zobrist.position, fifty_move_counter) >= 2 {
+            return GameStatus::DrawByRepetition;
+        }
+
+        return match ctx.board.is_insufficient_material() {
+            true => GameStatus::DrawByInsufficientMaterial,
+            false => GameStatus::Ongoing,
+        };
+    }
+
+    match ctx.board.in_check() {
+        true => GameStatus::Checkmate(ctx.board.side_to_move),
+        false => GameStatus::Stalemate,
+    }
+}
@@ -0,0 +1,72 @@
+use crate::zobrist::ZobristKey;
+
+/// Keys of the positions played in the game before the current one, oldest first.
+///
+/// The history only changes when moves are played on the game, through
+/// [`crate::Milky::load_moves`]. The positions a search plays through are kept apart, on top of
+/// it, so a search can never leave the game history out of sync with the board.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameHistory {
+    positions: Vec<ZobristKey>,
+}
+
+impl GameHistory {
+    pub const fn new() -> Self {
+        Self { positions: vec![] }
+    }
+
+    pub fn push(&mut self, position: ZobristKey) {
+        self.positions.push(position);
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    pub fn positions(&self) -> &[ZobristKey] {
+        &self.positions
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Times `position` already happened in the game with the same side to move, only counting
+    /// back to the last irreversible move like [`GameHistory::is_repetition`].
+    pub fn repetitions(&self, position: ZobristKey, fifty_move_counter: u8) -> usize {
+        self.positions
+            .iter()
+            .rev()
+            .take(fifty_move_counter as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == position)
+            .count()
+    }
+
+    /// Whether `position` already happened with the same side to move, in the game or along
+    /// `path`, the positions played after the game history like the ones of a search.
+    ///
+    /// Only positions since the last irreversible move (tracked by the fifty move counter) can
+    /// repeat, so the scan walks back two plies at a time and stops there instead of going
+    /// through the whole game.
+    pub fn is_repetition(
+        &self,
+        path: &[ZobristKey],
+        position: ZobristKey,
+        fifty_move_counter: u8,
+    ) -> bool {
+        self.positions
+            .iter()
+            .chain(path)
+            .rev()
+            .take(fifty_move_counter as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&key| key == position)
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use milky_bitboard::Side;
+
+use crate::search_state::MATE_LOWER_BOUND;
+use crate::zobrist::ZobristKey;
+
+/// Scores beyond this are treated as a won (or lost) game when guessing the outcome of a game.
+const OUTCOME_SCORE: i32 = 400;
+
+/// How far the score of a position has to be from the outcome of the game for it to be learned.
+const DISAGREEMENT: i32 = 150;
+
+/// Largest correction applied to a position after a single game, so one unlucky game can't
+/// flip the engine's opinion about a position.
+const MAX_CORRECTION: i32 = 50;
+
+/// Score and depth stored for a learned position, written to the transposition table before
+/// every search so the search trusts it over its own evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LearnedEntry {
+    pub score: i32,
+    pub depth: u8,
+}
+
+/// Root position searched during the current game.
+#[derive(Debug, Clone, Copy)]
+struct SearchedPosition {
+    key: ZobristKey,
+    side: Side,
+    score: i32,
+    depth: u8,
+}
+
+/// Persistent memory of positions where the search turned out to be wrong.
+///
+/// The root of every search is recorded during a game. UCI never tells the engine how a game
+/// ended, so once it is over the score of the last search stands for its outcome, and every
+/// position whose score disagreed badly with it gets a small correction towards it. The learned
+/// scores are saved to a file, making the engine avoid lines it misjudged in earlier games.
+#[derive(Debug, Default)]
+pub(crate) struct Learning {
+    entries: HashMap<ZobristKey, LearnedEntry>,
+    game: Vec<SearchedPosition>,
+}
+
+impl Learning {
+    /// Loads the positions learned in previous sessions, a missing file means nothing was learned
+    /// yet.
+    ///
+    /// Every line of the file holds a position key in hex, the depth and the score of the
+    /// position.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+
+        let mut learning = Self::default();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let Some((key, entry)) = parse_entry(&line) else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("malformed learning entry: {line}"),
+                ));
+            };
+
+            learning.entries.insert(key, entry);
+        }
+
+        Ok(learning)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| **key);
+
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        for (key, entry) in entries {
+            writeln!(
+                writer,
+                "{:016x} {} {}",
+                key.inner(),
+                entry.depth,
+                entry.score
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (ZobristKey, LearnedEntry)> + '_ {
+        self.entries.iter().map(|(key, entry)| (*key, *entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Records the result of a search on a position of the current game, from the point of view
+    /// of the side to move.
+    pub fn record(&mut self, key: ZobristKey, side: Side, score: i32, depth: u8) {
+        if depth > 0 {
+            self.game.push(SearchedPosition {
+                key,
+                side,
+                score,
+                depth,
+            });
+        }
+    }
+
+    /// Learns from the positions searched during the game that just ended, returning how many
+    /// positions were corrected.
+    pub fn finish_game(&mut self) -> usize {
+        let game = std::mem::take(&mut self.game);
+        let Some(last) = game.last() else {
+            return 0;
+        };
+
+        let mut learned = 0;
+        for position in &game {
+            if position.score.abs() >= MATE_LOWER_BOUND {
+                continue;
+            }
+
+            let outcome = if position.side == last.side { last.score } else { -last.score };
+            let error = outcome.clamp(-OUTCOME_SCORE, OUTCOME_SCORE)
+                - position.score.clamp(-OUTCOME_SCORE, OUTCOME_SCORE);
+            if error.abs() <= DISAGREEMENT {
+                continue;
+            }
+
+            let correction = (error / 4).clamp(-MAX_CORRECTION, MAX_CORRECTION);
+            let entry = self.entries.entry(position.key).or_insert(LearnedEntry {
+                score: position.score,
+                depth: position.depth,
+            });
+            entry.score += correction;
+            entry.depth = entry.depth.max(position.depth);
+            learned += 1;
+        }
+
+        learned
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(ZobristKey, LearnedEntry)> {
+    let mut parts = line.split_whitespace();
+    let key = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let depth = parts.next()?.parse().ok()?;
+    let score = parts.next()?.parse().ok()?;
+
+    Some((ZobristKey::from(key), LearnedEntry { score, depth }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_learns_positions_that_disagree_with_the_outcome() {
+        let mut learning = Learning::default();
+        // the engine was happy with its position, but ended up losing it
+        learning.record(ZobristKey::from(1), Side::White, 80, 10);
+        learning.record(ZobristKey::from(2), Side::White, -20, 10);
+        learning.record(ZobristKey::from(3), Side::Black, 300, 12);
+        learning.record(ZobristKey::from(4), Side::White, -600, 12);
+
+        assert_eq!(learning.finish_game(), 2);
+        let entries = learning.entries().collect::<HashMap<_, _>>();
+        assert_eq!(
+            entries[&ZobristKey::from(1)],
+            LearnedEntry {
+                score: 30,
+                depth: 10
+            }
+        );
+        assert_eq!(
+            entries[&ZobristKey::from(2)],
+            LearnedEntry {
+                score: -70,
+                depth: 10
+            }
+        );
+
+        // nothing is left to learn once the game is over
+        assert_eq!(learning.finish_game(), 0);
+    }
+
+    #[test]
+    fn test_learning_file_roundtrip() {
+        let path = std::env::temp_dir().join(format!("milky-learning-{}", std::process::id()));
+
+        let mut learning = Learning::default();
+        learning.record(ZobristKey::from(0xDEAD_BEEF), Side::White, 200, 9);
+        learning.record(ZobristKey::from(7), Side::Black, 0, 9);
+        learning.finish_game();
+        learning.save(&path).unwrap();
+
+        let loaded = Learning::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut expected = learning.entries().collect::<Vec<_>>();
+        let mut actual = loaded.entries().collect::<Vec<_>>();
+        expected.sort_by_key(|(key, _)| *key);
+        actual.sort_by_key(|(key, _)| *key);
+        assert_eq!(actual, expected);
+        assert_eq!(loaded.len(), 1);
+
+        let missing = std::env::temp_dir().join("milky-learning-missing");
+        assert_eq!(Learning::load(&missing).unwrap().len(), 0);
+    }
+}
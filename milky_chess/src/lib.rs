@@ -1,25 +1,52 @@
+pub mod game_status;
+pub mod magic;
 pub mod moves;
+#[cfg(feature = "search")]
+pub mod observer;
+#[cfg(feature = "search")]
+pub mod options;
+pub mod perft;
+pub mod san;
+pub mod selftest;
+#[cfg(feature = "search")]
 pub mod time_manager;
 
 mod board;
+#[cfg(feature = "search")]
+mod cuckoo;
 mod evaluate;
-mod magic;
+mod history;
+#[cfg(feature = "search")]
+mod learning;
 mod milky;
+mod pawn_hash;
 mod random;
+#[cfg(feature = "search")]
 mod search;
+mod search_state;
+#[cfg(feature = "search")]
 mod transposition_table;
 mod zobrist;
 
 use std::sync::OnceLock;
 
-use board::BoardState;
+pub use board::BoardState;
 use evaluate::{ENDGAME_SCORE, OPENING_SCORE_THRESHOLD};
+pub use history::GameHistory;
 pub use milky::Milky;
+use milky_bitboard::geometry::{Board8x8, BoardGeometry};
 use milky_bitboard::{BitBoard, Side, Square};
 use moves::{Movable, MoveContext, generate_moves, make_move};
+#[cfg(feature = "search")]
+pub use search::{LMR_BASE, LMR_DIVISOR};
+#[cfg(feature = "search")]
+pub use transposition_table::DEFAULT_HASH_SIZE;
 
 pub static MAX_PLY: usize = 64;
-pub static MAX_REPETITIONS: usize = 1024;
+
+/// Amount of search threads used when no thread count is requested.
+#[cfg(feature = "search")]
+pub static DEFAULT_THREADS: usize = 1;
 
 static PAWN_ATTACKS: OnceLock<[[BitBoard; 64]; 2]> = OnceLock::new();
 static KNIGHT_ATTACKS: OnceLock<[BitBoard; 64]> = OnceLock::new();
@@ -38,6 +65,20 @@ macro_rules! attacks {
     ($attacks:ident) => {{ $attacks.get().unwrap() }};
 }
 
+/// Prints free form diagnostics to the GUI as UCI `info string` lines, taking the same arguments
+/// as `format!`.
+///
+/// The protocol reads a `string` field up to the end of the line, so every line of the text is
+/// sent with its own prefix instead of leaking into the stream as an unknown command.
+#[macro_export]
+macro_rules! info_string {
+    ($($arg:tt)*) => {{
+        for line in format!($($arg)*).lines() {
+            println!("info string {line}");
+        }
+    }};
+}
+
 /// Every bit is set except for the bits on the A file
 static EMPTY_A_FILE: BitBoard = BitBoard::new(0xFEFEFEFEFEFEFEFE);
 
@@ -271,6 +312,12 @@ pub fn init_static_members() {
     init_attack_tables();
     init_pawn_masks(Side::White);
     init_pawn_masks(Side::Black);
+    #[cfg(feature = "search")]
+    cuckoo::init_cuckoo_table();
+    #[cfg(feature = "search")]
+    search::init_reductions();
+
+    debug_assert_eq!(evaluate::validate_eval_tables(), Ok(()));
 }
 
 fn init_pawn_masks(side: Side) {
@@ -282,16 +329,16 @@ fn init_pawn_masks(side: Side) {
         masks[i] |= ISOLATED_PAWNS_MASKS[square.file() as usize];
         masks[i] |= FILE_MASKS[square.file() as usize];
 
-        for rank in 0..8 {
+        for rank in 0..Board8x8::RANKS {
             match side {
                 Side::White => {
                     if rank <= square.rank() as usize {
-                        masks[i] &= !RANK_MASKS[7 - rank];
+                        masks[i] &= !RANK_MASKS[Board8x8::RANKS - 1 - rank];
                     }
                 }
                 Side::Black => {
                     if rank >= square.rank() as usize {
-                        masks[i] &= !RANK_MASKS[7 - rank];
+                        masks[i] &= !RANK_MASKS[Board8x8::RANKS - 1 - rank];
                     }
                 }
                 _ => unreachable!(),
@@ -444,16 +491,15 @@ fn compute_bishop_attacks(square: Square, blockers: BitBoard) -> BitBoard {
         (-1, -1), // SW
     ];
 
-    let rank = square as i8 / 8;
-    let file = square as i8 % 8;
+    let rank = Board8x8::rank_of(square as usize) as i8;
+    let file = Board8x8::file_of(square as usize) as i8;
 
     for (rank_dir, file_dir) in directions {
         let mut r = rank + rank_dir;
         let mut f = file + file_dir;
 
-        while (0..8).contains(&r) && (0..8).contains(&f) {
-            let index = (r * 8 + f) as u64;
-            let square = Square::from_u64_unchecked(index);
+        while Board8x8::contains(r, f) {
+            let square = Square::from_rank_file(r as usize, f as usize);
             attacks.set_bit(square);
 
             if !(BitBoard::from_square(square) & blockers).is_empty() {
@@ -478,16 +524,15 @@ fn compute_rook_attacks(square: Square, blockers: BitBoard) -> BitBoard {
         (0, -1), // S
     ];
 
-    let rank = square as i8 / 8;
-    let file = square as i8 % 8;
+    let rank = Board8x8::rank_of(square as usize) as i8;
+    let file = Board8x8::file_of(square as usize) as i8;
 
     for (rank_dir, file_dir) in directions {
         let mut r = rank + rank_dir;
         let mut f = file + file_dir;
 
-        while (0..8).contains(&r) && (0..8).contains(&f) {
-            let index = (r * 8 + f) as u64;
-            let square = Square::from_u64_unchecked(index);
+        while Board8x8::contains(r, f) {
+            let square = Square::from_rank_file(r as usize, f as usize);
             attacks.set_bit(square);
 
             if !(BitBoard::from_square(square) & blockers).is_empty() {
@@ -512,16 +557,18 @@ fn compute_bishop_blockers(square: Square) -> BitBoard {
         (-1, -1), // SW
     ];
 
-    let rank = square as i8 / 8;
-    let file = square as i8 % 8;
+    let rank = Board8x8::rank_of(square as usize) as i8;
+    let file = Board8x8::file_of(square as usize) as i8;
 
     for (rank_dir, file_dir) in directions {
         let mut r = rank + rank_dir;
         let mut f = file + file_dir;
 
-        while (1..7).contains(&r) && (1..7).contains(&f) {
-            let index = (r * 8 + f) as u64;
-            blockers.set_bit(Square::from_u64_unchecked(index));
+        // the edges of the board never block a bishop
+        while (1..Board8x8::RANKS as i8 - 1).contains(&r)
+            && (1..Board8x8::FILES as i8 - 1).contains(&f)
+        {
+            blockers.set_bit(Square::from_rank_file(r as usize, f as usize));
             r += rank_dir;
             f += file_dir;
         }
@@ -540,21 +587,24 @@ fn compute_rook_blockers(square: Square) -> BitBoard {
         (0, -1), // S
     ];
 
-    let rank = square as i8 / 8;
-    let file = square as i8 % 8;
+    let rank = Board8x8::rank_of(square as usize) as i8;
+    let file = Board8x8::file_of(square as usize) as i8;
 
     for (rank_dir, file_dir) in directions {
         let mut r = rank + rank_dir;
         let mut f = file + file_dir;
 
-        while (0..8).contains(&r) && (0..8).contains(&f) {
+        while Board8x8::contains(r, f) {
             // if either direction moved at least once, skip the edges this ensure we generate
             // the moves for cornered rooks
-            if (rank_dir != 0 && (r == 0 || r == 7)) || (file_dir != 0 && (f == 0 || f == 7)) {
+            let last_rank = Board8x8::RANKS as i8 - 1;
+            let last_file = Board8x8::FILES as i8 - 1;
+            if (rank_dir != 0 && (r == 0 || r == last_rank))
+                || (file_dir != 0 && (f == 0 || f == last_file))
+            {
                 break;
             }
-            let index = (r * 8 + f) as u64;
-            blockers.set_bit(Square::from_u64_unchecked(index));
+            blockers.set_bit(Square::from_rank_file(r as usize, f as usize));
             r += rank_dir;
             f += file_dir;
         }
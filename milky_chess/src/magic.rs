@@ -1,92 +1,181 @@
+//! Generation and verification of the magic numbers used to index slider attack tables.
+//!
+//! The magic numbers are checked in as constants, this module only exists to regenerate them
+//! through `cargo run --bin gen_magics` and to verify that the checked-in tables still map every
+//! occupancy to the right attacks.
+
 use milky_bitboard::{BitBoard, Square};
 
 use crate::random::Random;
 use crate::{
-    BISHOP_RELEVANT_OCCUPANCIES, ROOK_RELEVANT_OCCUPANCIES, SliderPieceKind,
-    compute_bishop_attacks, compute_bishop_blockers, compute_rook_attacks, compute_rook_blockers,
-    set_occupancy,
+    BISHOP_MAGIC_BITBOARDS, BISHOP_RELEVANT_OCCUPANCIES, ROOK_MAGIC_BITBOARDS,
+    ROOK_RELEVANT_OCCUPANCIES, SliderPieceKind, compute_bishop_attacks, compute_bishop_blockers,
+    compute_rook_attacks, compute_rook_blockers, set_occupancy,
 };
 
-#[allow(dead_code)]
-pub fn init_magic_numbers() {
-    let mut rng = Random::new();
-
-    for square in 0..64 {
-        let square = Square::from_u64_unchecked(square);
-        let rook_magic = find_magic_number(
-            square,
-            ROOK_RELEVANT_OCCUPANCIES[square as usize],
-            SliderPieceKind::Rook,
-            &mut rng,
-        );
-        println!("0x{rook_magic:X},");
+/// Magic numbers for every square, in square index order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagicNumbers {
+    pub rook: [u64; 64],
+    pub bishop: [u64; 64],
+}
+
+impl MagicNumbers {
+    /// Magic numbers currently used by the engine.
+    pub fn builtin() -> Self {
+        Self {
+            rook: ROOK_MAGIC_BITBOARDS.map(|magic| *magic),
+            bishop: BISHOP_MAGIC_BITBOARDS.map(|magic| *magic),
+        }
+    }
+
+    /// Searches magic numbers for every square. The random number generator is seeded with a
+    /// fixed value, so this always produces the same numbers.
+    pub fn generate() -> Self {
+        let mut rng = Random::new();
+
+        let rook = std::array::from_fn(|square| {
+            find_magic_number(square_from_index(square), SliderPieceKind::Rook, &mut rng)
+        });
+
+        let bishop = std::array::from_fn(|square| {
+            find_magic_number(square_from_index(square), SliderPieceKind::Bishop, &mut rng)
+        });
+
+        Self { rook, bishop }
+    }
+
+    /// Checks that every occupancy of every square maps to an index holding its attacks, returning
+    /// the first square that has a collision.
+    pub fn verify(&self) -> Result<(), String> {
+        for index in 0..64 {
+            let square = square_from_index(index);
+
+            if !is_magic_valid(square, SliderPieceKind::Rook, self.rook[index]) {
+                return Err(format!("rook magic for {square} has collisions"));
+            }
+
+            if !is_magic_valid(square, SliderPieceKind::Bishop, self.bishop[index]) {
+                return Err(format!("bishop magic for {square} has collisions"));
+            }
+        }
+
+        Ok(())
     }
 
-    println!();
-    println!();
-
-    for square in 0..64 {
-        let square = Square::from_u64_unchecked(square);
-        let bishop_magic = find_magic_number(
-            square,
-            BISHOP_RELEVANT_OCCUPANCIES[square as usize],
-            SliderPieceKind::Bishop,
-            &mut rng,
-        );
-        println!("0x{bishop_magic:X},");
+    /// Formats the magic numbers as the rust source of the tables in `lib.rs`.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+
+        source.push_str("/// Magic numbers used for bishop magic bitboard indexing.\n");
+        push_table(&mut source, "BISHOP_MAGIC_BITBOARDS", &self.bishop);
+        source.push('\n');
+        push_table(&mut source, "ROOK_MAGIC_BITBOARDS", &self.rook);
+
+        source
     }
 }
 
-#[allow(dead_code)]
-fn find_magic_number(
-    square: Square,
-    relevant_bits: u32,
-    kind: SliderPieceKind,
-    rng: &mut Random,
-) -> u64 {
-    let mut occupancies = [BitBoard::default(); 4096];
-    let mut attacks = [BitBoard::default(); 4096];
-    let mut used_attacks = [BitBoard::default(); 4096];
+fn push_table(source: &mut String, name: &str, magics: &[u64; 64]) {
+    source.push_str(&format!("static {name}: [BitBoard; 64] = [\n"));
 
+    for magic in magics {
+        source.push_str(&format!("    BitBoard::new(0x{magic:X}),\n"));
+    }
+
+    source.push_str("];\n");
+}
+
+fn square_from_index(index: usize) -> Square {
+    Square::from_u64_unchecked(index as u64)
+}
+
+fn relevant_bits(square: Square, kind: SliderPieceKind) -> u32 {
+    match kind {
+        SliderPieceKind::Rook => ROOK_RELEVANT_OCCUPANCIES[square as usize],
+        SliderPieceKind::Bishop => BISHOP_RELEVANT_OCCUPANCIES[square as usize],
+    }
+}
+
+/// Every relevant occupancy of a square paired with the attacks it produces.
+fn occupancy_attacks(square: Square, kind: SliderPieceKind) -> Vec<(BitBoard, BitBoard)> {
+    let relevant_bits = relevant_bits(square, kind);
     let blockers = match kind {
         SliderPieceKind::Rook => compute_rook_blockers(square),
         SliderPieceKind::Bishop => compute_bishop_blockers(square),
     };
 
-    let occupancy_idx = 1 << relevant_bits;
+    (0..1 << relevant_bits)
+        .map(|index| {
+            let occupancy = set_occupancy(index, relevant_bits, blockers);
+            let attacks = match kind {
+                SliderPieceKind::Rook => compute_rook_attacks(square, occupancy),
+                SliderPieceKind::Bishop => compute_bishop_attacks(square, occupancy),
+            };
+
+            (occupancy, attacks)
+        })
+        .collect()
+}
+
+/// A magic is valid when occupancies sharing an index also share the same attacks.
+fn has_collisions(occupancies: &[(BitBoard, BitBoard)], relevant_bits: u32, magic: u64) -> bool {
+    let mut used_attacks = vec![BitBoard::default(); occupancies.len()];
 
-    for index in 0..occupancy_idx {
-        occupancies[index] = set_occupancy(index, relevant_bits, blockers);
+    for &(occupancy, attacks) in occupancies {
+        let magic_index = (occupancy.wrapping_mul(magic) >> (64 - relevant_bits)) as usize;
 
-        attacks[index] = match kind {
-            SliderPieceKind::Rook => compute_rook_attacks(square, occupancies[index]),
-            SliderPieceKind::Bishop => compute_bishop_attacks(square, occupancies[index]),
+        if used_attacks[magic_index].is_empty() {
+            used_attacks[magic_index] = attacks;
+        } else if used_attacks[magic_index] != attacks {
+            return true;
         }
     }
 
-    'search: for _ in 0..100_000_000 {
+    false
+}
+
+fn is_magic_valid(square: Square, kind: SliderPieceKind, magic: u64) -> bool {
+    let occupancies = occupancy_attacks(square, kind);
+    !has_collisions(&occupancies, relevant_bits(square, kind), magic)
+}
+
+fn find_magic_number(square: Square, kind: SliderPieceKind, rng: &mut Random) -> u64 {
+    let relevant_bits = relevant_bits(square, kind);
+    let occupancies = occupancy_attacks(square, kind);
+    let blockers = occupancies
+        .last()
+        .map(|&(occupancy, _)| occupancy)
+        .unwrap_or_default();
+
+    loop {
         let magic_number = rng.gen_u64() & rng.gen_u64() & rng.gen_u64();
-        if ((blockers.wrapping_mul(magic_number)) & 0xFF00_0000_0000_0000).count_ones() < 6 {
+
+        // good magics map the blockers mask into the high bits, skip the ones that can't
+        if (blockers.wrapping_mul(magic_number) & 0xFF00_0000_0000_0000).count_ones() < 6 {
             continue;
         }
 
-        for attack in used_attacks.iter_mut().take(occupancy_idx) {
-            *attack = BitBoard::default()
+        if !has_collisions(&occupancies, relevant_bits, magic_number) {
+            return magic_number;
         }
+    }
+}
 
-        for index in 0..occupancy_idx {
-            let magic_index =
-                ((occupancies[index].wrapping_mul(magic_number)) >> (64 - relevant_bits)) as usize;
-
-            if used_attacks[magic_index].is_empty() {
-                used_attacks[magic_index] = attacks[index];
-            } else if used_attacks[magic_index] != attacks[index] {
-                continue 'search;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        return magic_number;
+    #[test]
+    fn test_builtin_magics_are_collision_free() {
+        assert_eq!(MagicNumbers::builtin().verify(), Ok(()));
     }
 
-    0
+    #[test]
+    fn test_invalid_magic_is_detected() {
+        let mut magics = MagicNumbers::builtin();
+        magics.rook[0] = 1;
+
+        assert!(magics.verify().is_err());
+    }
 }
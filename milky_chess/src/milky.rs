@@ -1,19 +1,60 @@
-use milky_bitboard::{Pieces, Square};
+#[cfg(feature = "search")]
+use std::time::{Duration, Instant};
+
+use milky_bitboard::geometry::{Board8x8, BoardGeometry};
+use milky_bitboard::{Move, Pieces, Side, Square};
 use milky_fen::FenParts;
 
 use crate::board::BoardState;
+use crate::game_status::GameStatus;
+use crate::history::GameHistory;
+#[cfg(feature = "search")]
+use crate::learning::Learning;
 use crate::moves::MoveKind;
-use crate::search::{SearchContext, SearchState};
-use crate::time_manager::{IntoTimeControl, SearchLimits, TimeManager};
-use crate::transposition_table::TranspositionTable;
+#[cfg(feature = "search")]
+use crate::observer::{IterationInfo, Score, SearchObserver, SearchResult, StopInfo};
+#[cfg(feature = "search")]
+use crate::options::{DETERMINISTIC_NODES_TIME, EngineOptions};
+use crate::perft::PerftStats;
+use crate::san::LegalMove;
+#[cfg(feature = "search")]
+use crate::search::{SearchContext, SearchPath};
+#[cfg(feature = "search")]
+use crate::search_state::MATE_UPPER_BOUND;
+use crate::search_state::SearchState;
+#[cfg(feature = "search")]
+use crate::time_manager::{IntoTimeControl, SearchLimits, StopHandle, TimeControl, TimeManager};
+#[cfg(feature = "search")]
+use crate::transposition_table::{TTFlag, TranspositionTable};
 use crate::zobrist::{GamePosition, Zobrist};
 use crate::{Movable, MoveContext, generate_moves, make_move};
 
+/// State owned by a helper thread of a multithreaded search.
+#[cfg(feature = "search")]
+struct Helper {
+    search_state: Box<SearchState>,
+    board_state: BoardState,
+    zobrist: Zobrist,
+}
+
 pub struct Milky {
     board_state: BoardState,
     zobrist: Zobrist,
+    game_history: GameHistory,
+    #[cfg(feature = "search")]
     transposition_table: TranspositionTable,
     search_state: SearchState,
+    #[cfg(feature = "search")]
+    options: EngineOptions,
+    #[cfg(feature = "search")]
+    observer: Option<Box<dyn SearchObserver>>,
+    /// Plies played in the game before the loaded position, taken from its FEN move counter.
+    start_ply: usize,
+    #[cfg(feature = "search")]
+    stop_handle: StopHandle,
+    /// Positions learned from previous games, only present while learning is enabled.
+    #[cfg(feature = "search")]
+    learning: Option<Learning>,
 }
 
 impl Default for Milky {
@@ -27,8 +68,19 @@ impl Milky {
         Self {
             board_state: BoardState::default(),
             zobrist: Zobrist::default(),
+            game_history: GameHistory::default(),
+            #[cfg(feature = "search")]
             transposition_table: TranspositionTable::default(),
             search_state: SearchState::default(),
+            #[cfg(feature = "search")]
+            options: EngineOptions::default(),
+            #[cfg(feature = "search")]
+            observer: None,
+            start_ply: 0,
+            #[cfg(feature = "search")]
+            stop_handle: StopHandle::default(),
+            #[cfg(feature = "search")]
+            learning: None,
         }
     }
 
@@ -55,6 +107,20 @@ impl Milky {
         &mut self.zobrist
     }
 
+    pub fn game_history(&self) -> &GameHistory {
+        &self.game_history
+    }
+
+    /// Whether the position on the board already happened in the game with the same side to
+    /// move.
+    pub fn is_repetition(&self) -> bool {
+        self.game_history.is_repetition(
+            &[],
+            self.zobrist.position,
+            self.board_state.fifty_move_counter,
+        )
+    }
+
     pub fn search_state(&self) -> &SearchState {
         &self.search_state
     }
@@ -64,38 +130,43 @@ impl Milky {
     }
 
     pub fn new_game(&mut self) {
-        self.transposition_table.clear();
+        #[cfg(feature = "search")]
+        self.clear_search_tables();
         self.board_state.reset();
+        self.game_history.clear();
     }
 
+    /// Sets up the board from a FEN, discarding the history of the previous position.
+    ///
+    /// The transposition table is kept, so a search on a position that follows the previous one
+    /// (like after a ponder hit) can reuse its results.
     pub fn load_position(&mut self, fen_parts: FenParts) {
-        let occupancies = [
-            fen_parts.white_occupancy,
-            fen_parts.black_occupancy,
-            fen_parts.both_occupancy,
-        ];
-
-        self.board_state.pieces = fen_parts.positions;
-        self.board_state.occupancies = occupancies;
-        self.board_state.en_passant = fen_parts.en_passant;
-        self.board_state.side_to_move = fen_parts.side_to_move;
-        self.board_state.castling_rights = fen_parts.castling_rights;
+        self.board_state.load_fen(&fen_parts);
+        self.game_history.clear();
+        self.start_ply = fen_parts.full_move_counter.saturating_sub(1) as usize * 2
+            + (fen_parts.side_to_move == Side::Black) as usize;
 
         self.zobrist.position = self.zobrist.hash_position(GamePosition {
             boards: self.board_state.pieces,
             side_to_move: self.board_state.side_to_move,
             en_passant: self.board_state.en_passant,
             castling_rights: self.board_state.castling_rights,
-        })
+        });
+        self.board_state.pawn_key = self.zobrist.hash_pawns(&self.board_state.pieces);
     }
 
-    pub fn load_moves(&mut self, moves: impl Iterator<Item = impl Movable>) {
+    /// Plays the moves on the board, stopping at the first one that isn't legal, and returns the
+    /// status of the game they lead to.
+    pub fn load_moves(&mut self, moves: impl Iterator<Item = impl Movable>) -> GameStatus {
         for mv in moves {
-            generate_moves(&mut MoveContext {
-                board: &mut self.board_state,
-                zobrist: &mut self.zobrist,
-                search: &mut self.search_state,
-            });
+            generate_moves(
+                &mut MoveContext {
+                    board: &mut self.board_state,
+                    zobrist: &mut self.zobrist,
+                    search: &mut self.search_state,
+                },
+                MoveKind::AllMoves,
+            );
 
             let valid_move = self.search_state.moves().find(|m| {
                 m.source() == mv.source()
@@ -104,10 +175,10 @@ impl Milky {
             });
 
             let Some(&valid_move) = valid_move else {
-                return;
+                break;
             };
 
-            self.board_state.record_repetition(&mut self.zobrist);
+            self.game_history.push(self.zobrist.position);
             let mut move_context = MoveContext {
                 board: &mut self.board_state,
                 zobrist: &mut self.zobrist,
@@ -115,22 +186,99 @@ impl Milky {
             };
             make_move(&mut move_context, valid_move, MoveKind::AllMoves);
         }
+
+        self.game_status()
     }
 
-    pub fn think(&mut self, time_control: impl IntoTimeControl) {
-        let time_manager = TimeManager::new(SearchLimits::new(
-            time_control.into_time_control(self.board_state.side_to_move),
-        ));
+    /// Whether the game on the board is over, and how it ended.
+    pub fn game_status(&mut self) -> GameStatus {
+        crate::game_status::game_status(
+            &mut MoveContext {
+                board: &mut self.board_state,
+                zobrist: &mut self.zobrist,
+                search: &mut self.search_state,
+            },
+            &self.game_history,
+        )
+    }
 
-        self.search_state.search_position(SearchContext {
-            transposition_table: &mut self.transposition_table,
-            zobrist: &mut self.zobrist,
+    /// Plies played in the game so far, counting the ones before the loaded FEN.
+    pub fn game_ply(&self) -> usize {
+        self.start_ply + self.game_history.len()
+    }
+
+    /// Statically evaluates every position, from the side to move point of view, without
+    /// searching. The current position is left untouched.
+    ///
+    /// Positions are set up on a single scratch board and only their pawns are hashed, which makes
+    /// this much cheaper than loading each position, for labeling or filtering large sets of
+    /// positions.
+    pub fn evaluate_batch(&mut self, fens: &[&str]) -> Result<Vec<i32>, milky_fen::Error> {
+        let mut board = BoardState::new();
+
+        fens.iter()
+            .map(|fen| {
+                board.load_fen(&milky_fen::parse_fen_string(fen)?);
+                board.pawn_key = self.zobrist.hash_pawns(&board.pieces);
+
+                Ok(crate::evaluate::evaluate_position(
+                    &mut crate::evaluate::EvalContext {
+                        board: &board,
+                        search: &mut self.search_state,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Every legal move of the current position, with its SAN.
+    pub fn legal_moves(&mut self) -> Vec<LegalMove> {
+        crate::san::legal_moves(&mut MoveContext {
             board: &mut self.board_state,
-            time_manager,
-        });
+            zobrist: &mut self.zobrist,
+            search: &mut self.search_state,
+        })
     }
 
-    #[cfg(feature = "bench")]
+    /// Amount of leaves `depth` plies below the current position.
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        crate::perft::perft(
+            &mut MoveContext {
+                board: &mut self.board_state,
+                zobrist: &mut self.zobrist,
+                search: &mut self.search_state,
+            },
+            depth,
+        )
+    }
+
+    /// Leaves `depth` plies below the current position, tallied by the kind of move reaching
+    /// them. Slower than [`Milky::perft`], as every leaf giving check is looked at for mate.
+    pub fn perft_stats(&mut self, depth: u8) -> PerftStats {
+        crate::perft::perft_stats(
+            &mut MoveContext {
+                board: &mut self.board_state,
+                zobrist: &mut self.zobrist,
+                search: &mut self.search_state,
+            },
+            depth,
+        )
+    }
+
+    /// [`Milky::perft_stats`] split by the legal moves of the current position, to find which
+    /// move a wrong count comes from.
+    pub fn perft_divide(&mut self, depth: u8) -> Vec<(Move, PerftStats)> {
+        crate::perft::perft_divide(
+            &mut MoveContext {
+                board: &mut self.board_state,
+                zobrist: &mut self.zobrist,
+                search: &mut self.search_state,
+            },
+            depth,
+        )
+    }
+
+    #[cfg(any(test, feature = "bench"))]
     pub fn move_ctx(&mut self) -> MoveContext<'_> {
         MoveContext {
             zobrist: &mut self.zobrist,
@@ -140,16 +288,363 @@ impl Milky {
     }
 }
 
+#[cfg(feature = "search")]
+impl Milky {
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut EngineOptions {
+        &mut self.options
+    }
+
+    /// Registers an observer notified about the progress of every following search, replacing
+    /// the previous one.
+    pub fn set_observer(&mut self, observer: impl SearchObserver + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// Handle stopping the searches of this engine, to be used from other threads while
+    /// [`Milky::think`] runs.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop_handle.clone()
+    }
+
+    /// Replaces the transposition table by one using at most `size` megabytes, forgetting every
+    /// position searched so far.
+    pub fn set_hash_size(&mut self, size: usize) {
+        self.transposition_table.resize(size);
+    }
+
+    /// Memory used by the transposition table, in megabytes.
+    pub fn hash_size(&self) -> usize {
+        self.transposition_table.size()
+    }
+
+    /// Saves the transposition table to [`EngineOptions::hash_file`], so a long analysis can be
+    /// resumed in a later session with [`Milky::load_hash`].
+    pub fn save_hash(&self) -> std::io::Result<()> {
+        self.transposition_table
+            .save(&self.options.hash_file, self.zobrist.fingerprint())
+    }
+
+    /// Replaces the transposition table by the one saved to [`EngineOptions::hash_file`], which
+    /// also brings back the size it had. Tables saved by another build, hashing positions
+    /// differently, are rejected.
+    pub fn load_hash(&mut self) -> std::io::Result<()> {
+        self.transposition_table
+            .load(&self.options.hash_file, self.zobrist.fingerprint())
+    }
+
+    /// Forgets every position searched so far and the move ordering histories, like a new game
+    /// does but leaving the game on the board.
+    pub fn clear_hash(&mut self) {
+        self.clear_search_tables();
+    }
+
+    /// Enables or disables learning from played games. Enabling it loads the positions learned so
+    /// far from [`EngineOptions::learning_file`].
+    pub fn set_learning(&mut self, enabled: bool) -> std::io::Result<()> {
+        self.learning =
+            if enabled { Some(Learning::load(&self.options.learning_file)?) } else { None };
+
+        Ok(())
+    }
+
+    /// Amount of positions learned, `None` when learning is disabled.
+    pub fn learned_positions(&self) -> Option<usize> {
+        self.learning.as_ref().map(Learning::len)
+    }
+
+    /// Learns from the searches of the game that just ended and saves everything learned so far
+    /// to [`EngineOptions::learning_file`]. Does nothing when learning is disabled.
+    pub fn finish_game(&mut self) -> std::io::Result<()> {
+        let Some(learning) = self.learning.as_mut() else {
+            return Ok(());
+        };
+
+        let learned = learning.finish_game();
+        let saved = learning.save(&self.options.learning_file);
+        self.debug_string(|| format!("learned {learned} positions from the last game"));
+
+        saved
+    }
+
+    /// Searches the current position with [`EngineOptions::threads`] threads. Helper threads
+    /// search the same position on their own copy of the board, sharing the transposition table
+    /// with the main thread, and are stopped once the main thread is done.
+    ///
+    /// The search runs until its time control is exhausted or [`Milky::stop_handle`] is stopped,
+    /// the handle is reset once the search returns.
+    ///
+    /// A checkmate or stalemate isn't searched, and leaves no best move behind. Positions drawn by
+    /// rule are still searched, as a GUI that doesn't adjudicate the draw expects a move.
+    pub fn think(&mut self, time_control: impl IntoTimeControl) -> SearchResult {
+        self.think_with_moves::<Move>(time_control, &[])
+    }
+
+    /// Like [`Milky::think`], but only the root moves in `search_moves` are searched, like UCI
+    /// `go searchmoves` asks for. Moves that aren't legal are ignored, and every move is
+    /// searched when none of them is.
+    pub fn think_with_moves<M: Movable>(
+        &mut self,
+        time_control: impl IntoTimeControl,
+        search_moves: &[M],
+    ) -> SearchResult {
+        let status = self.game_status();
+        if matches!(status, GameStatus::Checkmate(_) | GameStatus::Stalemate) {
+            self.search_state.clear_result(match status {
+                GameStatus::Checkmate(_) => -MATE_UPPER_BOUND,
+                _ => 0,
+            });
+            self.stop_handle.reset();
+
+            self.debug_string(|| format!("not searching, the game is over: {status}"));
+            return self.search_result(Duration::ZERO);
+        }
+
+        let root_moves = self
+            .legal_moves()
+            .into_iter()
+            .map(|legal_move| legal_move.piece_move)
+            .filter(|piece_move| {
+                search_moves.iter().any(|search_move| {
+                    search_move.source() == piece_move.source()
+                        && search_move.target() == piece_move.target()
+                        && search_move.promotion() == piece_move.promotion()
+                })
+            })
+            .collect::<Vec<_>>();
+        if root_moves.len() < search_moves.len() {
+            self.debug_string(|| "ignoring search moves that aren't legal".to_string());
+        }
+
+        let start_time = Instant::now();
+        let helpers_stop = StopHandle::default();
+
+        let mut strength_limit = self.options.strength_limit;
+        let mut nodes_time = self.options.nodes_time;
+        // helpers would make up for the node budget of a limited strength
+        let mut threads = match strength_limit.enabled {
+            true => 1,
+            false => self.options.threads,
+        };
+        if self.options.deterministic {
+            self.clear_search_tables();
+            strength_limit.seed = 0;
+            threads = 1;
+            if nodes_time == 0 {
+                nodes_time = DETERMINISTIC_NODES_TIME;
+            }
+        }
+
+        let time_manager = TimeManager::new(
+            SearchLimits::new(time_control.into_time_control(self.board_state.side_to_move))
+                .with_nodes_time(nodes_time)
+                .with_max_nodes(strength_limit.node_limit())
+                .with_stop_handle(self.stop_handle.clone()),
+        );
+        let max_depth = time_manager.max_depth().unwrap_or(u8::MAX);
+        let opening_jitter =
+            strength_limit.jitter().or(Some(self.options.opening_jitter)
+                .filter(|jitter| jitter.is_active(self.game_ply())));
+
+        let transposition_table = &mut self.transposition_table;
+        transposition_table.new_search();
+        if let Some(learning) = self.learning.as_ref() {
+            for (key, entry) in learning.entries() {
+                transposition_table.set(
+                    Move::default(),
+                    key,
+                    entry.score,
+                    TTFlag::Exact,
+                    entry.depth,
+                    0,
+                );
+            }
+        }
+
+        let mut helpers = (1..threads)
+            .map(|_| Helper {
+                search_state: Box::default(),
+                board_state: self.board_state.clone(),
+                zobrist: self.zobrist.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        std::thread::scope(|scope| {
+            for (index, helper) in helpers.iter_mut().enumerate() {
+                let transposition_table = &self.transposition_table;
+                let game_history = &self.game_history;
+                let root_moves = &root_moves;
+                let features = self.options.search_features;
+                let contempt = self.options.contempt;
+                let time_manager = TimeManager::new(
+                    SearchLimits::new(TimeControl::Infinite).with_stop_handle(helpers_stop.clone()),
+                );
+
+                scope.spawn(move || {
+                    helper.search_state.search_position(SearchContext {
+                        transposition_table,
+                        zobrist: &mut helper.zobrist,
+                        board: &mut helper.board_state,
+                        game_history,
+                        root_moves,
+                        search_path: SearchPath::default(),
+                        time_manager,
+                        features,
+                        debug: false,
+                        opening_jitter,
+                        contempt,
+                        observer: None,
+                        start_depth: 1 + (index % 2) as u8,
+                    });
+                });
+            }
+
+            self.search_state.search_position(SearchContext {
+                transposition_table: &self.transposition_table,
+                zobrist: &mut self.zobrist,
+                board: &mut self.board_state,
+                game_history: &self.game_history,
+                root_moves: &root_moves,
+                search_path: SearchPath::default(),
+                time_manager,
+                features: self.options.search_features,
+                debug: self.options.debug,
+                opening_jitter,
+                contempt: self.options.contempt,
+                observer: self
+                    .observer
+                    .as_mut()
+                    .map(|observer| observer.as_mut() as &mut dyn SearchObserver),
+                start_depth: 1,
+            });
+
+            helpers_stop.stop();
+        });
+
+        self.stop_handle.reset();
+
+        self.collect_helpers(&helpers, max_depth, start_time.elapsed());
+
+        if let Some(learning) = self.learning.as_mut() {
+            learning.record(
+                self.zobrist.position,
+                self.board_state.side_to_move,
+                self.search_state.score,
+                self.search_state.completed_depth,
+            );
+        }
+
+        let result = self.search_result(start_time.elapsed());
+
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_stop(&StopInfo {
+                best_move: result.best_move,
+                depth: result.depth,
+                nodes: result.nodes,
+                elapsed: result.time,
+            });
+        }
+
+        result
+    }
+
+    /// Forgets everything searches learned about the positions of the game.
+    fn clear_search_tables(&mut self) {
+        self.transposition_table.clear();
+        self.search_state.clear_histories();
+    }
+
+    /// Results of the last search, which took `time`.
+    fn search_result(&self, time: Duration) -> SearchResult {
+        let pv = self.search_state.principal_variation().to_vec();
+
+        SearchResult {
+            best_move: self.search_state.best_move(),
+            ponder: pv.get(1).copied(),
+            score: Score::from_search_score(self.search_state.score),
+            depth: self.search_state.completed_depth,
+            seldepth: self.search_state.seldepth,
+            nodes: self.search_state.nodes,
+            pv,
+            time,
+        }
+    }
+
+    /// Adds the nodes searched by the helper threads to the main thread, and takes over the
+    /// principal variation of a helper that completed a deeper iteration than the main thread,
+    /// within `max_depth`, as helpers search without limits until the main thread stops.
+    ///
+    /// The main thread reported its own iterations, so the one taken over is reported before the
+    /// best move is, which would otherwise disagree with the last `info` line.
+    fn collect_helpers(&mut self, helpers: &[Helper], max_depth: u8, elapsed: Duration) {
+        let main = &mut self.search_state;
+        main.nodes += helpers
+            .iter()
+            .map(|helper| helper.search_state.nodes)
+            .sum::<u64>();
+
+        let best = helpers
+            .iter()
+            .map(|helper| &helper.search_state)
+            .filter(|helper| {
+                helper.completed_depth > main.completed_depth
+                    && helper.completed_depth <= max_depth
+                    && helper.pv_length[0] > 0
+            })
+            .max_by_key(|helper| (helper.completed_depth, helper.score));
+
+        if let Some(best) = best {
+            main.completed_depth = best.completed_depth;
+            main.score = best.score;
+            main.pv_length[0] = best.pv_length[0];
+            main.pv_table[0] = best.pv_table[0];
+
+            main.seldepth = main.seldepth.max(best.seldepth);
+
+            let (depth, score) = (main.completed_depth, main.score);
+            self.debug_string(|| format!("using helper thread pv depth {depth} score {score}"));
+
+            let info = IterationInfo {
+                depth,
+                seldepth: self.search_state.seldepth,
+                score: Score::from_search_score(score),
+                nodes: self.search_state.nodes,
+                tt_hits: self.search_state.tt_hits,
+                hashfull: self.transposition_table.hashfull(),
+                elapsed,
+                pv: self.search_state.principal_variation().to_vec(),
+            };
+            if let Some(observer) = self.observer.as_mut() {
+                observer.on_iteration_complete(&info);
+            }
+        }
+    }
+
+    /// Sends a diagnostic to the observer in debug mode, the text is only built when it is sent.
+    fn debug_string(&mut self, text: impl FnOnce() -> String) {
+        if let (true, Some(observer)) = (self.options.debug, self.observer.as_mut()) {
+            observer.on_debug(&text());
+        }
+    }
+}
+
 impl std::fmt::Display for Milky {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f,)?;
 
-        for rank in 0..8 {
+        for rank in 0..Board8x8::RANKS {
             let mut line = String::with_capacity(20);
-            line.push_str(&format!("  {} ", 8 - rank));
+            line.push_str(&format!("  {} ", Board8x8::RANKS - rank));
 
-            for file in 0..8 {
-                let square = Square::from_u64_unchecked(rank * 8 + file);
+            for file in 0..Board8x8::FILES {
+                let square = Square::from_rank_file(rank, file);
                 let mut piece = String::from(".");
 
                 for (idx, &board) in self.board_state.pieces.iter().enumerate() {
@@ -176,3 +671,551 @@ impl std::fmt::Display for Milky {
         writeln!(f)
     }
 }
+
+#[cfg(all(test, feature = "search"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::board::tests::{QuietMove, load_startpos};
+    use crate::options::{OpeningJitter, StrengthLimit};
+    use crate::time_manager::ConventionalTimeControl;
+
+    /// Asserts that both engines hold the same position with the same game history.
+    fn assert_same_history(milky: &Milky, expected: &Milky) {
+        let (board, expected_board) = (milky.board_state(), expected.board_state());
+
+        assert_eq!(milky.zobrist().position, expected.zobrist().position);
+        assert_eq!(board.ply, expected_board.ply);
+        assert_eq!(board.fifty_move_counter, expected_board.fifty_move_counter);
+        assert_eq!(board.snapshots.len(), expected_board.snapshots.len());
+        assert_eq!(milky.game_history(), expected.game_history());
+    }
+
+    fn fresh(moves: &str) -> Milky {
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, moves);
+        milky
+    }
+
+    #[test]
+    fn test_ponder_miss_leaves_no_stale_history() {
+        crate::init_static_members();
+
+        // we played e2e3 and ponder on the expected e7e6 reply, but the opponent plays d7d5
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "g1f3 g8f6 e2e3 e7e6");
+        milky.think(TimeControl::FixedDepth(4));
+
+        load_startpos(&mut milky, "g1f3 g8f6 e2e3 d7d5");
+        assert_same_history(&milky, &fresh("g1f3 g8f6 e2e3 d7d5"));
+
+        milky.think(TimeControl::FixedDepth(4));
+        assert_same_history(&milky, &fresh("g1f3 g8f6 e2e3 d7d5"));
+    }
+
+    #[test]
+    fn test_ponder_hit_keeps_game_history() {
+        crate::init_static_members();
+
+        // the opponent plays the predicted f6g8, repeating the starting position
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "g1f3 g8f6 f3g1 f6g8");
+        milky.think(TimeControl::FixedDepth(4));
+
+        load_startpos(&mut milky, "g1f3 g8f6 f3g1 f6g8");
+        assert_same_history(&milky, &fresh("g1f3 g8f6 f3g1 f6g8"));
+        assert_eq!(milky.board_state().snapshots.len(), 4);
+        assert!(milky.is_repetition());
+    }
+
+    #[test]
+    fn test_repeated_positions_do_not_accumulate_history() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        for _ in 0..3 {
+            load_startpos(&mut milky, "e2e4 e7e5 g1f3");
+            milky.think(TimeControl::FixedDepth(3));
+        }
+
+        assert_same_history(&milky, &fresh("e2e4 e7e5 g1f3"));
+        assert_eq!(milky.game_history().len(), 3);
+    }
+
+    #[test]
+    fn test_evaluate_batch_matches_evaluate() {
+        crate::init_static_members();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+
+        let mut milky = fresh("e2e4");
+        let key = milky.zobrist().position;
+        let batch = milky.evaluate_batch(&fens).unwrap();
+
+        // the loaded position is not touched by the batch
+        assert_eq!(milky.zobrist().position, key);
+        assert_eq!(milky.game_history().len(), 1);
+
+        for (fen, score) in fens.iter().zip(batch) {
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            assert_eq!(milky.evaluate(), score, "{fen}");
+        }
+
+        assert!(milky.evaluate_batch(&[fens[0], "not a fen"]).is_err());
+    }
+
+    #[test]
+    fn test_opening_jitter_varies_root_move() {
+        crate::init_static_members();
+
+        let best_move = |seed, moves| {
+            let mut milky = fresh(moves);
+            milky.options_mut().opening_jitter = OpeningJitter {
+                max_score: 50,
+                seed,
+                moves: 2,
+            };
+            milky.think(TimeControl::FixedDepth(4));
+            milky.search_state().best_move()
+        };
+
+        let moves = (0..8).map(|seed| best_move(seed, "")).collect::<Vec<_>>();
+        assert!(moves.iter().any(|piece_move| *piece_move != moves[0]));
+        assert_eq!(best_move(3, ""), moves[3]);
+
+        // two full moves in, the jitter no longer applies
+        let moves = (0..4)
+            .map(|seed| best_move(seed, "e2e4 e7e5 g1f3 b8c6"))
+            .collect::<Vec<_>>();
+        assert!(moves.iter().all(|piece_move| *piece_move == moves[0]));
+    }
+
+    #[test]
+    fn test_fifty_move_rule() {
+        crate::init_static_members();
+
+        let load = |fen| {
+            let mut milky = Milky::new();
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            milky
+        };
+
+        let mut milky = load("7k/8/6K1/8/8/8/8/1Q6 w - - 100 80");
+        assert_eq!(milky.game_status(), GameStatus::DrawByFiftyMove);
+        // there is still a move to answer with
+        assert_ne!(
+            milky.think(TimeControl::FixedDepth(4)).best_move,
+            Move::default()
+        );
+
+        // a mate on the hundredth ply still ends the game
+        let mut milky = load("7k/6Q1/6K1/8/8/8/8/8 b - - 100 80");
+        assert_eq!(milky.game_status(), GameStatus::Checkmate(Side::Black));
+
+        let mut milky = load("7k/8/6K1/8/8/8/8/1Q6 w - - 99 80");
+        let result = milky.think(TimeControl::FixedDepth(4));
+        assert_eq!(result.best_move.to_string(), "b1b8");
+        assert_eq!(result.score, Score::Mate(1));
+    }
+
+    #[test]
+    fn test_insufficient_material_is_a_draw() {
+        crate::init_static_members();
+
+        let load = |fen| {
+            let mut milky = Milky::new();
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            milky
+        };
+
+        let mut milky = load("8/5k2/8/8/8/2N5/8/5K2 w - - 0 1");
+        assert_eq!(milky.game_status(), GameStatus::DrawByInsufficientMaterial);
+        let result = milky.think(TimeControl::FixedDepth(4));
+        assert_ne!(result.best_move, Move::default());
+        assert_eq!(result.score, Score::Centipawns(0));
+
+        // taking the last pawn leaves a lone knight, which is worth nothing
+        let mut milky = load("8/5k2/8/1p6/8/2N5/8/5K2 w - - 0 1");
+        let result = milky.think(TimeControl::FixedDepth(4));
+        assert_ne!(result.best_move.to_string(), "c3b5");
+    }
+
+    #[test]
+    fn test_contempt_shifts_draw_scores() {
+        crate::init_static_members();
+
+        // going back to g1 repeats the position, drawing the game
+        let best_move = |contempt| {
+            let mut milky = fresh("g1f3 g8f6 f3g1 f6g8 g1f3 g8f6");
+            milky.options_mut().contempt = contempt;
+            milky
+                .think(TimeControl::FixedDepth(4))
+                .best_move
+                .to_string()
+        };
+
+        assert_ne!(best_move(0), "f3g1");
+        assert_eq!(best_move(-500), "f3g1");
+    }
+
+    #[test]
+    fn test_deterministic_searches_repeat() {
+        crate::init_static_members();
+
+        let clock = TimeControl::Conventional(ConventionalTimeControl {
+            time_left: Duration::from_millis(2000),
+            increment: Duration::ZERO,
+            moves_to_go: Some(40),
+        });
+        let mut milky = fresh("e2e4 e7e5");
+        milky.options_mut().threads = 4;
+        milky.options_mut().deterministic = true;
+
+        let first = milky.think(clock);
+        // whatever was searched in between is forgotten
+        milky.think(TimeControl::FixedDepth(6));
+        let second = milky.think(clock);
+
+        assert!(first.nodes > 0);
+        assert_eq!(first.nodes, second.nodes);
+        assert_eq!(first.best_move, second.best_move);
+        assert_eq!(first.pv, second.pv);
+    }
+
+    #[test]
+    fn test_clear_hash_forgets_previous_searches() {
+        crate::init_static_members();
+
+        let mut milky = fresh("e2e4 e7e5");
+        milky.options_mut().threads = 1;
+
+        let first = milky.think(TimeControl::FixedDepth(6));
+        let second = milky.think(TimeControl::FixedDepth(6));
+        assert!(second.nodes < first.nodes);
+
+        milky.clear_hash();
+        let third = milky.think(TimeControl::FixedDepth(6));
+        assert_eq!(first.nodes, third.nodes);
+        assert_eq!(first.pv, third.pv);
+    }
+
+    #[test]
+    fn test_tables_stay_warm_between_positions_of_a_game() {
+        crate::init_static_members();
+        let moves = "e2e4 e7e5 g1f3 b8c6";
+
+        let mut cold = fresh(moves);
+        cold.options_mut().threads = 1;
+        let cold = cold.think(TimeControl::FixedDepth(6));
+
+        let mut milky = fresh("e2e4 e7e5");
+        milky.options_mut().threads = 1;
+        milky.think(TimeControl::FixedDepth(6));
+        load_startpos(&mut milky, moves);
+        assert!(milky.think(TimeControl::FixedDepth(6)).nodes < cold.nodes);
+
+        // only a new game starts from empty tables
+        milky.new_game();
+        load_startpos(&mut milky, moves);
+        assert_eq!(milky.think(TimeControl::FixedDepth(6)).nodes, cold.nodes);
+    }
+
+    #[test]
+    fn test_strength_limit_caps_the_search() {
+        crate::init_static_members();
+
+        let mut milky = fresh("");
+        milky.options_mut().threads = 4;
+        milky.options_mut().strength_limit = StrengthLimit {
+            enabled: true,
+            elo: StrengthLimit::MIN_ELO,
+            seed: 0,
+        };
+
+        let result = milky.think(TimeControl::FixedDepth(20));
+        assert!(result.depth < 20);
+        assert!(result.nodes < 1000);
+        assert_ne!(result.best_move, Move::default());
+    }
+
+    #[test]
+    fn test_game_ply_counts_fen_moves() {
+        crate::init_static_members();
+        let mut milky = fresh("e2e4 e7e5 g1f3");
+        assert_eq!(milky.game_ply(), 3);
+
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+        assert_eq!(milky.game_ply(), 4);
+    }
+
+    #[test]
+    fn test_multithreaded_search() {
+        crate::init_static_members();
+
+        let mut single = Milky::new();
+        single.load_position(
+            milky_fen::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap(),
+        );
+        single.think(TimeControl::FixedDepth(5));
+
+        let mut milky = Milky::new();
+        milky.options_mut().threads = 4;
+        milky.load_position(
+            milky_fen::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap(),
+        );
+        milky.think(TimeControl::FixedDepth(5));
+
+        assert_eq!(milky.search_state().best_move().to_string(), "a1a8");
+        assert!(milky.search_state().completed_depth >= 4);
+
+        // the helpers leave the position of the main thread alone
+        assert_same_history(&milky, &single);
+    }
+
+    #[test]
+    fn test_degenerate_limits_still_find_a_move() {
+        crate::init_static_members();
+        // the rook takes a free queen, which a depth 1 search sees
+        let fen = "6k1/8/8/3q4/8/8/8/3R2K1 w - - 0 1";
+        let out_of_time = ConventionalTimeControl {
+            time_left: Duration::ZERO,
+            increment: Duration::ZERO,
+            moves_to_go: Some(0),
+        };
+
+        for time_control in [
+            TimeControl::MoveTime(Duration::ZERO),
+            TimeControl::FixedDepth(0),
+            TimeControl::FixedDepth(1),
+            TimeControl::FixedNodes(0),
+            TimeControl::Conventional(out_of_time),
+        ] {
+            let mut milky = Milky::new();
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            milky.think(time_control);
+
+            assert_eq!(milky.search_state().completed_depth, 1, "{time_control:?}");
+            assert_eq!(milky.search_state().best_move().to_string(), "d1d5");
+        }
+    }
+
+    #[test]
+    fn test_search_moves_restrict_the_root() {
+        crate::init_static_members();
+        // the rook takes a free queen unless told to look at king moves only
+        let fen = "6k1/8/8/3q4/8/8/8/3R2K1 w - - 0 1";
+        let search = |search_moves: &[QuietMove]| {
+            let mut milky = Milky::new();
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            milky.think_with_moves(TimeControl::FixedDepth(4), search_moves);
+            milky.search_state().best_move().to_string()
+        };
+
+        assert_eq!(search(&[]), "d1d5");
+        assert_eq!(search(&[QuietMove(Square::G1, Square::F2)]), "g1f2");
+
+        // illegal moves are dropped, and when none is left every move is searched
+        assert_eq!(
+            search(&[
+                QuietMove(Square::A1, Square::A2),
+                QuietMove(Square::G1, Square::F1),
+            ]),
+            "g1f1"
+        );
+        assert_eq!(search(&[QuietMove(Square::A1, Square::A2)]), "d1d5");
+    }
+
+    #[test]
+    fn test_think_returns_the_search_result() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        milky.load_position(
+            milky_fen::parse_fen_string("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap(),
+        );
+        let result = milky.think(TimeControl::FixedDepth(4));
+
+        assert_eq!(result.best_move.to_string(), "a1a8");
+        assert_eq!(result.score, Score::Mate(1));
+        assert_eq!(result.depth, 4);
+        assert!(result.seldepth >= 1);
+        assert_eq!(result.nodes, milky.search_state().nodes);
+        assert_eq!(result.pv, milky.search_state().principal_variation());
+        assert_eq!(result.ponder, result.pv.get(1).copied());
+
+        // nothing is searched once the game is over
+        milky.load_position(milky_fen::parse_fen_string("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+        let result = milky.think(TimeControl::FixedDepth(4));
+        assert_eq!(result.best_move, Move::default());
+        assert_eq!((result.depth, result.nodes), (0, 0));
+        assert!(result.pv.is_empty());
+    }
+
+    #[test]
+    fn test_fixed_depth_searches_the_requested_depth() {
+        crate::init_static_members();
+        let mut milky = fresh("");
+        milky.think(TimeControl::FixedDepth(3));
+
+        assert_eq!(milky.search_state().completed_depth, 3);
+    }
+
+    #[test]
+    fn test_stop_handle_stops_infinite_search() {
+        crate::init_static_members();
+        let mut milky = fresh("e2e4");
+        milky.options_mut().threads = 2;
+
+        let stop_handle = milky.stop_handle();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            stop_handle.stop();
+        });
+
+        milky.think(TimeControl::Infinite);
+        stopper.join().unwrap();
+
+        assert!(milky.search_state().completed_depth > 0);
+        assert!(!milky.stop_handle().is_stopped());
+    }
+
+    #[test]
+    fn test_move_time_is_respected_mid_iteration() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+
+        let start = std::time::Instant::now();
+        milky.think(TimeControl::MoveTime(Duration::from_millis(50)));
+
+        // generous bound to keep the test reliable on slow machines
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(milky.search_state().completed_depth > 0);
+    }
+
+    #[test]
+    fn test_learning_from_a_lost_game() {
+        crate::init_static_members();
+        let path = std::env::temp_dir().join(format!("milky-learn-{}", std::process::id()));
+
+        let mut milky = fresh("");
+        milky.options_mut().learning_file = path.clone();
+        milky.set_learning(true).unwrap();
+        assert_eq!(milky.learned_positions(), Some(0));
+
+        // the engine thought the opening was fine, but ended the game a queen down
+        milky.think(TimeControl::FixedDepth(3));
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1";
+        milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+        milky.think(TimeControl::FixedDepth(3));
+        milky.finish_game().unwrap();
+        assert_eq!(milky.learned_positions(), Some(1));
+
+        let mut other = fresh("");
+        other.options_mut().learning_file = path.clone();
+        other.set_learning(true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(other.learned_positions(), Some(1));
+
+        other.set_learning(false).unwrap();
+        assert_eq!(other.learned_positions(), None);
+        other.finish_game().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_saved_hash_resumes_the_analysis() {
+        crate::init_static_members();
+        let path = std::env::temp_dir().join(format!("milky-hash-{}", std::process::id()));
+
+        let mut milky = fresh("e2e4 e7e5");
+        milky.options_mut().threads = 1;
+        milky.options_mut().hash_file = path.clone();
+        milky.set_hash_size(2);
+        let first = milky.think(TimeControl::FixedDepth(6));
+        milky.save_hash().unwrap();
+
+        let mut other = fresh("e2e4 e7e5");
+        other.options_mut().threads = 1;
+        other.options_mut().hash_file = path.clone();
+        other.load_hash().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(other.hash_size(), 2);
+
+        let resumed = other.think(TimeControl::FixedDepth(6));
+        assert!(resumed.nodes < first.nodes);
+        assert_eq!(resumed.best_move, first.best_move);
+
+        // a missing file leaves the table as it was
+        assert!(other.load_hash().is_err());
+        assert_eq!(other.hash_size(), 2);
+    }
+
+    #[test]
+    fn test_game_over_positions_are_not_searched() {
+        crate::init_static_members();
+
+        // fool's mate
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "f2f3 e7e5 g2g4 d8h4");
+        assert_eq!(milky.game_status(), GameStatus::Checkmate(Side::White));
+
+        milky.think(TimeControl::FixedDepth(4));
+        assert_eq!(milky.search_state().best_move(), Move::default());
+        assert_eq!(milky.search_state().nodes, 0);
+        assert_eq!(milky.search_state().completed_depth, 0);
+
+        // moves sent after the mate are ignored
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "f2f3 e7e5 g2g4 d8h4 e2e3");
+        assert_eq!(milky.game_status(), GameStatus::Checkmate(Side::White));
+
+        let fen = milky_fen::parse_fen_string("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut milky = Milky::new();
+        milky.load_position(fen);
+        assert_eq!(milky.game_status(), GameStatus::Stalemate);
+
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "e2e4");
+        assert_eq!(milky.game_status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn test_every_search_starts_a_new_generation() {
+        crate::init_static_members();
+
+        let mut milky = fresh("");
+        milky.think(TimeControl::FixedDepth(4));
+        let root = milky.zobrist().position;
+        let generation = milky.transposition_table.probe(root).unwrap().generation;
+
+        // the entries of the previous search are left for the next ones to overwrite
+        load_startpos(&mut milky, "e2e4");
+        milky.think(TimeControl::FixedDepth(1));
+        let tt = &milky.transposition_table;
+        assert_eq!(tt.probe(root).unwrap().generation, generation);
+        assert_ne!(
+            tt.probe(milky.zobrist().position).unwrap().generation,
+            generation
+        );
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        crate::init_static_members();
+
+        // the starting position comes back a second time, which isn't a draw yet
+        let mut milky = Milky::new();
+        load_startpos(&mut milky, "g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(milky.game_status(), GameStatus::Ongoing);
+
+        load_startpos(&mut milky, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(milky.game_status(), GameStatus::DrawByRepetition);
+        assert!(milky.game_status().is_over());
+    }
+}
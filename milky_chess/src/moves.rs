@@ -4,8 +4,9 @@ use milky_bitboard::{
 };
 
 use crate::board::{get_bishop_attacks, get_queen_attacks, get_rook_attacks};
+#[cfg(feature = "search")]
 use crate::evaluate::{EvalContext, score_move};
-use crate::search::SearchState;
+use crate::search_state::SearchState;
 use crate::zobrist::Zobrist;
 use crate::{BoardState, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, attacks};
 
@@ -44,10 +45,35 @@ pub trait Movable {
     fn promotion(&self) -> PromotionPieces;
 }
 
+impl Movable for Move {
+    fn source(&self) -> Square {
+        Move::source(self)
+    }
+
+    fn target(&self) -> Square {
+        Move::target(self)
+    }
+
+    fn promotion(&self) -> PromotionPieces {
+        Move::promotion(self)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum MoveKind {
     AllMoves,
     Captures,
+    Quiets,
+}
+
+impl MoveKind {
+    pub fn includes_captures(&self) -> bool {
+        matches!(self, MoveKind::AllMoves | MoveKind::Captures)
+    }
+
+    pub fn includes_quiets(&self) -> bool {
+        matches!(self, MoveKind::AllMoves | MoveKind::Quiets)
+    }
 }
 
 pub struct MoveContext<'ctx> {
@@ -60,6 +86,7 @@ pub struct SortContext<'ctx> {
     pub zobrist: &'ctx mut Zobrist,
     pub search: &'ctx mut SearchState,
     pub board: &'ctx mut BoardState,
+    /// Best move the transposition table holds for the position, ordered before any other.
     pub best_move: Move,
 }
 
@@ -83,10 +110,13 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
             ctx.zobrist.position ^= ctx.zobrist.pieces_table[piece][source];
             ctx.zobrist.position ^= ctx.zobrist.pieces_table[piece][target];
 
-            ctx.board.fifty_move_counter += 1;
+            // the counter of a FEN can already be at its limit
+            ctx.board.fifty_move_counter = ctx.board.fifty_move_counter.saturating_add(1);
 
             if piece.kind() == PieceKind::Pawn {
                 ctx.board.fifty_move_counter = 0;
+                ctx.board.pawn_key ^= ctx.zobrist.pieces_table[piece][source];
+                ctx.board.pawn_key ^= ctx.zobrist.pieces_table[piece][target];
             }
 
             if piece_move.is_capture() {
@@ -103,6 +133,9 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
                     if ctx.board.pieces[piece].get_bit(target).is_set() {
                         ctx.board.pieces[piece].clear_bit(target);
                         ctx.zobrist.position ^= ctx.zobrist.pieces_table[piece][target];
+                        if piece == start {
+                            ctx.board.pawn_key ^= ctx.zobrist.pieces_table[piece][target];
+                        }
                         break;
                     }
                 }
@@ -124,6 +157,7 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
                 ctx.board.pieces[promoted_piece].set_bit(target);
                 ctx.zobrist.position ^= ctx.zobrist.pieces_table[pawn_side][target];
                 ctx.zobrist.position ^= ctx.zobrist.pieces_table[promoted_piece][target];
+                ctx.board.pawn_key ^= ctx.zobrist.pieces_table[pawn_side][target];
             }
 
             if piece_move.is_en_passant() {
@@ -141,6 +175,7 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
 
                 ctx.board.pieces[pawn_side].clear_bit(square);
                 ctx.zobrist.position ^= ctx.zobrist.pieces_table[pawn_side][square];
+                ctx.board.pawn_key ^= ctx.zobrist.pieces_table[pawn_side][square];
             }
 
             if ctx.board.en_passant.is_available() {
@@ -149,12 +184,17 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
             ctx.board.en_passant = Square::OffBoard;
 
             if piece_move.is_double_push() {
-                ctx.board.en_passant = match ctx.board.side_to_move {
+                let en_passant = match ctx.board.side_to_move {
                     Side::White => target.one_backward().unwrap(),
                     Side::Black => target.one_forward().unwrap(),
                     _ => unreachable!(),
                 };
-                ctx.zobrist.position ^= ctx.zobrist.en_passant[ctx.board.en_passant];
+
+                let enemy = ctx.board.side_to_move.enemy();
+                if ctx.board.can_capture_en_passant(en_passant, enemy) {
+                    ctx.board.en_passant = en_passant;
+                    ctx.zobrist.position ^= ctx.zobrist.en_passant[en_passant];
+                }
             }
 
             if piece_move.is_castling() {
@@ -238,35 +278,139 @@ pub(crate) fn make_move(ctx: &mut MoveContext<'_>, piece_move: Move, move_kind:
                 false
             }
         }
+        MoveKind::Quiets => {
+            if piece_move.is_capture() {
+                false
+            } else {
+                make_move(ctx, piece_move, MoveKind::AllMoves)
+            }
+        }
     }
 }
 
-pub(crate) fn sort_moves(ctx: &mut SortContext<'_>) {
-    let mut scored_moves = vec![];
+/// Hands out the generated moves from the most to the least promising, equally scored moves in
+/// generation order.
+///
+/// Every move is scored up front, but most nodes cut off after a couple of moves, so instead of
+/// sorting the whole list the best of the remaining moves is only looked for when the next move
+/// is asked for. The moves are copied out of the move list of the search state, which searching
+/// them overwrites.
+#[cfg(feature = "search")]
+pub(crate) struct MovePicker {
+    moves: [Move; 256],
+    scores: [i32; 256],
+    len: usize,
+    next: usize,
+    /// Set once every move was put in order, the moves are then handed out as they are.
+    sorted: bool,
+}
 
-    for m in ctx.search.moves.into_iter().take(ctx.search.move_count) {
-        let mut eval_context = EvalContext {
-            board: ctx.board,
-            search: ctx.search,
+#[cfg(feature = "search")]
+impl MovePicker {
+    pub(crate) fn new(ctx: &mut SortContext<'_>) -> Self {
+        let len = ctx.search.move_count;
+        let mut picker = Self {
+            moves: ctx.search.moves,
+            scores: [0; 256],
+            len,
+            next: 0,
+            sorted: false,
         };
-        let score = if ctx.best_move == m { 30_000 } else { score_move(&mut eval_context, m) };
-        scored_moves.push((score, m));
+
+        for idx in 0..len {
+            let piece_move = picker.moves[idx];
+            let mut eval_context = EvalContext {
+                board: ctx.board,
+                search: ctx.search,
+            };
+            picker.scores[idx] = score_move(&mut eval_context, piece_move, ctx.best_move);
+        }
+
+        picker
+    }
+
+    /// Orders every move at once, for nodes where all of them are searched anyways, returning
+    /// them so the order can still be adjusted before they are handed out.
+    pub(crate) fn sort(&mut self) -> &mut [Move] {
+        let moves = &mut self.moves;
+        let scores = &mut self.scores;
+
+        for idx in 1..self.len {
+            let piece_move = moves[idx];
+            let score = scores[idx];
+
+            // strictly greater keeps equally scored moves in generation order
+            let mut slot = idx;
+            while slot > 0 && scores[slot - 1] < score {
+                moves[slot] = moves[slot - 1];
+                scores[slot] = scores[slot - 1];
+                slot -= 1;
+            }
+
+            moves[slot] = piece_move;
+            scores[slot] = score;
+        }
+
+        self.sorted = true;
+        &mut self.moves[..self.len]
+    }
+}
+
+#[cfg(feature = "search")]
+impl Iterator for MovePicker {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if self.next == self.len {
+            return None;
+        }
+
+        if !self.sorted {
+            let mut best = self.next;
+            for idx in self.next + 1..self.len {
+                if self.scores[idx] > self.scores[best] {
+                    best = idx;
+                }
+            }
+
+            // rotating rather than swapping keeps the moves left behind in generation order
+            self.moves[self.next..=best].rotate_right(1);
+            self.scores[self.next..=best].rotate_right(1);
+        }
+
+        self.next += 1;
+        Some(self.moves[self.next - 1])
     }
+}
 
-    scored_moves.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+/// Generates the captures and the quiet moves giving check for the side to move, which is what
+/// the first plies of quiescence search look at.
+#[cfg(feature = "search")]
+pub(crate) fn generate_captures_and_checks(ctx: &mut MoveContext<'_>) {
+    generate_moves(ctx, MoveKind::AllMoves);
+
+    let mut kept = 0;
+    for idx in 0..ctx.search.move_count {
+        let piece_move = ctx.search.moves[idx];
+        if piece_move.is_capture() || ctx.board.gives_check(piece_move) {
+            ctx.search.moves[kept] = piece_move;
+            kept += 1;
+        }
+    }
 
-    scored_moves
-        .into_iter()
-        .enumerate()
-        .for_each(|(idx, (_, m))| ctx.search.moves[idx] = m);
+    ctx.search.move_count = kept;
 }
 
 #[cfg(feature = "bench")]
 pub fn generate_moves_bench(ctx: &mut MoveContext<'_>) {
-    generate_moves(ctx)
+    generate_moves(ctx, MoveKind::AllMoves)
 }
 
-pub(crate) fn generate_moves(ctx: &mut MoveContext<'_>) {
+/// Generates the pseudo-legal moves of the given kind for the side to move.
+///
+/// Quiescence search only looks at captures, so generating only what the caller needs skips
+/// creating moves that would be discarded on `make_move` anyways.
+pub(crate) fn generate_moves(ctx: &mut MoveContext<'_>, kind: MoveKind) {
     ctx.search.move_count = 0;
     for (idx, board) in ctx.board.pieces.into_iter().enumerate() {
         let piece = Pieces::from_usize_unchecked(idx);
@@ -276,17 +420,45 @@ pub(crate) fn generate_moves(ctx: &mut MoveContext<'_>) {
         }
 
         match piece {
-            Pieces::WhitePawn | Pieces::BlackPawn => generate_pawn_moves(ctx, board, piece),
-            Pieces::WhiteKing | Pieces::BlackKing => generate_king_moves(ctx, board, piece),
-            Pieces::WhiteKnight | Pieces::BlackKnight => generate_knight_moves(ctx, board, piece),
-            Pieces::WhiteBishop | Pieces::BlackBishop => generate_bishop_moves(ctx, board, piece),
-            Pieces::WhiteRook | Pieces::BlackRook => generate_rook_moves(ctx, board, piece),
-            Pieces::WhiteQueen | Pieces::BlackQueen => generate_queen_moves(ctx, board, piece),
+            Pieces::WhitePawn | Pieces::BlackPawn => generate_pawn_moves(ctx, board, piece, kind),
+            Pieces::WhiteKing | Pieces::BlackKing => generate_king_moves(ctx, board, piece, kind),
+            Pieces::WhiteKnight | Pieces::BlackKnight => {
+                generate_knight_moves(ctx, board, piece, kind)
+            }
+            Pieces::WhiteBishop | Pieces::BlackBishop => {
+                generate_bishop_moves(ctx, board, piece, kind)
+            }
+            Pieces::WhiteRook | Pieces::BlackRook => generate_rook_moves(ctx, board, piece, kind),
+            Pieces::WhiteQueen | Pieces::BlackQueen => {
+                generate_queen_moves(ctx, board, piece, kind)
+            }
         }
     }
 }
 
-fn generate_pawn_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+fn promotion_options(search: &SearchState) -> &'static [PromotionPieces] {
+    match search.skip_underpromotions {
+        true => &[PromotionPieces::Knight, PromotionPieces::Queen],
+        false => &[
+            PromotionPieces::Knight,
+            PromotionPieces::Bishop,
+            PromotionPieces::Rook,
+            PromotionPieces::Queen,
+        ],
+    }
+}
+
+fn generate_pawn_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces, kind: MoveKind) {
+    if kind.includes_quiets() {
+        generate_pawn_pushes(ctx, board, piece);
+    }
+
+    if kind.includes_captures() {
+        generate_pawn_captures(ctx, board, piece);
+    }
+}
+
+fn generate_pawn_pushes(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
     let promotion_rank = match ctx.board.side_to_move {
         Side::White => Rank::Seventh,
         Side::Black => Rank::Second,
@@ -299,12 +471,7 @@ fn generate_pawn_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces
         _ => unreachable!(),
     };
 
-    let promotion_options = [
-        PromotionPieces::Knight,
-        PromotionPieces::Bishop,
-        PromotionPieces::Rook,
-        PromotionPieces::Queen,
-    ];
+    let promotion_options = promotion_options(ctx.search);
 
     for square in board {
         let one_forward = match ctx.board.side_to_move {
@@ -323,7 +490,7 @@ fn generate_pawn_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces
             .is_empty()
         {
             if square.is_on_rank(promotion_rank) {
-                for option in promotion_options {
+                for &option in promotion_options {
                     ctx.search.push_move(Move::new(
                         square,
                         one_forward,
@@ -364,14 +531,26 @@ fn generate_pawn_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces
                 }
             }
         }
+    }
+}
+
+fn generate_pawn_captures(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+    let promotion_rank = match ctx.board.side_to_move {
+        Side::White => Rank::Seventh,
+        Side::Black => Rank::Second,
+        _ => unreachable!(),
+    };
 
+    let promotion_options = promotion_options(ctx.search);
+
+    for square in board {
         let enemy_occupancies = ctx.board.occupancies[ctx.board.side_to_move.enemy()];
         let pawn_attacks = attacks!(PAWN_ATTACKS)[ctx.board.side_to_move][square];
         let attacks = pawn_attacks.attacked_squares(enemy_occupancies);
 
         for target in attacks {
             if square.is_on_rank(promotion_rank) {
-                for option in promotion_options {
+                for &option in promotion_options {
                     ctx.search.push_move(Move::new(
                         square,
                         target,
@@ -413,19 +592,19 @@ fn generate_pre_computed_moves<F>(
     ctx: &mut MoveContext<'_>,
     piece: Pieces,
     board: BitBoard,
+    kind: MoveKind,
     get_attacks: F,
 ) where
     F: Fn(Square) -> BitBoard,
 {
+    let enemy = ctx.board.occupancies[ctx.board.side_to_move.enemy()];
+    let empty = !ctx.board.occupancies[Side::Both];
+
     for square in board {
         let attacks = get_attacks(square);
-        let occupancies = !ctx.board.occupancies[ctx.board.side_to_move];
-        let attacks = attacks.attacked_squares(occupancies);
-
-        for target in attacks {
-            let occupancies = ctx.board.occupancies[ctx.board.side_to_move.enemy()];
 
-            if occupancies.get_bit(target).is_set() {
+        if kind.includes_captures() {
+            for target in attacks.attacked_squares(enemy) {
                 ctx.search.push_move(Move::new(
                     square,
                     target,
@@ -433,7 +612,11 @@ fn generate_pre_computed_moves<F>(
                     PromotionPieces::NoPromotion,
                     MoveFlags::CAPTURE,
                 ));
-            } else {
+            }
+        }
+
+        if kind.includes_quiets() {
+            for target in attacks.attacked_squares(empty) {
                 ctx.search.push_move(Move::new(
                     square,
                     target,
@@ -446,26 +629,50 @@ fn generate_pre_computed_moves<F>(
     }
 }
 
-fn generate_knight_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
-    generate_pre_computed_moves(ctx, piece, board, |sq| attacks!(KNIGHT_ATTACKS)[sq]);
+fn generate_knight_moves(
+    ctx: &mut MoveContext<'_>,
+    board: BitBoard,
+    piece: Pieces,
+    kind: MoveKind,
+) {
+    generate_pre_computed_moves(ctx, piece, board, kind, |sq| attacks!(KNIGHT_ATTACKS)[sq]);
 }
 
-fn generate_bishop_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+fn generate_bishop_moves(
+    ctx: &mut MoveContext<'_>,
+    board: BitBoard,
+    piece: Pieces,
+    kind: MoveKind,
+) {
     let occupancies = ctx.board.occupancies[Side::Both];
-    generate_pre_computed_moves(ctx, piece, board, |sq| get_bishop_attacks(sq, occupancies));
+    generate_pre_computed_moves(ctx, piece, board, kind, |sq| {
+        get_bishop_attacks(sq, occupancies)
+    });
 }
 
-fn generate_rook_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+fn generate_rook_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces, kind: MoveKind) {
     let occupancies = ctx.board.occupancies[Side::Both];
-    generate_pre_computed_moves(ctx, piece, board, |sq| get_rook_attacks(sq, occupancies));
+    generate_pre_computed_moves(ctx, piece, board, kind, |sq| {
+        get_rook_attacks(sq, occupancies)
+    });
 }
 
-fn generate_queen_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+fn generate_queen_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces, kind: MoveKind) {
     let occupancies = ctx.board.occupancies[Side::Both];
-    generate_pre_computed_moves(ctx, piece, board, |sq| get_queen_attacks(sq, occupancies));
+    generate_pre_computed_moves(ctx, piece, board, kind, |sq| {
+        get_queen_attacks(sq, occupancies)
+    });
+}
+
+fn generate_king_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces, kind: MoveKind) {
+    if kind.includes_quiets() {
+        generate_castling_moves(ctx, piece);
+    }
+
+    generate_pre_computed_moves(ctx, piece, board, kind, |sq| attacks!(KING_ATTACKS)[sq]);
 }
 
-fn generate_king_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces) {
+fn generate_castling_moves(ctx: &mut MoveContext<'_>, piece: Pieces) {
     let king_side = match ctx.board.side_to_move {
         Side::White => CastlingRights::WHITE_K,
         Side::Black => CastlingRights::BLACK_K,
@@ -556,6 +763,347 @@ fn generate_king_moves(ctx: &mut MoveContext<'_>, board: BitBoard, piece: Pieces
             ))
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use milky_fen::parse_fen_string;
+
+    use super::*;
+    use crate::Milky;
+
+    fn generate(milky: &mut Milky, kind: MoveKind) -> Vec<Move> {
+        let mut ctx = milky.move_ctx();
+        generate_moves(&mut ctx, kind);
+
+        let mut moves = ctx.search.moves().copied().collect::<Vec<_>>();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_generate_moves_by_kind() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "rnbqkb1r/pp1p1ppp/5n2/2pPp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 4",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+
+        for fen in fens {
+            milky.load_position(parse_fen_string(fen).unwrap());
+
+            let all_moves = generate(&mut milky, MoveKind::AllMoves);
+            let captures = generate(&mut milky, MoveKind::Captures);
+            let quiets = generate(&mut milky, MoveKind::Quiets);
+
+            assert!(captures.iter().all(|m| m.is_capture()), "{fen}");
+            assert!(quiets.iter().all(|m| !m.is_capture()), "{fen}");
+
+            let mut combined = [captures, quiets].concat();
+            combined.sort();
+            assert_eq!(combined, all_moves, "{fen}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "search")]
+    fn test_move_picker_hands_out_moves_in_sorted_order() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        let picker = |milky: &mut Milky| {
+            let mut ctx = milky.move_ctx();
+            generate_moves(&mut ctx, MoveKind::AllMoves);
+            MovePicker::new(&mut SortContext {
+                board: ctx.board,
+                zobrist: ctx.zobrist,
+                search: ctx.search,
+                best_move: Move::default(),
+            })
+        };
+
+        let picked = picker(&mut milky).collect::<Vec<_>>();
+        let sorted = picker(&mut milky).sort().to_vec();
+        assert_eq!(picked.len(), 48);
+        assert_eq!(picked, sorted);
+
+        // captures come before quiet moves
+        let first_quiet = picked.iter().position(|m| !m.is_capture()).unwrap();
+        assert!(picked[first_quiet..].iter().all(|m| !m.is_capture()));
+    }
+
+    #[test]
+    fn test_underpromotions_are_skipped_when_requested() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        milky.load_position(parse_fen_string("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap());
+
+        let promotions = |milky: &mut Milky| {
+            generate(milky, MoveKind::AllMoves)
+                .into_iter()
+                .filter(|m| m.promotion().is_promoting())
+                .map(|m| m.promotion())
+                .collect::<Vec<_>>()
+        };
+
+        let every_promotion = promotions(&mut milky);
+        assert_eq!(every_promotion.len(), 12);
+
+        milky.search_state_mut().skip_underpromotions = true;
+        let filtered = promotions(&mut milky);
+        assert_eq!(filtered.len(), 6);
+        assert!(filtered.iter().all(|promotion| matches!(
+            promotion,
+            PromotionPieces::Knight | PromotionPieces::Queen
+        )));
+    }
 
-    generate_pre_computed_moves(ctx, piece, board, |square| attacks!(KING_ATTACKS)[square]);
+    /// Every move on positions with rooks and kings on their home squares, under every
+    /// combination of castling rights, checked against a straightforward square by square
+    /// reference of the `CASTLING_RIGHTS` table.
+    mod castling_rights {
+        use milky_bitboard::CastlingRights;
+
+        use super::*;
+        use crate::zobrist::GamePosition;
+
+        /// Rights lost when a piece leaves or lands on the square.
+        fn rights_lost(square: Square) -> CastlingRights {
+            match square {
+                Square::E1 => CastlingRights::WHITE_K | CastlingRights::WHITE_Q,
+                Square::H1 => CastlingRights::WHITE_K,
+                Square::A1 => CastlingRights::WHITE_Q,
+                Square::E8 => CastlingRights::BLACK_K | CastlingRights::BLACK_Q,
+                Square::H8 => CastlingRights::BLACK_K,
+                Square::A8 => CastlingRights::BLACK_Q,
+                _ => CastlingRights::empty(),
+            }
+        }
+
+        fn fen_rights(rights: CastlingRights) -> String {
+            let fen = [
+                (CastlingRights::WHITE_K, 'K'),
+                (CastlingRights::WHITE_Q, 'Q'),
+                (CastlingRights::BLACK_K, 'k'),
+                (CastlingRights::BLACK_Q, 'q'),
+            ]
+            .into_iter()
+            .filter(|(right, _)| rights.contains(*right))
+            .map(|(_, symbol)| symbol)
+            .collect::<String>();
+
+            if fen.is_empty() { String::from("-") } else { fen }
+        }
+
+        fn assert_rights_transitions(milky: &mut Milky, fen: &str) -> usize {
+            milky.load_position(parse_fen_string(fen).unwrap());
+            let rights = milky.board_state().castling_rights;
+            let moves = generate(milky, MoveKind::AllMoves);
+            let mut played = 0;
+
+            for piece_move in moves {
+                if !make_move(&mut milky.move_ctx(), piece_move, MoveKind::AllMoves) {
+                    continue;
+                }
+
+                let expected =
+                    rights - rights_lost(piece_move.source()) - rights_lost(piece_move.target());
+                let board = milky.board_state();
+                assert_eq!(board.castling_rights, expected, "{fen} {piece_move}");
+
+                let key = milky.zobrist().hash_position(GamePosition {
+                    boards: board.pieces,
+                    side_to_move: board.side_to_move,
+                    en_passant: board.en_passant,
+                    castling_rights: board.castling_rights,
+                });
+                assert_eq!(milky.zobrist().position, key, "{fen} {piece_move}");
+
+                milky.zobrist_mut().position = milky.board_state_mut().undo_move();
+                assert_eq!(milky.board_state().castling_rights, rights);
+                played += 1;
+            }
+
+            played
+        }
+
+        #[test]
+        fn test_castling_rights_transitions() {
+            crate::init_static_members();
+            let mut milky = Milky::new();
+
+            // knights and pawns of both sides can capture every rook on its home square, the
+            // pawns promoting as they do, and the last position has en passant available
+            let positions = [
+                "r3k2r/1P4P1/1N4N1/8/8/1n4n1/1p4p1/R3K2R w {} - 0 1",
+                "r3k2r/1P4P1/1N4N1/8/8/1n4n1/1p4p1/R3K2R b {} - 0 1",
+                "r3k2r/8/8/8/8/8/8/R3K2R w {} - 0 1",
+                "r3k2r/8/8/8/8/8/8/R3K2R b {} - 0 1",
+                "r3k2r/8/8/3pP3/8/8/8/R3K2R w {} d6 0 1",
+            ];
+
+            let mut captured_rooks = 0;
+            for bits in 0..16 {
+                let rights = fen_rights(CastlingRights::from_bits_retain(bits));
+
+                for position in positions {
+                    let fen = position.replace("{}", &rights);
+                    assert!(assert_rights_transitions(&mut milky, &fen) > 0, "{fen}");
+
+                    captured_rooks += generate(&mut milky, MoveKind::Captures)
+                        .iter()
+                        .filter(|m| !rights_lost(m.target()).is_empty())
+                        .count();
+                }
+            }
+
+            // per side, the first two positions have 4 + 4 promotions, 2 knight and 2 rook
+            // captures of rooks, and the rooks capture each other on the other three
+            assert_eq!(captured_rooks, 16 * (2 * 12 + 3 * 2));
+        }
+
+        #[test]
+        fn test_castling_moves_clear_both_rights() {
+            crate::init_static_members();
+            let mut milky = Milky::new();
+            milky.load_position(parse_fen_string("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap());
+
+            let castles = generate(&mut milky, MoveKind::AllMoves)
+                .into_iter()
+                .filter(|m| m.is_castling())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                castles,
+                [
+                    Move::castle_queenside(Side::White),
+                    Move::castle_kingside(Side::White)
+                ]
+            );
+
+            for castle in castles {
+                assert!(make_move(&mut milky.move_ctx(), castle, MoveKind::AllMoves));
+                assert_eq!(
+                    milky.board_state().castling_rights,
+                    CastlingRights::BLACK_K | CastlingRights::BLACK_Q
+                );
+                milky.zobrist_mut().position = milky.board_state_mut().undo_move();
+            }
+        }
+    }
+
+    fn can_castle_queenside(milky: &mut Milky, fen: &str) -> bool {
+        milky.load_position(parse_fen_string(fen).unwrap());
+        let side = milky.board_state().side_to_move;
+
+        // a king landing on an attacked square is only rejected when the move is played
+        let castle = Move::castle_queenside(side);
+        let is_legal = generate(milky, MoveKind::AllMoves).contains(&castle)
+            && make_move(&mut milky.move_ctx(), castle, MoveKind::AllMoves);
+        if is_legal {
+            milky.zobrist_mut().position = milky.board_state_mut().undo_move();
+        }
+
+        is_legal
+    }
+
+    #[test]
+    fn test_queenside_castling_b_file() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+
+        // the king never crosses the b-file, but the rook does, so a piece standing there blocks
+        // castling while an attack on it doesn't
+        let positions = [
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", true),
+            ("r3k2r/8/8/8/8/8/8/RN2K2R w KQkq - 0 1", false),
+            ("r3k2r/8/8/8/8/8/8/Rn2K2R w KQkq - 0 1", false),
+            ("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1", true),
+            ("2r1k3/8/8/8/8/8/8/R3K3 w Q - 0 1", false),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", true),
+            ("rn2k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", false),
+            ("rN2k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", false),
+            ("r3k3/8/8/8/8/8/8/1R2K3 b q - 0 1", true),
+            ("r3k3/8/8/8/8/8/8/2R1K3 b q - 0 1", false),
+        ];
+
+        for (fen, expected) in positions {
+            assert_eq!(can_castle_queenside(&mut milky, fen), expected, "{fen}");
+        }
+    }
+
+    /// Leaf counts of positions where pawns promote by capturing rooks on their home squares,
+    /// touching move generation, the castling rights table and the zobrist updates at once.
+    mod perft {
+        use super::*;
+
+        fn perft(milky: &mut Milky, depth: u8) -> usize {
+            if depth == 0 {
+                return 1;
+            }
+
+            generate_moves(&mut milky.move_ctx(), MoveKind::AllMoves);
+            let moves = milky.search_state().moves().copied().collect::<Vec<_>>();
+
+            let mut nodes = 0;
+            for piece_move in moves {
+                if !make_move(&mut milky.move_ctx(), piece_move, MoveKind::AllMoves) {
+                    continue;
+                }
+
+                nodes += perft(milky, depth - 1);
+                milky.zobrist_mut().position = milky.board_state_mut().undo_move();
+            }
+
+            nodes
+        }
+
+        fn assert_perft(fen: &str, expected_nodes: &[usize]) {
+            crate::init_static_members();
+            let mut milky = Milky::new();
+            milky.load_position(parse_fen_string(fen).unwrap());
+            let position = milky.zobrist().position;
+
+            for (depth, expected) in expected_nodes.iter().enumerate() {
+                assert_eq!(
+                    perft(&mut milky, depth as u8),
+                    *expected,
+                    "{fen} depth {depth}"
+                );
+                assert_eq!(milky.zobrist().position, position, "{fen}");
+            }
+        }
+
+        #[test]
+        fn test_perft_promotion_captures_white() {
+            assert_perft(
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                &[1, 6, 264, 9467, 422_333, 15_833_292],
+            );
+        }
+
+        #[test]
+        fn test_perft_promotion_captures_black() {
+            // the same position as above with the colors swapped
+            assert_perft(
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                &[1, 6, 264, 9467, 422_333, 15_833_292],
+            );
+        }
+
+        #[test]
+        fn test_perft_promotions() {
+            assert_perft(
+                "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+                &[1, 24, 496, 9483, 182_838, 3_605_103],
+            );
+        }
+    }
 }
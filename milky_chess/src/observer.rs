@@ -0,0 +1,378 @@
+use std::time::Duration;
+
+use milky_bitboard::Move;
+
+use crate::search_state::{MATE_LOWER_BOUND, MATE_UPPER_BOUND};
+
+/// Score of a position from the side to move point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative when the side to move is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Converts a raw search score, where mates are encoded close to the score bounds.
+    pub fn from_search_score(score: i32) -> Self {
+        if score > -MATE_UPPER_BOUND && score < -MATE_LOWER_BOUND {
+            Score::Mate(-(score + MATE_UPPER_BOUND) / 2)
+        } else if score > MATE_LOWER_BOUND && score < MATE_UPPER_BOUND {
+            Score::Mate((MATE_UPPER_BOUND - score) / 2 + 1)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Centipawns(score) => write!(f, "cp {score}"),
+            Score::Mate(moves) => write!(f, "mate {moves}"),
+        }
+    }
+}
+
+/// Results of a completed iterative deepening iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IterationInfo {
+    pub depth: u8,
+    /// Deepest ply reached so far, quiescence search included.
+    pub seldepth: u8,
+    pub score: Score,
+    pub nodes: u64,
+    /// Nodes where the transposition table entry was good enough to skip searching.
+    pub tt_hits: u64,
+    /// Permille of the transposition table used by this search.
+    pub hashfull: u16,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>,
+}
+
+impl IterationInfo {
+    /// Search speed since the start of the search, the elapsed time being rounded up to a
+    /// millisecond so the first iterations don't divide by zero.
+    pub fn nodes_per_second(&self) -> u64 {
+        nodes_per_second(self.nodes, self.elapsed)
+    }
+}
+
+fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    let elapsed_ms = elapsed.as_millis().max(1) as u64;
+    nodes.saturating_mul(1000) / elapsed_ms
+}
+
+/// A root move about to be searched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootMoveInfo {
+    pub depth: u8,
+    pub piece_move: Move,
+    /// Position of the move in the search order, starting at 1.
+    pub move_number: u32,
+    pub nodes: u64,
+}
+
+/// Progress of a search in the middle of an iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressInfo {
+    pub nodes: u64,
+    /// Permille of the transposition table used by this search.
+    pub hashfull: u16,
+    pub elapsed: Duration,
+}
+
+impl ProgressInfo {
+    /// Search speed since the start of the search, see [`IterationInfo::nodes_per_second`].
+    pub fn nodes_per_second(&self) -> u64 {
+        nodes_per_second(self.nodes, self.elapsed)
+    }
+}
+
+/// Results of a search, taken from the deepest iteration that completed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// Null when the game was already over and nothing was searched.
+    pub best_move: Move,
+    /// Reply expected to the best move, the second move of the principal variation.
+    pub ponder: Option<Move>,
+    pub score: Score,
+    /// Deepest iteration that was fully searched.
+    pub depth: u8,
+    /// Deepest ply reached, quiescence search included.
+    pub seldepth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    pub time: Duration,
+}
+
+/// Final results of a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopInfo {
+    pub best_move: Move,
+    /// Deepest iteration that was fully searched.
+    pub depth: u8,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+/// Receives structured progress of a search, for library users that want to monitor a search
+/// without parsing the UCI output.
+///
+/// Every method does nothing by default, so observers only implement what they care about.
+pub trait SearchObserver: Send {
+    fn on_iteration_complete(&mut self, _info: &IterationInfo) {}
+
+    fn on_root_move(&mut self, _info: &RootMoveInfo) {}
+
+    /// Called about once a second while the search runs, so long iterations still show signs
+    /// of life.
+    fn on_progress(&mut self, _info: &ProgressInfo) {}
+
+    fn on_stop(&mut self, _info: &StopInfo) {}
+
+    /// Free form diagnostics about the decisions of the engine, only sent in debug mode.
+    fn on_debug(&mut self, _text: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use milky_fen::parse_fen_string;
+
+    use super::*;
+    use crate::Milky;
+    use crate::time_manager::TimeControl;
+
+    #[derive(Debug, Default)]
+    struct Events {
+        iterations: Vec<IterationInfo>,
+        root_moves: Vec<RootMoveInfo>,
+        progress: Vec<ProgressInfo>,
+        stops: Vec<StopInfo>,
+        debug: Vec<String>,
+    }
+
+    struct Recorder(Arc<Mutex<Events>>);
+
+    impl SearchObserver for Recorder {
+        fn on_iteration_complete(&mut self, info: &IterationInfo) {
+            self.0.lock().unwrap().iterations.push(info.clone());
+        }
+
+        fn on_root_move(&mut self, info: &RootMoveInfo) {
+            self.0.lock().unwrap().root_moves.push(info.clone());
+        }
+
+        fn on_progress(&mut self, info: &ProgressInfo) {
+            self.0.lock().unwrap().progress.push(info.clone());
+        }
+
+        fn on_stop(&mut self, info: &StopInfo) {
+            self.0.lock().unwrap().stops.push(info.clone());
+        }
+
+        fn on_debug(&mut self, text: &str) {
+            self.0.lock().unwrap().debug.push(text.to_string());
+        }
+    }
+
+    #[test]
+    fn test_score_from_search_score() {
+        assert_eq!(Score::from_search_score(35), Score::Centipawns(35));
+        assert_eq!(
+            Score::from_search_score(MATE_UPPER_BOUND - 1),
+            Score::Mate(1)
+        );
+        assert_eq!(
+            Score::from_search_score(MATE_UPPER_BOUND - 3),
+            Score::Mate(2)
+        );
+        // mated on the second ply, right after the move of the side to move
+        assert_eq!(
+            Score::from_search_score(-MATE_UPPER_BOUND + 2),
+            Score::Mate(-1)
+        );
+        assert_eq!(
+            Score::from_search_score(-MATE_UPPER_BOUND + 4),
+            Score::Mate(-2)
+        );
+    }
+
+    #[test]
+    fn test_nodes_per_second() {
+        let info = |nodes, elapsed| IterationInfo {
+            depth: 1,
+            seldepth: 1,
+            score: Score::Centipawns(0),
+            nodes,
+            tt_hits: 0,
+            hashfull: 0,
+            elapsed,
+            pv: vec![],
+        };
+
+        assert_eq!(
+            info(5000, Duration::from_millis(250)).nodes_per_second(),
+            20_000
+        );
+        assert_eq!(info(300, Duration::ZERO).nodes_per_second(), 300_000);
+    }
+
+    #[test]
+    fn test_observer_receives_search_progress() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        let mut milky = Milky::new();
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+        milky.think(TimeControl::FixedDepth(4));
+
+        let events = events.lock().unwrap();
+        let depths = events
+            .iterations
+            .iter()
+            .map(|info| info.depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [1, 2, 3, 4]);
+
+        // every root move is reported on the first iteration
+        let first_iteration = events.root_moves.iter().filter(|info| info.depth == 1);
+        assert_eq!(first_iteration.count(), 20);
+
+        let last = events.iterations.last().unwrap();
+        assert_eq!(events.stops.len(), 1);
+        assert_eq!(events.stops[0].depth, 4);
+        assert_eq!(events.stops[0].best_move, last.pv[0]);
+        assert_eq!(events.stops[0].nodes, last.nodes);
+    }
+
+    #[test]
+    fn test_helper_results_are_reported_within_the_depth_limit() {
+        crate::init_static_members();
+
+        // helpers run ahead of the main thread more often than not with this many of them
+        for _ in 0..4 {
+            let events = Arc::new(Mutex::new(Events::default()));
+            let mut milky = Milky::new();
+            milky.options_mut().threads = 8;
+            let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+            milky.load_position(parse_fen_string(fen).unwrap());
+            milky.set_observer(Recorder(events.clone()));
+            let result = milky.think(TimeControl::FixedDepth(6));
+
+            let events = events.lock().unwrap();
+            let last = events.iterations.last().unwrap();
+            assert_eq!(result.depth, 6);
+            assert_eq!(last.depth, result.depth);
+            assert_eq!(last.pv, result.pv);
+        }
+    }
+
+    #[test]
+    fn test_root_moves_report_the_iteration_depth() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        // the root is in check, and searched a ply deeper than its iteration
+        let mut milky = Milky::new();
+        let fen = "k7/8/8/8/8/8/1q6/K7 w - - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+        milky.think(TimeControl::FixedDepth(3));
+
+        let events = events.lock().unwrap();
+        let depths = events
+            .root_moves
+            .iter()
+            .map(|info| info.depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_observer_receives_diagnostics_in_debug_mode() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        // fool's mate, there is nothing to search
+        let mut milky = Milky::new();
+        let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+
+        milky.think(TimeControl::FixedDepth(2));
+        assert!(events.lock().unwrap().debug.is_empty());
+
+        milky.options_mut().debug = true;
+        milky.think(TimeControl::FixedDepth(2));
+        assert_eq!(
+            events.lock().unwrap().debug,
+            ["not searching, the game is over: white is checkmated"]
+        );
+    }
+
+    #[test]
+    fn test_aspiration_fails_search_the_same_depth_again() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        let mut milky = Milky::new();
+        // the score of the third iteration falls out of the window, on both sides in turn
+        let fen = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+        milky.think(TimeControl::FixedDepth(5));
+
+        let events = events.lock().unwrap();
+        let depths = events
+            .iterations
+            .iter()
+            .map(|info| info.depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_progress_is_reported_during_long_searches() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+        milky.think(TimeControl::MoveTime(Duration::from_millis(1500)));
+
+        let events = events.lock().unwrap();
+        assert!(!events.progress.is_empty());
+        for progress in &events.progress {
+            assert!(progress.elapsed >= Duration::from_secs(1));
+            assert!(progress.nodes > 0);
+            assert!(progress.nodes <= events.stops[0].nodes);
+        }
+    }
+
+    #[test]
+    fn test_aborted_iteration_keeps_last_completed_pv() {
+        crate::init_static_members();
+        let events = Arc::new(Mutex::new(Events::default()));
+
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.set_observer(Recorder(events.clone()));
+        milky.think(TimeControl::FixedNodes(50_000));
+
+        let events = events.lock().unwrap();
+        let last = events.iterations.last().unwrap();
+        assert_eq!(events.stops[0].depth, last.depth);
+        assert_eq!(events.stops[0].best_move, last.pv[0]);
+
+        // the search stopped at the node limit, give or take the nodes entered while unwinding
+        assert!((50_000..50_016).contains(&events.stops[0].nodes));
+    }
+}
@@ -0,0 +1,296 @@
+use std::path::PathBuf;
+
+bitflags::bitflags! {
+    /// Search techniques that can be individually turned off, allowing the contribution of each one
+    /// to be measured by playing the engine against itself with and without it.
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    #[rustfmt::skip]
+    pub struct SearchFeatures: u16 {
+        const NULL_MOVE                    = 0b000000000001;
+        const LATE_MOVE_REDUCTION          = 0b000000000010;
+        const ASPIRATION_WINDOWS           = 0b000000000100;
+        const TRANSPOSITION_TABLE          = 0b000000001000;
+        const FUTILITY_PRUNING             = 0b000000010000;
+        /// Rook and bishop promotions are almost never better than a queen promotion, so the
+        /// search doesn't generate them. Perft and move listings always do.
+        const SKIP_UNDERPROMOTIONS         = 0b000000100000;
+        /// Quiescence search skips captures that lose material according to the static
+        /// exchange evaluation.
+        const SEE_PRUNING                  = 0b000001000000;
+        /// Nodes whose static evaluation is well above beta near the horizon fail high without
+        /// being searched.
+        const REVERSE_FUTILITY             = 0b000010000000;
+        /// Nodes whose static evaluation is well below alpha near the horizon are given up on
+        /// when quiescence search can't raise alpha either.
+        const RAZORING                     = 0b000100000000;
+        /// PV nodes without a hash move to order first are searched one ply shallower.
+        const INTERNAL_ITERATIVE_REDUCTION = 0b001000000000;
+        /// A hash move scoring well above every alternative is searched one ply deeper.
+        const SINGULAR_EXTENSION           = 0b010000000000;
+        /// Quiet moves past a move count growing with the remaining depth are skipped near the
+        /// horizon.
+        const LATE_MOVE_PRUNING            = 0b100000000000;
+    }
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl SearchFeatures {
+    /// Name of the (hidden) UCI option toggling each feature.
+    pub const OPTION_NAMES: [(&str, SearchFeatures); 12] = [
+        ("nullmove", SearchFeatures::NULL_MOVE),
+        ("lmr", SearchFeatures::LATE_MOVE_REDUCTION),
+        ("aspiration", SearchFeatures::ASPIRATION_WINDOWS),
+        ("tt", SearchFeatures::TRANSPOSITION_TABLE),
+        ("futility", SearchFeatures::FUTILITY_PRUNING),
+        ("skipunderpromotions", SearchFeatures::SKIP_UNDERPROMOTIONS),
+        ("seepruning", SearchFeatures::SEE_PRUNING),
+        ("reversefutility", SearchFeatures::REVERSE_FUTILITY),
+        ("razoring", SearchFeatures::RAZORING),
+        ("iir", SearchFeatures::INTERNAL_ITERATIVE_REDUCTION),
+        ("singular", SearchFeatures::SINGULAR_EXTENSION),
+        ("lmp", SearchFeatures::LATE_MOVE_PRUNING),
+    ];
+
+    /// Finds the feature toggled by the option with the given name, ignoring case.
+    pub fn from_option_name(name: &str) -> Option<SearchFeatures> {
+        Self::OPTION_NAMES
+            .iter()
+            .find(|(option, _)| option.eq_ignore_ascii_case(name))
+            .map(|(_, feature)| *feature)
+    }
+}
+
+/// Small pseudo random bonus added to the score of root moves early in the game, so games without
+/// an opening book don't all follow the same line.
+///
+/// The bonus only depends on the seed, the position and the move, so games are still reproducible
+/// for a given seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningJitter {
+    /// Largest bonus (or penalty) in centipawns, zero disables the jitter.
+    pub max_score: i32,
+    pub seed: u64,
+    /// Jitter is only applied while the game is within its first `moves` full moves.
+    pub moves: u32,
+}
+
+impl Default for OpeningJitter {
+    fn default() -> Self {
+        Self {
+            max_score: 0,
+            seed: 0,
+            moves: 8,
+        }
+    }
+}
+
+impl OpeningJitter {
+    /// Whether the jitter applies to a position `game_ply` plies into the game.
+    pub fn is_active(&self, game_ply: usize) -> bool {
+        self.max_score > 0 && game_ply / 2 < self.moves as usize
+    }
+
+    /// Bonus in `-max_score..=max_score` for playing the move with the given encoding on the
+    /// position with the given key.
+    pub(crate) fn score(&self, position: u64, piece_move: u32) -> i32 {
+        // splitmix64 finalizer, mixing the inputs well enough for neighbouring moves to get
+        // unrelated bonuses
+        let mut value =
+            self.seed ^ position ^ (piece_move as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^= value >> 31;
+
+        let range = 2 * self.max_score as u64 + 1;
+        (value % range) as i32 - self.max_score
+    }
+}
+
+/// Weakens the engine to play at about `elo`, so casual players get games they can win.
+///
+/// The search is capped at a node budget shrinking with the rating, and root moves get a pseudo
+/// random bonus growing as the rating drops, making the engine pick among the moves it considers
+/// close to the best one rather than always playing the best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrengthLimit {
+    pub enabled: bool,
+    /// Rating to play at, clamped to `MIN_ELO..=MAX_ELO`.
+    pub elo: u32,
+    /// Seed of the root move bonus, see [`OpeningJitter::seed`].
+    pub seed: u64,
+}
+
+impl Default for StrengthLimit {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            elo: 1500,
+            seed: 0,
+        }
+    }
+}
+
+impl StrengthLimit {
+    /// Rating points per centipawn of root move bonus, below the highest rating.
+    const ELO_PER_CENTIPAWN: u32 = 6;
+    /// Rating points doubling the node budget.
+    const ELO_PER_DOUBLING: u32 = 150;
+    pub const MAX_ELO: u32 = 2800;
+    pub const MIN_ELO: u32 = 1000;
+    /// Node budget at the lowest rating.
+    const MIN_NODES: u64 = 64;
+
+    fn elo(&self) -> u32 {
+        self.elo.clamp(Self::MIN_ELO, Self::MAX_ELO)
+    }
+
+    /// Nodes a search may visit when the strength is limited.
+    pub fn node_limit(&self) -> Option<u64> {
+        let doublings = (self.elo() - Self::MIN_ELO) / Self::ELO_PER_DOUBLING;
+        self.enabled.then(|| Self::MIN_NODES << doublings)
+    }
+
+    /// Bonus given to the root moves when the strength is limited, for the whole game.
+    pub fn jitter(&self) -> Option<OpeningJitter> {
+        let max_score = ((Self::MAX_ELO - self.elo()) / Self::ELO_PER_CENTIPAWN) as i32;
+
+        Some(OpeningJitter {
+            max_score,
+            seed: self.seed,
+            moves: u32::MAX,
+        })
+        .filter(|_| self.enabled && max_score > 0)
+    }
+}
+
+/// Nodes every millisecond of the clock is worth in deterministic mode, when `nodes_time` isn't
+/// set.
+pub const DETERMINISTIC_NODES_TIME: u64 = 1000;
+
+/// Runtime configurable engine settings, usually controlled through UCI `setoption`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOptions {
+    /// When non-zero, every millisecond of the clock is treated as this many nodes, making
+    /// time-based searches independent of the hardware they run on.
+    pub nodes_time: u64,
+    /// Search techniques enabled for the next searches, everything is enabled by default.
+    pub search_features: SearchFeatures,
+    /// Set through UCI `debug on`, makes the engine report extra diagnostics as `info string`.
+    pub debug: bool,
+    pub opening_jitter: OpeningJitter,
+    /// Centipawns the engine considers a draw worse than an equal position, so it avoids early
+    /// draws against weaker opponents. Negative values make it seek draws instead.
+    pub contempt: i32,
+    /// Replaces the opening jitter, and keeps the search on a single thread, while enabled.
+    pub strength_limit: StrengthLimit,
+    /// Amount of threads searching, the first one reports the progress and the others help it
+    /// by filling the transposition table (Lazy SMP).
+    pub threads: usize,
+    /// Whether the GUI lets the engine think on the opponent's time, `go ponder` is searched as a
+    /// regular `go` when it doesn't.
+    pub ponder: bool,
+    /// Makes the same search on the same position always visit the same nodes and find the same
+    /// move, for regression testing and debugging search changes. Searches run on a single
+    /// thread, with the clock converted into nodes (see `nodes_time`), a fixed seed for the
+    /// strength limit, and start from empty tables.
+    pub deterministic: bool,
+    /// File the positions learned from previous games are kept in, see [`crate::Milky::set_learning`].
+    pub learning_file: PathBuf,
+    /// File the transposition table is saved to and loaded from, see [`crate::Milky::save_hash`].
+    pub hash_file: PathBuf,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            nodes_time: 0,
+            search_features: SearchFeatures::default(),
+            debug: false,
+            opening_jitter: OpeningJitter::default(),
+            contempt: 0,
+            strength_limit: StrengthLimit::default(),
+            threads: crate::DEFAULT_THREADS,
+            ponder: false,
+            deterministic: false,
+            learning_file: PathBuf::from("milky.learn"),
+            hash_file: PathBuf::from("milky.hash"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_feature_is_enabled_by_default() {
+        assert_eq!(
+            EngineOptions::default().search_features,
+            SearchFeatures::all()
+        );
+    }
+
+    #[test]
+    fn test_feature_from_option_name() {
+        assert_eq!(
+            SearchFeatures::from_option_name("NullMove"),
+            Some(SearchFeatures::NULL_MOVE)
+        );
+        assert_eq!(
+            SearchFeatures::from_option_name("tt"),
+            Some(SearchFeatures::TRANSPOSITION_TABLE)
+        );
+        assert_eq!(SearchFeatures::from_option_name("hash"), None);
+
+        let all = SearchFeatures::OPTION_NAMES
+            .iter()
+            .fold(SearchFeatures::empty(), |acc, (_, feature)| acc | *feature);
+        assert_eq!(all, SearchFeatures::all());
+    }
+
+    #[test]
+    fn test_opening_jitter() {
+        let jitter = OpeningJitter {
+            max_score: 5,
+            seed: 42,
+            moves: 4,
+        };
+
+        let scores = (0..1000)
+            .map(|piece_move| jitter.score(0xABCD, piece_move))
+            .collect::<Vec<_>>();
+        assert!(scores.iter().all(|score| (-5..=5).contains(score)));
+        assert!(scores.contains(&-5) && scores.contains(&5));
+        assert_eq!(jitter.score(0xABCD, 7), scores[7]);
+
+        assert!(jitter.is_active(7));
+        assert!(!jitter.is_active(8));
+        assert!(!OpeningJitter::default().is_active(0));
+    }
+
+    #[test]
+    fn test_strength_limit() {
+        let limit = |elo| StrengthLimit {
+            enabled: true,
+            elo,
+            seed: 0,
+        };
+
+        assert_eq!(StrengthLimit::default().node_limit(), None);
+        assert_eq!(StrengthLimit::default().jitter(), None);
+
+        assert_eq!(limit(0).node_limit(), Some(64));
+        assert_eq!(limit(1150).node_limit(), Some(128));
+        assert!(limit(2000).node_limit() < limit(2500).node_limit());
+
+        assert_eq!(limit(0).jitter().unwrap().max_score, 300);
+        assert!(limit(1000).jitter().unwrap().is_active(500));
+        assert_eq!(limit(StrengthLimit::MAX_ELO).jitter(), None);
+    }
+}
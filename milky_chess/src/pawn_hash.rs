@@ -0,0 +1,87 @@
+//! Cache of the pawn structure evaluation, keyed by the pawns on the board alone.
+//!
+//! Pawns move far less often than the other pieces, so most positions a search visits share
+//! their pawn structure with positions it already evaluated, and the structure terms, which look
+//! at every pawn of both sides, can be read back instead of computed again.
+
+use milky_bitboard::BitBoard;
+
+use crate::zobrist::ZobristKey;
+
+/// Entries of the table, a power of two so the index of a key is its lower bits.
+const PAWN_HASH_ENTRIES: usize = 1 << 14;
+
+/// Evaluation of a pawn structure, see [`crate::evaluate::evaluate_pawns`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PawnEntry {
+    /// Pawn key of the structure, see [`crate::board::BoardState::pawn_key`].
+    pub key: ZobristKey,
+    /// Opening and endgame scores of the structure, from white's point of view.
+    pub score: [i32; 2],
+    /// Passed pawns of each side, indexed by [`milky_bitboard::Side`]. No term reads them yet,
+    /// they are kept for the ones looking at the pieces around passed pawns.
+    #[allow(dead_code)]
+    pub passed: [BitBoard; 2],
+    /// Squares attacked by the pawns of each side, indexed by [`milky_bitboard::Side`].
+    pub attacks: [BitBoard; 2],
+}
+
+/// Always replacing table of pawn structure evaluations. Every search thread has its own, as
+/// entries are cheap to compute again and the table is small enough to stay in cache.
+#[derive(Debug, Clone)]
+pub(crate) struct PawnHashTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl Default for PawnHashTable {
+    fn default() -> Self {
+        Self {
+            entries: vec![PawnEntry::default(); PAWN_HASH_ENTRIES],
+        }
+    }
+}
+
+impl PawnHashTable {
+    fn index(key: ZobristKey) -> usize {
+        key.inner() as usize & (PAWN_HASH_ENTRIES - 1)
+    }
+
+    pub fn probe(&self, key: ZobristKey) -> Option<PawnEntry> {
+        let entry = self.entries[Self::index(key)];
+        (entry.key == key).then_some(entry)
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        self.entries[Self::index(entry.key)] = entry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_found_by_their_key() {
+        let mut table = PawnHashTable::default();
+        let key = ZobristKey::from(0xABCD_0001);
+        let entry = PawnEntry {
+            key,
+            score: [-5, 30],
+            passed: [BitBoard::new(1 << 12), BitBoard::default()],
+            attacks: [BitBoard::new(1 << 3 | 1 << 5), BitBoard::default()],
+        };
+
+        assert_eq!(table.probe(key), None);
+        table.store(entry);
+        assert_eq!(table.probe(key), Some(entry));
+
+        // another structure landing on the same entry replaces it
+        let other = ZobristKey::from(key.inner() + PAWN_HASH_ENTRIES as u64);
+        table.store(PawnEntry {
+            key: other,
+            ..entry
+        });
+        assert_eq!(table.probe(key), None);
+        assert_eq!(table.probe(other).unwrap().score, [-5, 30]);
+    }
+}
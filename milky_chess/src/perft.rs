@@ -0,0 +1,239 @@
+//! Perft (performance test) walks every legal move sequence up to a depth and counts the leaves,
+//! the standard way of validating move generation against published reference counts.
+//!
+//! Besides the node count, the leaves can be tallied by the kind of move reaching them, matching
+//! the columns of the reference tables, which pinpoints which kind of move a generator gets
+//! wrong instead of only telling that the count is off.
+
+use milky_bitboard::Move;
+
+use crate::moves::{MoveContext, MoveKind, generate_moves, make_move};
+
+/// Leaves of a perft search, along with the kind of move that reached each of them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    /// Every capture, en passant captures included.
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    /// Moves giving check, checkmates included.
+    pub checks: u64,
+    pub checkmates: u64,
+}
+
+impl std::ops::AddAssign for PerftStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passants += other.en_passants;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.checkmates += other.checkmates;
+    }
+}
+
+impl std::fmt::Display for PerftStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nodes {} captures {} en_passants {} castles {} promotions {} checks {} checkmates {}",
+            self.nodes,
+            self.captures,
+            self.en_passants,
+            self.castles,
+            self.promotions,
+            self.checks,
+            self.checkmates
+        )
+    }
+}
+
+/// Generated moves are written to the move list of the search state, which every generation
+/// reuses, so they are copied out before playing any of them.
+fn generated_moves(ctx: &mut MoveContext<'_>) -> ([Move; 256], usize) {
+    generate_moves(ctx, MoveKind::AllMoves);
+    (ctx.search.moves, ctx.search.move_count)
+}
+
+/// Amount of leaves `depth` plies below the position.
+pub(crate) fn perft(ctx: &mut MoveContext<'_>, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let (moves, move_count) = generated_moves(ctx);
+    let mut nodes = 0;
+
+    for &piece_move in &moves[..move_count] {
+        if !make_move(ctx, piece_move, MoveKind::AllMoves) {
+            continue;
+        }
+
+        nodes += perft(ctx, depth - 1);
+        ctx.zobrist.position = ctx.board.undo_move();
+    }
+
+    nodes
+}
+
+/// Leaves `depth` plies below the position, tallied by the move reaching them.
+pub(crate) fn perft_stats(ctx: &mut MoveContext<'_>, depth: u8) -> PerftStats {
+    if depth == 0 {
+        return PerftStats {
+            nodes: 1,
+            ..Default::default()
+        };
+    }
+
+    let mut stats = PerftStats::default();
+    for (_, move_stats) in perft_divide(ctx, depth) {
+        stats += move_stats;
+    }
+
+    stats
+}
+
+/// Leaves `depth` plies below the position under each of its legal moves, in generation order.
+pub(crate) fn perft_divide(ctx: &mut MoveContext<'_>, depth: u8) -> Vec<(Move, PerftStats)> {
+    let (moves, move_count) = generated_moves(ctx);
+    let mut divided = vec![];
+
+    for &piece_move in &moves[..move_count] {
+        if !make_move(ctx, piece_move, MoveKind::AllMoves) {
+            continue;
+        }
+
+        let stats = match depth {
+            0 | 1 => leaf_stats(ctx, piece_move),
+            _ => perft_stats(ctx, depth - 1),
+        };
+        ctx.zobrist.position = ctx.board.undo_move();
+
+        divided.push((piece_move, stats));
+    }
+
+    divided
+}
+
+/// Tallies the leaf reached by `piece_move`, which was just played on the board.
+fn leaf_stats(ctx: &mut MoveContext<'_>, piece_move: Move) -> PerftStats {
+    let check = ctx.board.in_check();
+
+    PerftStats {
+        nodes: 1,
+        captures: piece_move.is_capture() as u64,
+        en_passants: piece_move.is_en_passant() as u64,
+        castles: piece_move.is_castling() as u64,
+        promotions: piece_move.promotion().is_promoting() as u64,
+        checks: check as u64,
+        checkmates: (check && !crate::san::has_legal_move(ctx)) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use milky_fen::parse_fen_string;
+
+    use super::*;
+    use crate::Milky;
+
+    /// Counts from the perft results page of the chess programming wiki, in the order of the
+    /// fields of [`PerftStats`].
+    fn assert_stats(fen: &str, expected: &[[u64; 7]]) {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        for (depth, counts) in (1..).zip(expected) {
+            let stats = milky.perft_stats(depth);
+            let actual = [
+                stats.nodes,
+                stats.captures,
+                stats.en_passants,
+                stats.castles,
+                stats.promotions,
+                stats.checks,
+                stats.checkmates,
+            ];
+
+            assert_eq!(&actual, counts, "{fen} depth {depth}");
+            assert_eq!(milky.perft(depth), stats.nodes, "{fen} depth {depth}");
+        }
+    }
+
+    #[test]
+    fn test_perft_stats_initial_position() {
+        assert_stats(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[
+                [20, 0, 0, 0, 0, 0, 0],
+                [400, 0, 0, 0, 0, 0, 0],
+                [8902, 34, 0, 0, 0, 12, 0],
+                [197_281, 1576, 0, 0, 0, 469, 8],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_perft_stats_kiwipete() {
+        assert_stats(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[
+                [48, 8, 0, 2, 0, 0, 0],
+                [2039, 351, 1, 91, 0, 3, 0],
+                [97_862, 17_102, 45, 3162, 0, 993, 1],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_perft_stats_endgame() {
+        assert_stats(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[
+                [14, 1, 0, 0, 0, 2, 0],
+                [191, 14, 0, 0, 0, 10, 0],
+                [2812, 209, 2, 0, 0, 267, 0],
+                [43_238, 3348, 123, 0, 0, 1680, 17],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_perft_stats_promotions() {
+        assert_stats(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[
+                [6, 0, 0, 0, 0, 0, 0],
+                [264, 87, 0, 6, 48, 10, 0],
+                [9467, 1021, 4, 0, 120, 38, 22],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        let divided = milky.perft_divide(2);
+        assert_eq!(divided.len(), 48);
+
+        let mut total = PerftStats::default();
+        for (_, stats) in &divided {
+            total += *stats;
+        }
+        assert_eq!(total, milky.perft_stats(2));
+
+        let castle = divided
+            .iter()
+            .find(|(piece_move, _)| piece_move.to_string() == "e1g1")
+            .unwrap();
+        assert_eq!(castle.1.nodes, 43);
+    }
+}
@@ -0,0 +1,218 @@
+//! Standard algebraic notation (SAN) for the legal moves of a position, used to show moves the way
+//! humans write them.
+
+use milky_bitboard::{Move, PieceKind, Pieces, Side, Square};
+
+use crate::moves::{MoveContext, MoveKind, generate_moves, make_move};
+
+/// A legal move of a position along with how it is written in SAN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegalMove {
+    pub piece_move: Move,
+    pub san: String,
+    /// Whether the move gives check, which is also true when it mates.
+    pub check: bool,
+    pub mate: bool,
+}
+
+/// Generated moves are written to the move list of the search state, which is reused by every
+/// generation, so they are copied out before playing any of them.
+fn generated_moves(ctx: &mut MoveContext<'_>) -> Vec<Move> {
+    generate_moves(ctx, MoveKind::AllMoves);
+    ctx.search.moves().copied().collect()
+}
+
+fn is_in_check(ctx: &MoveContext<'_>) -> bool {
+    let king = match ctx.board.side_to_move {
+        Side::White => Pieces::WhiteKing,
+        Side::Black => Pieces::BlackKing,
+        _ => unreachable!(),
+    };
+
+    let king_square = ctx.board.pieces[king].trailing_zeros();
+    ctx.board
+        .is_square_attacked(king_square, ctx.board.side_to_move.enemy())
+}
+
+pub(crate) fn has_legal_move(ctx: &mut MoveContext<'_>) -> bool {
+    generated_moves(ctx).into_iter().any(|piece_move| {
+        let is_legal = make_move(ctx, piece_move, MoveKind::AllMoves);
+        if is_legal {
+            ctx.zobrist.position = ctx.board.undo_move();
+        }
+        is_legal
+    })
+}
+
+/// Every legal move of the position, in generation order.
+pub(crate) fn legal_moves(ctx: &mut MoveContext<'_>) -> Vec<LegalMove> {
+    let mut legal_moves = vec![];
+
+    for piece_move in generated_moves(ctx) {
+        if !make_move(ctx, piece_move, MoveKind::AllMoves) {
+            continue;
+        }
+
+        let check = is_in_check(ctx);
+        let mate = check && !has_legal_move(ctx);
+        ctx.zobrist.position = ctx.board.undo_move();
+
+        legal_moves.push(LegalMove {
+            piece_move,
+            san: String::new(),
+            check,
+            mate,
+        });
+    }
+
+    let moves = legal_moves
+        .iter()
+        .map(|legal_move| legal_move.piece_move)
+        .collect::<Vec<_>>();
+
+    for legal_move in legal_moves.iter_mut() {
+        legal_move.san = to_san(legal_move, &moves);
+    }
+
+    legal_moves
+}
+
+fn to_san(legal_move: &LegalMove, legal_moves: &[Move]) -> String {
+    let piece_move = legal_move.piece_move;
+    let source = piece_move.source().to_string();
+    let target = piece_move.target().to_string();
+
+    let mut san = if piece_move.is_castling() {
+        match piece_move.target() {
+            Square::G1 | Square::G8 => String::from("O-O"),
+            _ => String::from("O-O-O"),
+        }
+    } else if piece_move.piece().kind() == PieceKind::Pawn {
+        let mut san = String::new();
+
+        if piece_move.is_capture() {
+            san.push_str(&source[..1]);
+            san.push('x');
+        }
+
+        san.push_str(&target);
+
+        if piece_move.promotion().is_promoting() {
+            san.push('=');
+            san.push_str(&piece_move.promotion().to_string().to_uppercase());
+        }
+
+        san
+    } else {
+        let mut san = piece_move.piece().to_string().to_uppercase();
+        san.push_str(&disambiguation(piece_move, legal_moves));
+
+        if piece_move.is_capture() {
+            san.push('x');
+        }
+
+        san.push_str(&target);
+        san
+    };
+
+    match (legal_move.mate, legal_move.check) {
+        (true, _) => san.push('#'),
+        (false, true) => san.push('+'),
+        _ => {}
+    }
+
+    san
+}
+
+/// The part of the source square needed to tell the move apart from moves of the same kind of
+/// piece to the same square: the file when it is enough, then the rank, and the full square when
+/// neither is.
+fn disambiguation(piece_move: Move, legal_moves: &[Move]) -> String {
+    let source = piece_move.source();
+    let ambiguous = legal_moves
+        .iter()
+        .filter(|other| {
+            other.piece() == piece_move.piece()
+                && other.target() == piece_move.target()
+                && other.source() != source
+        })
+        .collect::<Vec<_>>();
+
+    let source_name = source.to_string();
+
+    if ambiguous.is_empty() {
+        String::new()
+    } else if ambiguous
+        .iter()
+        .all(|other| other.source().file() != source.file())
+    {
+        source_name[..1].to_string()
+    } else if ambiguous
+        .iter()
+        .all(|other| other.source().rank() != source.rank())
+    {
+        source_name[1..].to_string()
+    } else {
+        source_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use milky_fen::parse_fen_string;
+
+    use crate::Milky;
+
+    fn san_moves(fen: &str) -> Vec<String> {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        let mut moves = milky
+            .legal_moves()
+            .into_iter()
+            .map(|legal_move| legal_move.san)
+            .collect::<Vec<_>>();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn test_start_position_san() {
+        let moves = san_moves("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+
+        assert_eq!(moves.len(), 20);
+        assert!(moves.contains(&String::from("e4")));
+        assert!(moves.contains(&String::from("Nf3")));
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        // both knights reach d2 and both rooks, on the same file, reach a2
+        let moves = san_moves("4k3/8/R7/8/8/8/8/RN2KN2 w - - 0 1");
+        assert!(moves.contains(&String::from("Nbd2")));
+        assert!(moves.contains(&String::from("Nfd2")));
+        assert!(moves.contains(&String::from("R6a2")));
+        assert!(moves.contains(&String::from("R1a2")));
+
+        // the queen on e4 shares a file with one queen reaching h1 and a rank with the other
+        let moves = san_moves("1k6/8/8/8/4Q2Q/8/K7/4Q3 w - - 0 1");
+        assert!(moves.contains(&String::from("Qe4h1")));
+        assert!(moves.contains(&String::from("Qhh1")));
+        assert!(moves.contains(&String::from("Q1h1")));
+    }
+
+    #[test]
+    fn test_san_flags() {
+        // castling, en passant, promotion with capture and mate
+        let moves = san_moves("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        assert!(moves.contains(&String::from("O-O")));
+        assert!(moves.contains(&String::from("O-O-O")));
+        assert!(moves.contains(&String::from("exd6")));
+        assert!(moves.contains(&String::from("bxa8=Q+")));
+        assert!(moves.contains(&String::from("b8=N")));
+
+        let moves = san_moves("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(moves.contains(&String::from("Ra8#")));
+    }
+}
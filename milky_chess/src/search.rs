@@ -1,214 +1,782 @@
 use std::num::Wrapping;
+use std::sync::OnceLock;
 
-use milky_bitboard::{Move, Pieces, Side, Square};
+use milky_bitboard::{Move, Square};
 
+use crate::cuckoo::has_upcoming_repetition;
 use crate::evaluate::{EvalContext, evaluate_position};
-use crate::moves::{MoveContext, MoveKind, SortContext, generate_moves, make_move, sort_moves};
+use crate::game_status::FIFTY_MOVE_PLIES;
+use crate::moves::{
+    MoveContext, MoveKind, MovePicker, SortContext, generate_captures_and_checks, generate_moves,
+    make_move,
+};
+use crate::observer::{IterationInfo, ProgressInfo, RootMoveInfo, Score, SearchObserver};
+use crate::options::{OpeningJitter, SearchFeatures};
+use crate::san::has_legal_move;
+use crate::search_state::{
+    INFINITY, MATE_LOWER_BOUND, MATE_UPPER_BOUND, SearchStack, SearchState, history_bonus,
+};
 use crate::time_manager::{TimeManager, TimeManagerContext};
-use crate::transposition_table::{TTFlag, TranspositionTable};
-use crate::zobrist::Zobrist;
-use crate::{BoardState, MAX_PLY};
-
-pub static INFINITY: i32 = 50000;
-pub static MATE_UPPER_BOUND: i32 = 49000;
-pub static MATE_LOWER_BOUND: i32 = 48000;
-
-pub type HistoryMoves = [[i32; 64]; 12];
-pub type KillerMoves = [[Move; 64]; 2];
-
-fn is_repetition(ctx: &SearchContext<'_>) -> bool {
-    ctx.board.repetition_table[0..ctx.board.repetition_index].contains(&ctx.zobrist.position)
-}
+use crate::transposition_table::{TTEntry, TTFlag, TranspositionTable};
+use crate::zobrist::{Zobrist, ZobristKey};
+use crate::{BoardState, GameHistory, MAX_PLY};
+
+/// Amount of nodes searched between two checks of the search limits.
+const ABORT_CHECK_INTERVAL: u64 = 2048;
+
+/// Quiescence plies, counted from where the main search ends, that also look at quiet moves
+/// giving check, which lets short mating sequences be seen past the horizon.
+const QUIESCENCE_CHECK_PLIES: u8 = 1;
+
+/// Deepest remaining depth at which reverse futility pruning cuts nodes off.
+const REVERSE_FUTILITY_LIMIT: u8 = 3;
+/// Margin, per ply of remaining depth, the static evaluation must stay above beta by for a node
+/// to be cut off by reverse futility pruning.
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+
+/// Deepest remaining depth at which razoring drops nodes into quiescence search.
+const RAZORING_LIMIT: u8 = 3;
+/// Margin, per ply of remaining depth, the static evaluation must stay below alpha by for a node
+/// to be razored.
+const RAZORING_MARGIN: i32 = 250;
+
+/// Shallowest remaining depth at which a PV node without a hash move is reduced.
+const IIR_LIMIT: u8 = 4;
+
+/// Plies every late move is reduced by, before the part growing with the depth and the move
+/// number.
+pub const LMR_BASE: f64 = 0.75;
+/// Divisor of the part of the reduction growing with the depth and the move number, larger
+/// divisors reduce late moves less.
+pub const LMR_DIVISOR: f64 = 2.25;
+
+/// Reduction of a late move, indexed by the remaining depth and the number of moves searched
+/// before it, see [`init_reductions`].
+static LMR_TABLE: OnceLock<[[u8; 64]; 64]> = OnceLock::new();
+
+/// History score above which a quiet move isn't reduced further at nodes that aren't improving.
+const LMR_HISTORY_THRESHOLD: i32 = 100;
+
+/// Shallowest remaining depth at which a null move cutoff is verified by a reduced search of the
+/// node itself.
+const NULL_MOVE_VERIFICATION_LIMIT: u8 = 8;
+
+/// Moves of each kind searched before a cutoff that are punished in the histories, moves later
+/// than these are rarely tried early anyway.
+const HISTORY_MALUS_MOVES: usize = 32;
+
+/// Deepest remaining depth at which late move pruning skips quiet moves.
+const LMP_LIMIT: u8 = 3;
+
+/// Shallowest remaining depth at which the hash move is checked for being singular.
+const SINGULAR_LIMIT: u8 = 6;
+/// How much deeper than the hash move entry the node can be for its score to still be trusted.
+const SINGULAR_DEPTH_SLACK: u8 = 3;
+/// Margin, per ply of remaining depth, every alternative must stay below the hash move score by
+/// for the hash move to be singular.
+const SINGULAR_MARGIN: i32 = 2;
+
+/// Depth at which the best move is checked for being an easy move, deep enough for the scores to
+/// be meaningful and early enough to save most of the allocated time.
+const EASY_MOVE_DEPTH: u8 = 8;
+/// Iterations in a row the best move must have stayed the same for it to be an easy move.
+const EASY_MOVE_STABILITY: u32 = 4;
+/// How much better than every other move the best move must be for it to be an easy move.
+const EASY_MOVE_MARGIN: i32 = 300;
 
 pub struct SearchContext<'ctx> {
-    pub transposition_table: &'ctx mut TranspositionTable,
+    /// Shared by every search thread.
+    pub transposition_table: &'ctx TranspositionTable,
     pub board: &'ctx mut BoardState,
     pub zobrist: &'ctx mut Zobrist,
+    /// Positions played in the game before the root, shared by every search thread.
+    pub game_history: &'ctx GameHistory,
+    /// Moves the search is restricted to at the root, every move is searched when empty.
+    pub(crate) root_moves: &'ctx [Move],
+    pub(crate) search_path: SearchPath,
     pub(crate) time_manager: TimeManager,
+    pub(crate) features: SearchFeatures,
+    pub(crate) observer: Option<&'ctx mut dyn SearchObserver>,
+    pub(crate) debug: bool,
+    /// Set when root move scores should be jittered for this search.
+    pub(crate) opening_jitter: Option<OpeningJitter>,
+    /// Centipawns a draw is worth less than an equal position to the side to move at the root.
+    pub(crate) contempt: i32,
+    /// Depth of the first iteration, helper threads start at different depths so they don't all
+    /// search the same tree in lockstep.
+    pub(crate) start_depth: u8,
 }
 
-pub struct SearchState {
-    pub nodes: u64,
-    pub score_pv: bool,
-    pub follow_pv: bool,
-    pub killer_moves: KillerMoves,
-    pub history_moves: HistoryMoves,
-    pub pv_table: [[Move; MAX_PLY]; MAX_PLY],
-    pub pv_length: [usize; MAX_PLY],
-
-    pub moves: [Move; 256],
-    pub move_count: usize,
+/// Keys of the positions the search played through to reach the current node, from the root
+/// down. Each is pushed right before a move (or null move) is made, and popped once it is
+/// undone, leaving the game history alone.
+#[derive(Debug, Default)]
+pub(crate) struct SearchPath {
+    keys: Vec<ZobristKey>,
 }
 
-impl Default for SearchState {
-    fn default() -> Self {
-        Self::new()
+impl SearchPath {
+    pub(crate) fn keys(&self) -> &[ZobristKey] {
+        &self.keys
     }
 }
 
-impl SearchState {
-    pub fn new() -> Self {
-        Self {
-            nodes: 0,
-            move_count: 0,
-            score_pv: false,
-            follow_pv: false,
+impl SearchContext<'_> {
+    /// Goes one ply deeper, right before a move is made on the board.
+    fn enter_ply(&mut self) {
+        self.search_path.keys.push(self.zobrist.position);
+        self.board.ply += 1;
+    }
 
-            moves: [Move::default(); 256],
-            history_moves: [[0; 64]; 12],
-            killer_moves: [[Move::default(); 64]; 2],
+    /// Comes back up one ply, once the move is undone or turned out to be illegal.
+    fn leave_ply(&mut self) {
+        self.search_path.keys.pop();
+        self.board.ply -= 1;
+    }
 
-            pv_length: [0; MAX_PLY],
-            pv_table: [[Move::default(); MAX_PLY]; MAX_PLY],
+    /// Passes the turn to the other side, right after entering the ply of the null move.
+    ///
+    /// No game reaches the positions after a null move, so it ends the repetition scans like an
+    /// irreversible move would, otherwise two null moves in a row would bring the position back
+    /// as a fake repetition. `undo_move` restores the fifty move counter.
+    fn make_null_move(&mut self) {
+        if self.board.en_passant.is_available() {
+            self.zobrist.position ^= self.zobrist.en_passant[self.board.en_passant];
         }
+
+        self.board.fifty_move_counter = 0;
+        self.board.en_passant = Square::OffBoard;
+        self.board.side_to_move = self.board.side_to_move.enemy();
+        self.zobrist.position ^= self.zobrist.side_key;
     }
 
-    pub fn moves(&self) -> impl Iterator<Item = &Move> {
-        self.moves[..self.move_count].iter()
+    /// Whether the current position already happened, along the search path or in the game.
+    fn is_repetition(&self) -> bool {
+        self.game_history.is_repetition(
+            self.search_path.keys(),
+            self.zobrist.position,
+            self.board.fifty_move_counter,
+        )
     }
 
-    pub fn best_move(&self) -> Move {
-        self.pv_table[0][0]
+    /// Sends a diagnostic to the observer in debug mode, the text is only built when it is sent.
+    fn debug_string(&mut self, text: impl FnOnce() -> String) {
+        if let (true, Some(observer)) = (self.debug, self.observer.as_deref_mut()) {
+            observer.on_debug(&text());
+        }
     }
 
-    pub fn enable_pv_scoring(&mut self, game_ply: usize) {
-        self.follow_pv = false;
+    /// Score of a draw for the side to move, the side to move at the root takes it as
+    /// `contempt` worse than an equal position, and its opponent as that much better.
+    fn draw_score(&self) -> i32 {
+        match self.board.ply % 2 == 0 {
+            true => -self.contempt,
+            false => self.contempt,
+        }
+    }
+}
 
-        for piece_move in self.moves.into_iter().take(self.move_count) {
-            if self.pv_table[0][game_ply] == piece_move {
-                self.score_pv = true;
-                self.follow_pv = true;
+/// How the root score behaves across iterations, to spot searches whose score keeps failing out
+/// of the aspiration window or jumping around between depths.
+///
+/// Unstable searches get wider aspiration windows, as narrow ones would keep failing, and more
+/// time, as the best move is likely to change.
+#[derive(Debug, Default)]
+struct SearchStability {
+    /// Aspiration window fails since the last iteration that stayed inside a narrow window.
+    aspiration_fails: u32,
+    /// Completed iterations in a row whose score moved further than the aspiration window from
+    /// the previous one.
+    oscillations: u32,
+    last_score: Option<i32>,
+    /// Completed iterations in a row that ended with the same best move.
+    best_move_iterations: u32,
+    last_best_move: Move,
+}
+
+impl SearchStability {
+    const ASPIRATION_WINDOW: i32 = 50;
+    const UNSTABLE_THRESHOLD: u32 = 2;
+
+    fn record_fail(&mut self) {
+        self.aspiration_fails += 1;
+    }
+
+    /// Records the score of an iteration that completed, `narrow_window` telling whether it was
+    /// searched with an aspiration window rather than the full one.
+    fn record_score(&mut self, score: i32, narrow_window: bool) {
+        if narrow_window {
+            self.aspiration_fails = 0;
+        }
+
+        match self.last_score {
+            Some(last_score) if (score - last_score).abs() > Self::ASPIRATION_WINDOW => {
+                self.oscillations += 1
             }
+            _ => self.oscillations = 0,
         }
+
+        self.last_score = Some(score);
     }
 
-    pub fn push_move(&mut self, piece_move: Move) {
-        self.moves[self.move_count] = piece_move;
-        self.move_count += 1;
+    fn record_best_move(&mut self, best_move: Move) {
+        match best_move == self.last_best_move {
+            true => self.best_move_iterations += 1,
+            false => self.best_move_iterations = 1,
+        }
+
+        self.last_best_move = best_move;
     }
 
-    pub fn search_position(&mut self, mut ctx: SearchContext<'_>) {
-        const ASPIRATION_WINDOW: i32 = 50;
+    fn is_unstable(&self) -> bool {
+        self.aspiration_fails >= Self::UNSTABLE_THRESHOLD
+            || self.oscillations >= Self::UNSTABLE_THRESHOLD
+    }
 
+    /// Half width of the aspiration window, doubled for every sign of instability once the
+    /// search is unstable.
+    fn aspiration_window(&self) -> i32 {
+        match self.is_unstable() {
+            true => Self::ASPIRATION_WINDOW << (self.aspiration_fails + self.oscillations).min(4),
+            false => Self::ASPIRATION_WINDOW,
+        }
+    }
+
+    /// Extends the time allocation when the search just became unstable, reporting it in debug
+    /// mode.
+    fn on_iteration(&self, ctx: &mut SearchContext<'_>, was_unstable: bool, depth: u8) {
+        if was_unstable || !self.is_unstable() {
+            return;
+        }
+
+        let extended = ctx.time_manager.extend();
+
+        ctx.debug_string(|| {
+            format!(
+                "unstable search depth {depth} aspiration_fails {} oscillations {} \
+                 time_extended {extended}",
+                self.aspiration_fails, self.oscillations
+            )
+        });
+    }
+}
+
+/// Precomputes the late move reductions, which grow with the logarithms of the remaining depth and
+/// of the number of moves searched before the move: the deeper the node and the later the move,
+/// the less likely the move is to be better than the ones before it.
+pub(crate) fn init_reductions() {
+    LMR_TABLE.get_or_init(|| {
+        let mut table = [[0; 64]; 64];
+
+        for (depth, reductions) in table.iter_mut().enumerate().skip(1) {
+            for (move_number, reduction) in reductions.iter_mut().enumerate().skip(1) {
+                let scaled = (depth as f64).ln() * (move_number as f64).ln() / LMR_DIVISOR;
+                // every move reaching late move reduction is reduced by at least a ply
+                *reduction = ((LMR_BASE + scaled) as u8).max(1);
+            }
+        }
+
+        table
+    });
+}
+
+fn late_move_reduction(depth: u8, moves_searched: i32) -> u8 {
+    LMR_TABLE.get().unwrap()[(depth as usize).min(63)][(moves_searched as usize).min(63)]
+}
+
+impl SearchState {
+    pub fn search_position(&mut self, mut ctx: SearchContext<'_>) {
         self.nodes = 0;
+        self.seldepth = 0;
+        self.tt_hits = 0;
         self.follow_pv = false;
         self.score_pv = false;
 
-        self.killer_moves = [[Move::default(); 64]; 2];
-        self.history_moves = [[0; 64]; 12];
+        self.stack = SearchStack::default();
+        self.age_histories();
         self.pv_table = [[Move::default(); MAX_PLY]; MAX_PLY];
         self.pv_length = [0; MAX_PLY];
+        self.root_move_nodes.clear();
+        // an underpromotion asked for at the root must still be generated
+        self.skip_underpromotions = ctx.features.contains(SearchFeatures::SKIP_UNDERPROMOTIONS)
+            && ctx.root_moves.is_empty();
+
+        // lowest score of a mate within the moves of `go mate`, pruning away the quiet moves of
+        // the defending side could turn a line it escapes from into a mate
+        let mate_bound = ctx
+            .time_manager
+            .mate_in()
+            .map(|moves| MATE_UPPER_BOUND - 2 * moves as i32);
+        if mate_bound.is_some() {
+            ctx.features
+                .remove(SearchFeatures::FUTILITY_PRUNING | SearchFeatures::LATE_MOVE_PRUNING);
+        }
 
         let mut alpha = Wrapping(-INFINITY);
         let mut beta = Wrapping(INFINITY);
-
-        let mut curr_depth = 1;
-
-        while !ctx.time_manager.should_stop(TimeManagerContext {
-            depth: curr_depth,
-            nodes: self.nodes,
-        }) {
+        // half width of the aspiration window, widened on every fail of the current depth
+        let mut window = SearchStability::ASPIRATION_WINDOW;
+
+        let mut curr_depth = ctx.start_depth.max(1);
+        self.completed_depth = 0;
+        self.score = 0;
+        self.stopped = false;
+        self.node_limit = ctx.time_manager.node_limit().unwrap_or(u64::MAX);
+        let mut stability = SearchStability::default();
+        let mut easy_move_checked = false;
+
+        // helper threads search without limits until they are stopped, they can't go deeper than
+        // the ply tables allow
+        while (curr_depth as usize) < MAX_PLY && !self.should_stop(&mut ctx, curr_depth) {
             self.follow_pv = true;
 
+            let was_unstable = stability.is_unstable();
+            let narrow_window = beta.0 - alpha.0 < 2 * INFINITY;
+
+            let previous_pv = (self.pv_table[0], self.pv_length[0]);
+            self.iteration_depth = curr_depth;
             let score = self.negamax(&mut ctx, alpha, beta, curr_depth);
-            if score <= alpha.0 || score >= beta.0 {
-                alpha = Wrapping(-INFINITY);
-                beta = Wrapping(INFINITY);
+
+            // the iteration was cut short, its scores and principal variation can't be trusted,
+            // so the one of the last completed iteration is kept
+            if self.stopped {
+                (self.pv_table[0], self.pv_length[0]) = previous_pv;
+                break;
+            }
+
+            // Mate search:
+            //
+            // Once the first iteration found a move to play, `go mate` searches with a window
+            // only holding the scores of mates within the requested moves, which mate distance
+            // pruning turns into a search of the lines short enough to hold one. Failing low
+            // means there is no such mate at this depth, so the next depth is searched.
+            if mate_bound.is_some() && self.completed_depth > 0 && score <= alpha.0 {
+                (self.pv_table[0], self.pv_length[0]) = previous_pv;
                 curr_depth += 1;
                 continue;
             }
 
-            alpha = Wrapping(score - ASPIRATION_WINDOW);
-            beta = Wrapping(score + ASPIRATION_WINDOW);
+            // Aspiration fail:
+            //
+            // The score fell out of the window, so the same depth is searched again with the
+            // window widened on the side it fell out of, the other bound still holds. The window
+            // doubles on every fail, until it spans every score.
+            if score <= alpha.0 || score >= beta.0 {
+                stability.record_fail();
+                stability.on_iteration(&mut ctx, was_unstable, curr_depth);
 
-            if self.pv_length[0] > 0 {
-                if score > -MATE_UPPER_BOUND && score < -MATE_LOWER_BOUND {
-                    print!(
-                        "info score mate {} depth {curr_depth} nodes {} pv ",
-                        -(score + MATE_UPPER_BOUND) / 2 - 1,
-                        self.nodes,
-                    )
-                } else if score > MATE_LOWER_BOUND && score < MATE_UPPER_BOUND {
-                    print!(
-                        "info score mate {} depth {curr_depth} nodes {} pv ",
-                        (MATE_UPPER_BOUND - score) / 2 + 1,
-                        self.nodes,
-                    )
-                } else {
-                    print!(
-                        "info score cp {score} depth {curr_depth} nodes {} pv ",
-                        self.nodes
-                    );
+                window = window.saturating_mul(2);
+                match score <= alpha.0 {
+                    true => alpha = Wrapping(score.saturating_sub(window).max(-INFINITY)),
+                    false => beta = Wrapping(score.saturating_add(window).min(INFINITY)),
+                }
+                continue;
+            }
+
+            stability.record_score(score, narrow_window);
+            stability.on_iteration(&mut ctx, was_unstable, curr_depth);
+
+            match mate_bound {
+                Some(mate_bound) => {
+                    alpha = Wrapping(mate_bound);
+                    beta = Wrapping(INFINITY);
                 }
+                None if ctx.features.contains(SearchFeatures::ASPIRATION_WINDOWS) => {
+                    window = stability.aspiration_window();
+                    alpha = Wrapping(score - window);
+                    beta = Wrapping(score + window);
+                }
+                None => {}
+            }
+
+            if let Some(ply) = self.verify_pv(ctx.board, ctx.zobrist) {
+                let illegal_move = self.pv_table[0][ply];
+                ctx.debug_string(|| {
+                    format!(
+                        "pv truncated at ply {} on illegal move {illegal_move}",
+                        ply + 1
+                    )
+                });
+            }
+
+            self.extend_pv(
+                ctx.board,
+                ctx.zobrist,
+                ctx.transposition_table,
+                curr_depth as usize,
+            );
 
-                for idx in 0..self.pv_length[0] {
-                    print!("{} ", self.pv_table[0][idx]);
+            if self.pv_length[0] > 0 {
+                let pv = self.pv_table[0][..self.pv_length[0]].to_vec();
+                let info = IterationInfo {
+                    depth: curr_depth,
+                    seldepth: self.seldepth,
+                    score: Score::from_search_score(score),
+                    nodes: self.nodes,
+                    tt_hits: self.tt_hits,
+                    hashfull: ctx.transposition_table.hashfull(),
+                    elapsed: ctx.time_manager.elapsed(),
+                    pv,
+                };
+
+                if let Some(observer) = ctx.observer.as_deref_mut() {
+                    observer.on_iteration_complete(&info);
                 }
 
-                println!();
+                self.completed_depth = curr_depth;
+                self.score = score;
+                stability.record_best_move(self.best_move());
+            }
+
+            if mate_bound.is_some_and(|mate_bound| score > mate_bound) {
+                break;
+            }
+
+            // Easy move:
+            //
+            // A best move that stayed the same since the early iterations and is far better than
+            // any alternative, like recapturing a piece, won't change by thinking longer, so most
+            // of the time allocated to it is saved for later moves.
+            if !easy_move_checked
+                && curr_depth >= EASY_MOVE_DEPTH
+                && stability.best_move_iterations >= EASY_MOVE_STABILITY
+                && !stability.is_unstable()
+                && score.abs() < MATE_LOWER_BOUND
+                && ctx.time_manager.can_play_easy_move()
+            {
+                easy_move_checked = true;
+
+                if self.is_easy_move(&mut ctx, score, curr_depth / 2) {
+                    ctx.time_manager.play_easy_move();
+
+                    let best_move = self.best_move();
+                    ctx.debug_string(|| format!("easy move {best_move} depth {curr_depth}"));
+                }
             }
 
             curr_depth += 1;
         }
+
+        self.skip_underpromotions = false;
+
+        if let Some(report) = ctx.time_manager.time_report() {
+            ctx.debug_string(|| report.to_string());
+        }
+    }
+
+    /// Searches every root move but the best one at `depth`, returning whether all of them score
+    /// at least [`EASY_MOVE_MARGIN`] below the best move.
+    fn is_easy_move(&mut self, ctx: &mut SearchContext<'_>, score: i32, depth: u8) -> bool {
+        let bound = Wrapping(score - EASY_MOVE_MARGIN);
+        let pv = (self.pv_table[0], self.pv_length[0]);
+
+        self.follow_pv = false;
+        self.stack[0].excluded_move = self.best_move();
+        let alternative = self.negamax(ctx, bound - Wrapping(1), bound, depth);
+        self.stack[0].excluded_move = Move::default();
+
+        // the search of the alternatives overwrites the principal variation when one of them is
+        // good enough
+        (self.pv_table[0], self.pv_length[0]) = pv;
+
+        !self.stopped && alternative < bound.0
+    }
+
+    /// Root move ordering:
+    ///
+    /// The moves that needed the most nodes to be refuted in the last iteration are the ones
+    /// closest to being the best move, which the history of a single search knows little about at
+    /// the root, so past the best move the root moves are ordered by their subtree sizes.
+    fn order_root_moves(&self, moves: &mut [Move]) {
+        if self.root_move_nodes.is_empty() || moves.len() < 2 {
+            return;
+        }
+
+        let root_move_nodes = &self.root_move_nodes;
+        moves[1..].sort_by_key(|piece_move| {
+            let nodes = root_move_nodes
+                .iter()
+                .find(|(root_move, _)| root_move == piece_move)
+                .map_or(0, |(_, nodes)| *nodes);
+            std::cmp::Reverse(nodes)
+        });
+    }
+
+    fn record_root_move_nodes(&mut self, piece_move: Move, nodes: u64) {
+        match self
+            .root_move_nodes
+            .iter_mut()
+            .find(|(root_move, _)| *root_move == piece_move)
+        {
+            Some((_, root_nodes)) => *root_nodes = nodes,
+            None => self.root_move_nodes.push((piece_move, nodes)),
+        }
+    }
+
+    /// Whether the limits of the search were reached. The first iteration is always searched to
+    /// the end, so even degenerate limits like `go movetime 0` or `go depth 0` have a move to play.
+    fn should_stop(&self, ctx: &mut SearchContext<'_>, depth: u8) -> bool {
+        self.completed_depth > 0
+            && ctx.time_manager.should_stop(TimeManagerContext {
+                depth,
+                nodes: self.nodes,
+            })
+    }
+
+    /// Checks the limits of the search every [`ABORT_CHECK_INTERVAL`] nodes, as querying the
+    /// clock on every node would be too slow, and returns whether the search must unwind. A node
+    /// budget is checked as soon as it runs out, so `go nodes` stops right at the limit.
+    fn should_abort(&mut self, ctx: &mut SearchContext<'_>, depth: u8) -> bool {
+        if !self.stopped
+            && (self.nodes % ABORT_CHECK_INTERVAL == 0 || self.nodes >= self.node_limit)
+        {
+            self.stopped = self.should_stop(ctx, depth);
+            // a ponder hit starts the budget of the move from the nodes searched so far
+            self.node_limit = ctx.time_manager.node_limit().unwrap_or(u64::MAX);
+            self.report_progress(ctx);
+        }
+
+        self.stopped
+    }
+
+    fn report_progress(&self, ctx: &mut SearchContext<'_>) {
+        let Some(observer) = ctx.observer.as_deref_mut() else {
+            return;
+        };
+
+        if ctx.time_manager.progress_due() {
+            observer.on_progress(&ProgressInfo {
+                nodes: self.nodes,
+                hashfull: ctx.transposition_table.hashfull(),
+                elapsed: ctx.time_manager.elapsed(),
+            });
+        }
+    }
+
+    /// Plays the principal variation out on the board, truncating it at the first move that is
+    /// not legal on the position it is played at, which can happen when the PV is grafted from
+    /// transposition table entries or an aborted iteration.
+    ///
+    /// Returns the ply of the first illegal move, if any.
+    fn verify_pv(&mut self, board: &mut BoardState, zobrist: &mut Zobrist) -> Option<usize> {
+        let mut played = 0;
+        let mut illegal_ply = None;
+
+        for ply in 0..self.pv_length[0] {
+            let piece_move = self.pv_table[0][ply];
+
+            let is_legal = board.is_pseudo_legal(piece_move)
+                && make_move(
+                    &mut MoveContext {
+                        zobrist,
+                        board,
+                        search: self,
+                    },
+                    piece_move,
+                    MoveKind::AllMoves,
+                );
+
+            if !is_legal {
+                illegal_ply = Some(ply);
+                self.pv_length[0] = ply;
+                break;
+            }
+
+            played += 1;
+        }
+
+        for _ in 0..played {
+            zobrist.position = board.undo_move();
+        }
+
+        illegal_ply
+    }
+
+    /// Extends a principal variation shorter than `max_length` with the best moves the
+    /// transposition table holds for the positions after it, for as long as they are legal and
+    /// don't walk back into a position of the line.
+    ///
+    /// The search cuts the PV short where it stops following it, like on a draw or a cutoff,
+    /// which can leave a single move and nothing to ponder on.
+    fn extend_pv(
+        &mut self,
+        board: &mut BoardState,
+        zobrist: &mut Zobrist,
+        transposition_table: &TranspositionTable,
+        max_length: usize,
+    ) {
+        let max_length = max_length.min(MAX_PLY);
+        let mut line = vec![zobrist.position];
+
+        for ply in 0..self.pv_length[0] {
+            let piece_move = self.pv_table[0][ply];
+            make_move(
+                &mut MoveContext {
+                    zobrist,
+                    board,
+                    search: self,
+                },
+                piece_move,
+                MoveKind::AllMoves,
+            );
+            line.push(zobrist.position);
+        }
+
+        while self.pv_length[0] < max_length {
+            let Some(entry) = transposition_table.probe(zobrist.position) else {
+                break;
+            };
+
+            let piece_move = entry.best_move;
+            let is_legal = piece_move != Move::default()
+                && board.is_pseudo_legal(piece_move)
+                && make_move(
+                    &mut MoveContext {
+                        zobrist,
+                        board,
+                        search: self,
+                    },
+                    piece_move,
+                    MoveKind::AllMoves,
+                );
+
+            if !is_legal {
+                break;
+            }
+
+            let repeated = line.contains(&zobrist.position);
+            line.push(zobrist.position);
+            if repeated {
+                break;
+            }
+
+            self.pv_table[0][self.pv_length[0]] = piece_move;
+            self.pv_length[0] += 1;
+        }
+
+        for _ in 1..line.len() {
+            zobrist.position = board.undo_move();
+        }
+    }
+
+    /// Whether a hundred plies went by without a capture or pawn move, unless the last one
+    /// checkmated, as a mate still ends the game on the hundredth ply.
+    fn is_fifty_move_draw(&mut self, ctx: &mut SearchContext<'_>) -> bool {
+        ctx.board.fifty_move_counter >= FIFTY_MOVE_PLIES
+            && (!ctx.board.in_check()
+                || has_legal_move(&mut MoveContext {
+                    board: ctx.board,
+                    zobrist: ctx.zobrist,
+                    search: self,
+                }))
     }
 
     fn negamax(
         &mut self,
         ctx: &mut SearchContext<'_>,
         mut alpha: Wrapping<i32>,
-        beta: Wrapping<i32>,
+        mut beta: Wrapping<i32>,
         mut depth: u8,
     ) -> i32 {
         const FULL_DEPTH_MOVES: i32 = 4;
         const REDUCTION_LIMIT: u8 = 3;
-        const RAZORING_LIMIT: u8 = 5;
-        const RAZORING_MARGIN: i32 = 1000;
+        const FUTILITY_LIMIT: u8 = 3;
+        const FUTILITY_MARGIN: i32 = 150;
 
         self.pv_length[ctx.board.ply] = ctx.board.ply;
 
-        if (ctx.board.ply != 0 && is_repetition(ctx)) || ctx.board.fifty_move_counter >= 100 {
-            return 0;
+        // Extensions can keep the depth above zero on the deepest ply the tables hold, the line
+        // ends there as its children would have no room for their PV or stack entry
+        if ctx.board.ply >= MAX_PLY - 1 {
+            return evaluate_position(&mut EvalContext {
+                board: ctx.board,
+                search: self,
+            });
+        }
+
+        // the root is searched even in a drawn game, it still needs a move
+        if ctx.board.ply != 0
+            && (ctx.is_repetition()
+                || self.is_fifty_move_draw(ctx)
+                || ctx.board.is_insufficient_material())
+        {
+            return ctx.draw_score();
+        }
+
+        // Upcoming repetition:
+        //
+        // If the side to move can go back to a position already in the search path, it can at
+        // least force a draw, so a losing alpha can be raised to a draw score without searching.
+        if ctx.board.ply != 0
+            && alpha.0 < ctx.draw_score()
+            && has_upcoming_repetition(ctx.board, ctx.search_path.keys(), ctx.zobrist.position)
+        {
+            alpha = Wrapping(ctx.draw_score());
+            if alpha >= beta {
+                return alpha.0;
+            }
+        }
+
+        // Mate distance pruning:
+        //
+        // No line through this node scores better than mating on the next ply, or worse than
+        // being mated right here, so when the window lies outside those bounds the node can't
+        // change the result. It cuts off the lines longer than a mate already found, which is
+        // what keeps mate searches fast.
+        if ctx.board.ply != 0 {
+            let ply = ctx.board.ply as i32;
+            alpha = alpha.max(Wrapping(-MATE_UPPER_BOUND + ply));
+            beta = beta.min(Wrapping(MATE_UPPER_BOUND - ply - 1));
+            if alpha >= beta {
+                return alpha.0;
+            }
+        }
+
+        // Quiescence search probes the transposition table itself
+        if depth == 0 {
+            return self.quiescence(ctx, alpha, beta, depth, QUIESCENCE_CHECK_PLIES);
         }
 
         let pv_node = beta.0 - alpha.0 > 1;
         let mut tt_flag = TTFlag::Alpha;
         let mut best_move = Move::default();
 
-        let score = ctx.transposition_table.get(
-            ctx.zobrist.position,
-            alpha.0,
-            beta.0,
-            depth,
-            ctx.board.ply,
-            &mut best_move,
-        );
+        // the result of a search leaving a move out doesn't belong to the position, so it is
+        // neither taken from nor stored in the transposition table
+        let excluded_move = self.stack[ctx.board.ply].excluded_move;
+        let restricted_root = ctx.board.ply == 0 && !ctx.root_moves.is_empty();
+        let use_tt = ctx.features.contains(SearchFeatures::TRANSPOSITION_TABLE)
+            && excluded_move == Move::default()
+            && !restricted_root;
+        let tt_entry = use_tt
+            .then(|| ctx.transposition_table.probe(ctx.zobrist.position))
+            .flatten();
+        // the best move of a shallower search is still the best guess to try first
+        if let Some(entry) = tt_entry {
+            best_move = entry.best_move;
+        }
+        let score =
+            tt_entry.and_then(|entry| entry.usable_score(alpha.0, beta.0, depth, ctx.board.ply));
 
         if let (Some(score), true, true) = (score, ctx.board.ply != 0, !pv_node) {
+            self.tt_hits += 1;
             return score;
         }
 
-        if depth == 0 {
-            return self.quiescence(ctx, alpha, beta, depth);
-        }
-
-        if ctx.board.ply > MAX_PLY - 1 {
-            return evaluate_position(&mut EvalContext {
-                board: ctx.board,
-                search: self,
-            });
+        // A key collision can hand back the best move of a different position, which can't be
+        // trusted for move ordering
+        if best_move != Move::default() && !ctx.board.is_pseudo_legal(best_move) {
+            best_move = Move::default();
         }
 
         self.nodes += 1;
+        self.seldepth = self.seldepth.max(ctx.board.ply as u8);
 
-        let king_square = match ctx.board.side_to_move {
-            Side::White => ctx.board.pieces[Pieces::WhiteKing].trailing_zeros(),
-            Side::Black => ctx.board.pieces[Pieces::BlackKing].trailing_zeros(),
-            _ => unreachable!(),
-        };
+        if self.should_abort(ctx, depth) {
+            return 0;
+        }
 
-        let in_check = ctx
-            .board
-            .is_square_attacked(king_square, ctx.board.side_to_move.enemy());
+        let in_check = ctx.board.in_check();
 
         if in_check {
             // Extend the search depth if in check, this is useful to find forced mates or tactical
@@ -216,18 +784,60 @@ impl SearchState {
             depth += 1;
         }
 
-        let static_eval = evaluate_position(&mut EvalContext {
-            board: ctx.board,
-            search: self,
-        });
+        // Internal iterative reduction:
+        //
+        // A PV node without a hash move to try first has poor move ordering, and is likely a
+        // node the previous iterations didn't expect to matter. Searching it one ply shallower
+        // is cheap, and stores a best move in the transposition table for the next iteration to
+        // order with.
+        if ctx
+            .features
+            .contains(SearchFeatures::INTERNAL_ITERATIVE_REDUCTION)
+            && pv_node
+            && depth >= IIR_LIMIT
+            && best_move == Move::default()
+            && excluded_move == Move::default()
+        {
+            depth -= 1;
+        }
+
+        // the evaluation is stored along the position, and spared when it comes back
+        let static_eval = match tt_entry.and_then(|entry| entry.static_eval) {
+            Some(static_eval) => static_eval as i32,
+            None => evaluate_position(&mut EvalContext {
+                board: ctx.board,
+                search: self,
+            }),
+        };
+        // the evaluation of a position in check says little about it, so it isn't compared
+        // against by the nodes below
+        self.stack[ctx.board.ply].static_eval = match in_check {
+            true => -INFINITY,
+            false => static_eval,
+        };
 
-        if depth < REDUCTION_LIMIT
+        // Improving:
+        //
+        // Whether the static evaluation went up since the side to move last played, which makes
+        // the node more likely to beat its bounds. Pruning is more careful at improving nodes
+        // and more aggressive at the others. A position in check two plies back has no
+        // evaluation to compare with, and counts as improving.
+        let improving = !in_check
+            && ctx.board.ply >= 2
+            && static_eval > self.stack[ctx.board.ply - 2].static_eval;
+
+        // Reverse futility pruning (static null move):
+        //
+        // When the static evaluation is so far above beta that even losing the margin on every
+        // remaining ply keeps it there, the node is expected to fail high without searching it.
+        // Mate scores are left alone, as no margin makes up for them.
+        if ctx.features.contains(SearchFeatures::REVERSE_FUTILITY)
+            && depth <= REVERSE_FUTILITY_LIMIT
             && !pv_node
             && !in_check
-            // prevent razoring on mate-in scores
-            && i32::abs(beta.0 - 1) > -INFINITY + 100
+            && beta.0.abs() < MATE_LOWER_BOUND
         {
-            let eval_margin = 120 * depth as i32;
+            let eval_margin = REVERSE_FUTILITY_MARGIN * (depth - improving as u8) as i32;
             if static_eval - eval_margin >= beta.0 {
                 return static_eval - eval_margin;
             }
@@ -236,15 +846,31 @@ impl SearchState {
         // Razoring:
         //
         // If the current evaluation is so bad that we're unlikely to ever improve the position
-        // (raise alpha). We skip the search and do a simpler quiescence search, the reason for a
-        // relatively low depth for the cutoff is due to the fact that in shallower depths there
-        // are less chances of deep tactical moves happening, so if a score is bad, it probably
-        // means that the move is bad.
-        if depth < RAZORING_LIMIT
+        // (raise alpha), a quiescence search is asked to confirm it. The reason for a relatively
+        // low depth for the cutoff is due to the fact that in shallower depths there are less
+        // chances of deep tactical moves happening, so if the captures can't save the position
+        // either, the node is given up on without searching it.
+        if ctx.features.contains(SearchFeatures::RAZORING)
+            && depth <= RAZORING_LIMIT
             && !pv_node
-            && static_eval <= alpha.0 - RAZORING_MARGIN * depth as i32
+            && !in_check
+            && alpha.0.abs() < MATE_LOWER_BOUND
+            && static_eval + RAZORING_MARGIN * depth as i32 <= alpha.0
         {
-            return self.quiescence(ctx, alpha, beta, depth);
+            let score = self.quiescence(
+                ctx,
+                alpha,
+                alpha + Wrapping(1),
+                depth,
+                QUIESCENCE_CHECK_PLIES,
+            );
+            if self.stopped {
+                return 0;
+            }
+
+            if score <= alpha.0 {
+                return score;
+            }
         }
 
         // Null move pruning:
@@ -253,42 +879,85 @@ impl SearchState {
         // Based on this, we give the opponent side an extra move, and if the score is still a
         // fail-high (score > beta), we can be quite confident that the best move would also fail
         // high. So we can simply return beta to prevent searching any further
-        if depth >= REDUCTION_LIMIT && !in_check && ctx.board.ply != 0 {
+        //
+        // The assumption breaks in zugzwang, which mostly happens when the side to move is left
+        // with only pawns, and a mate score for beta would be trusted on the word of a reduced
+        // search, so null moves are skipped in both cases.
+        if ctx.features.contains(SearchFeatures::NULL_MOVE)
+            && depth >= REDUCTION_LIMIT
+            && !in_check
+            && ctx.board.ply != 0
+            && excluded_move == Move::default()
+            && self.stack[ctx.board.ply - 1].current_move != Move::default()
+            && !self.verifying_null_move
+            && beta.0.abs() < MATE_LOWER_BOUND
+            && ctx.board.has_non_pawn_material(ctx.board.side_to_move)
+        {
             ctx.board.snapshot_board(ctx.zobrist);
 
-            ctx.board.ply += 1;
-            ctx.board.record_repetition(ctx.zobrist);
-
-            if ctx.board.en_passant.is_available() {
-                ctx.zobrist.position ^= ctx.zobrist.en_passant[ctx.board.en_passant];
-            }
-
-            ctx.board.en_passant = Square::OffBoard;
-            ctx.board.side_to_move = ctx.board.side_to_move.enemy();
-            ctx.zobrist.position ^= ctx.zobrist.side_key;
+            self.stack[ctx.board.ply].current_move = Move::default();
+            ctx.enter_ply();
+            ctx.make_null_move();
 
             let score = -Wrapping(self.negamax(ctx, -beta, -beta + Wrapping(1), depth - 1 - 2));
-            ctx.board.ply -= 1;
-            ctx.board.repetition_index -= 1;
+            ctx.leave_ply();
             ctx.zobrist.position = ctx.board.undo_move();
 
-            if ctx.time_manager.should_stop(TimeManagerContext {
-                depth,
-                nodes: self.nodes,
-            }) {
+            if self.stopped {
                 return 0;
             }
 
-            if score >= beta {
+            // Deep cutoffs prune the largest subtrees, so they are verified by searching the
+            // node itself to the same reduced depth without null moves, which catches the
+            // zugzwangs left with pieces on the board
+            let verified = match depth >= NULL_MOVE_VERIFICATION_LIMIT && score >= beta {
+                true => {
+                    self.verifying_null_move = true;
+                    let verification = self.negamax(ctx, beta - Wrapping(1), beta, depth - 1 - 2);
+                    self.verifying_null_move = false;
+                    self.pv_length[ctx.board.ply] = ctx.board.ply;
+
+                    if self.stopped {
+                        return 0;
+                    }
+
+                    Wrapping(verification) >= beta
+                }
+                false => true,
+            };
+
+            if score >= beta && verified {
                 return beta.0;
             }
         }
 
-        generate_moves(&mut MoveContext {
-            zobrist: ctx.zobrist,
-            board: ctx.board,
-            search: self,
-        });
+        // Singular extension:
+        //
+        // When the hash move was found to be at least as good as its score, and searching every
+        // other move with a window a margin below that score shows none of them comes close, the
+        // hash move is the only move keeping the position together. Such forced lines are where
+        // the search is most likely to be wrong, so the hash move gets an extra ply.
+        let singular_move = match ctx.features.contains(SearchFeatures::SINGULAR_EXTENSION)
+            && depth >= SINGULAR_LIMIT
+            && ctx.board.ply != 0
+            && use_tt
+            && best_move != Move::default()
+        {
+            true => self.singular_move(ctx, best_move, depth),
+            false => None,
+        };
+        if self.stopped {
+            return 0;
+        }
+
+        generate_moves(
+            &mut MoveContext {
+                zobrist: ctx.zobrist,
+                board: ctx.board,
+                search: self,
+            },
+            MoveKind::AllMoves,
+        );
 
         // If move is within the PV path from the previous iteration, give it a small bonus to
         // improve its position in ordering.
@@ -300,19 +969,78 @@ impl SearchState {
         }
 
         // Order moves by MVV-LVA score to improve pruning efficiency
-        sort_moves(&mut SortContext {
+        let mut move_picker = MovePicker::new(&mut SortContext {
             zobrist: ctx.zobrist,
             board: ctx.board,
             search: self,
             best_move,
         });
+        if ctx.board.ply == 0 {
+            self.order_root_moves(move_picker.sort());
+        }
+
+        // Futility pruning:
+        //
+        // Close to the horizon, a quiet move is unlikely to make up for a static evaluation that
+        // is far below alpha, so only moves that change the material balance or give check are
+        // worth searching.
+        let futile = ctx.features.contains(SearchFeatures::FUTILITY_PRUNING)
+            && depth <= FUTILITY_LIMIT
+            && !pv_node
+            && !in_check
+            && alpha.0.abs() < MATE_LOWER_BOUND
+            && static_eval + FUTILITY_MARGIN * (depth + improving as u8) as i32 <= alpha.0;
+
+        // Late move pruning:
+        //
+        // With good move ordering, quiet moves late in the list of a node close to the horizon
+        // rarely raise alpha, so they are skipped past a move count that grows with the depth,
+        // and shrinks when the node isn't improving.
+        let late_move_limit = (ctx.features.contains(SearchFeatures::LATE_MOVE_PRUNING)
+            && depth <= LMP_LIMIT
+            && !pv_node
+            && !in_check
+            && alpha.0.abs() < MATE_LOWER_BOUND)
+            .then(|| (3 + depth as i32 * depth as i32) / (2 - improving as i32));
 
         let mut legal_moves = 0;
         let mut moves_searched = 0;
 
-        for piece_move in self.moves.into_iter().take(self.move_count) {
-            ctx.board.ply += 1;
-            ctx.board.record_repetition(ctx.zobrist);
+        // quiet moves and captures searched without causing a cutoff
+        let mut quiets_searched = [Move::default(); HISTORY_MALUS_MOVES];
+        let mut quiet_count = 0;
+        let mut captures_searched = [Move::default(); HISTORY_MALUS_MOVES];
+        let mut capture_count = 0;
+
+        for piece_move in move_picker {
+            if piece_move == excluded_move
+                || (restricted_root && !ctx.root_moves.contains(&piece_move))
+            {
+                continue;
+            }
+
+            // at least one move is searched, so a node with pruned moves is never mistaken for
+            // a mate or stalemate
+            let prunable = (futile && moves_searched > 0)
+                || late_move_limit.is_some_and(|limit| moves_searched >= limit);
+            if prunable
+                && !piece_move.is_capture()
+                && !piece_move.promotion().is_promoting()
+                && !ctx.board.gives_check(piece_move)
+            {
+                continue;
+            }
+
+            let jitter = match ctx.opening_jitter {
+                Some(jitter) if ctx.board.ply == 0 => {
+                    Wrapping(jitter.score(ctx.zobrist.position.inner(), *piece_move))
+                }
+                _ => Wrapping(0),
+            };
+
+            self.stack[ctx.board.ply].current_move = piece_move;
+            ctx.enter_ply();
+            let nodes_before = self.nodes;
 
             let valid_move = make_move(
                 &mut MoveContext {
@@ -325,64 +1053,105 @@ impl SearchState {
             );
 
             if !valid_move {
-                ctx.board.ply -= 1;
-                ctx.board.repetition_index -= 1;
+                ctx.leave_ply();
                 continue;
             }
 
             legal_moves += 1;
 
-            let score = if moves_searched == 0 {
-                -Wrapping(self.negamax(ctx, -beta, -alpha, depth - 1))
-            } else {
-                // To apply late move reduction, a move cannot be a capture or a promotion, the
-                // king must not be in check and the search must also be past the depth allowed to
-                // be reduced
-                let should_reduce = moves_searched >= FULL_DEPTH_MOVES
-                    && depth >= REDUCTION_LIMIT
-                    && !in_check
-                    && !piece_move.is_capture()
-                    && !piece_move.promotion().is_promoting();
-
-                // Apply late move reduction by reducing the depth by 2 per ply
-                let shallow = if should_reduce {
-                    -Wrapping(self.negamax(ctx, -alpha - Wrapping(1), -alpha, depth - 2))
-                } else {
-                    // This move should not yet reduce, but we are also on a non-pv path, so
-                    // instead of going down the search, we give it a fake score slightly above
-                    // alpha that ensures it will trigger the full search below.
-                    alpha + Wrapping(1)
-                };
+            if ctx.board.ply == 1 && excluded_move == Move::default() {
+                if let Some(observer) = ctx.observer.as_deref_mut() {
+                    observer.on_root_move(&RootMoveInfo {
+                        depth: self.iteration_depth,
+                        piece_move,
+                        move_number: legal_moves,
+                        nodes: self.nodes,
+                    });
+                }
+            }
+
+            let child_depth = match singular_move == Some(piece_move) {
+                true => depth,
+                false => depth - 1,
+            };
 
-                if shallow > alpha {
-                    // LMR found a better move, so we search at full depth but with a narrower
-                    // window to double check if it is a better move.
-                    let deeper =
-                        -Wrapping(self.negamax(ctx, -alpha - Wrapping(1), -alpha, depth - 1));
+            let score = {
+                // the bounds of the window the move is searched with, see the opening jitter below
+                let (alpha, beta) = (alpha - jitter, beta - jitter);
 
-                    // If the narrower window also proves to improve alpha, we do a final full
-                    // depth and full width window search.
-                    if deeper > alpha && deeper < beta {
-                        -Wrapping(self.negamax(ctx, -beta, -alpha, depth - 1))
+                if moves_searched == 0 {
+                    -Wrapping(self.negamax(ctx, -beta, -alpha, child_depth))
+                } else {
+                    // To apply late move reduction, a move cannot be a capture or a promotion,
+                    // the king must not be in check and the search must also be past the depth
+                    // allowed to be reduced
+                    let should_reduce = ctx.features.contains(SearchFeatures::LATE_MOVE_REDUCTION)
+                        && moves_searched >= FULL_DEPTH_MOVES
+                        && depth >= REDUCTION_LIMIT
+                        && !in_check
+                        && !piece_move.is_capture()
+                        && !piece_move.promotion().is_promoting();
+
+                    // Apply late move reduction by reducing the depth by the plies of the
+                    // reduction table, and one more when the node isn't improving, unless the
+                    // move did well in similar positions
+                    let shallow = if should_reduce {
+                        let history = self.quiet_history(ctx.board.ply - 1, piece_move);
+                        let reduction = late_move_reduction(depth, moves_searched)
+                            + (!improving && history <= LMR_HISTORY_THRESHOLD) as u8;
+                        let reduced_depth = child_depth.saturating_sub(reduction);
+                        -Wrapping(self.negamax(ctx, -alpha - Wrapping(1), -alpha, reduced_depth))
+                    } else {
+                        // This move should not yet reduce, but we are also on a non-pv path, so
+                        // instead of going down the search, we give it a fake score slightly above
+                        // alpha that ensures it will trigger the full search below.
+                        alpha + Wrapping(1)
+                    };
+
+                    if shallow > alpha {
+                        // LMR found a better move, so we search at full depth but with a narrower
+                        // window to double check if it is a better move.
+                        let deeper =
+                            -Wrapping(self.negamax(ctx, -alpha - Wrapping(1), -alpha, child_depth));
+
+                        // If the narrower window also proves to improve alpha, we do a final full
+                        // depth and full width window search.
+                        if deeper > alpha && deeper < beta {
+                            -Wrapping(self.negamax(ctx, -beta, -alpha, child_depth))
+                        } else {
+                            deeper
+                        }
                     } else {
-                        deeper
+                        shallow
                     }
-                } else {
-                    shallow
                 }
             };
 
-            ctx.board.ply -= 1;
-            ctx.board.repetition_index -= 1;
+            ctx.leave_ply();
             ctx.zobrist.position = ctx.board.undo_move();
 
-            if ctx.time_manager.should_stop(TimeManagerContext {
-                depth,
-                nodes: self.nodes,
-            }) {
+            if ctx.board.ply == 0 && excluded_move == Move::default() {
+                self.record_root_move_nodes(piece_move, self.nodes - nodes_before);
+            }
+
+            if self.stopped {
                 return 0;
             }
 
+            // Opening jitter:
+            //
+            // Nudging the scores of root moves makes the engine pick different moves among the
+            // ones it considers about equal, mate scores are left alone so they stay exact. A
+            // limited strength nudges them further, making it pick worse moves too.
+            //
+            // A move failing low only returns a bound of the window it was searched with, so the
+            // window is shifted by the nudge rather than nudging the bound, which would let every
+            // move failing low raise alpha a bit further.
+            let score = match score.0.abs() < MATE_LOWER_BOUND {
+                true => score + jitter,
+                false => score,
+            };
+
             moves_searched += 1;
 
             // Alpha raise
@@ -396,14 +1165,6 @@ impl SearchState {
 
                 best_move = piece_move;
 
-                // History heuristic
-                //
-                // Keep track of quiet moves that increases alpha by giving them a bonus based on
-                // its depth, this put those moves higher on the move sorting
-                if !piece_move.is_capture() {
-                    self.history_moves[piece_move.piece()][piece_move.target()] += depth as i32;
-                }
-
                 alpha = score;
 
                 // Principal variation bookkeeping, the current move is the new best move, so we
@@ -424,56 +1185,187 @@ impl SearchState {
                 //
                 // This is a fail-hard alpha/beta search
                 if score >= beta {
-                    ctx.transposition_table.set(
-                        best_move,
-                        ctx.zobrist.position,
-                        beta.0,
-                        TTFlag::Beta,
-                        depth,
-                        ctx.board.ply,
-                    );
+                    if use_tt {
+                        ctx.transposition_table.store(
+                            TTEntry {
+                                key: ctx.zobrist.position,
+                                score: beta.0,
+                                depth,
+                                flag: TTFlag::Beta,
+                                best_move,
+                                static_eval: i16::try_from(static_eval).ok(),
+                                ..Default::default()
+                            },
+                            ctx.board.ply,
+                        );
+                    }
+
+                    let bonus = history_bonus(depth);
 
                     if !piece_move.is_capture() {
                         // When a non-capture (killer move) causes a beta cutoff, we store keep track of
                         // them in order to give them a higher priority in searching when there's a
                         // similar position.
-                        self.killer_moves[1][ctx.board.ply] = self.killer_moves[0][ctx.board.ply];
-                        self.killer_moves[0][ctx.board.ply] = piece_move;
+                        let killers = &mut self.stack[ctx.board.ply].killers;
+                        killers[1] = killers[0];
+                        killers[0] = piece_move;
+
+                        // History heuristic
+                        //
+                        // The quiet move refuting the node gets a bonus growing with the depth,
+                        // both in the history and for following the moves that led to the node,
+                        // as the same reply often refutes them elsewhere in the tree. The quiet
+                        // moves searched before it failed to, so they get the same malus.
+                        self.update_quiet_history(ctx.board.ply, piece_move, bonus);
+                        for &quiet in &quiets_searched[..quiet_count] {
+                            self.update_quiet_history(ctx.board.ply, quiet, -bonus);
+                        }
+                    } else {
+                        // Capture history
+                        //
+                        // Captures refuting a node are remembered the same way, which lets the
+                        // ordering of captures learn beyond what MVV/LVA can tell.
+                        let victim = ctx.board.captured_kind(piece_move);
+                        self.update_capture_history(piece_move, victim, bonus);
+                    }
+
+                    // whatever refuted the node, the captures searched before it didn't
+                    for &capture in &captures_searched[..capture_count] {
+                        let victim = ctx.board.captured_kind(capture);
+                        self.update_capture_history(capture, victim, -bonus);
                     }
 
                     return beta.0;
                 }
             }
+
+            match piece_move.is_capture() {
+                true if capture_count < HISTORY_MALUS_MOVES => {
+                    captures_searched[capture_count] = piece_move;
+                    capture_count += 1;
+                }
+                false if quiet_count < HISTORY_MALUS_MOVES => {
+                    quiets_searched[quiet_count] = piece_move;
+                    quiet_count += 1;
+                }
+                _ => {}
+            }
         }
 
         if legal_moves == 0 {
+            // only the excluded move was legal, there is no alternative to it
+            if excluded_move != Move::default() {
+                return alpha.0;
+            }
+
             if in_check {
                 return -MATE_UPPER_BOUND + ctx.board.ply as i32;
             } else {
-                return 0;
+                return ctx.draw_score();
             }
         }
 
-        ctx.transposition_table.set(
-            best_move,
-            ctx.zobrist.position,
-            alpha.0,
-            tt_flag,
-            depth,
-            ctx.board.ply,
-        );
+        if use_tt {
+            ctx.transposition_table.store(
+                TTEntry {
+                    key: ctx.zobrist.position,
+                    score: alpha.0,
+                    depth,
+                    flag: tt_flag,
+                    best_move,
+                    static_eval: i16::try_from(static_eval).ok(),
+                    ..Default::default()
+                },
+                ctx.board.ply,
+            );
+        }
 
         alpha.0
     }
 
+    /// Returns the hash move when every other move scores well below its score, searching the
+    /// alternatives at half the remaining depth with the hash move excluded.
+    ///
+    /// Only hash moves backed by a lower bound or exact score from a search not much shallower
+    /// than `depth` are checked, as a shallow or upper bound score says little about the move.
+    fn singular_move(
+        &mut self,
+        ctx: &mut SearchContext<'_>,
+        hash_move: Move,
+        depth: u8,
+    ) -> Option<Move> {
+        let entry = ctx.transposition_table.probe(ctx.zobrist.position)?;
+
+        if entry.best_move != hash_move
+            || entry.flag == TTFlag::Alpha
+            || entry.depth + SINGULAR_DEPTH_SLACK < depth
+            || entry.score.abs() >= MATE_LOWER_BOUND
+        {
+            return None;
+        }
+
+        let ply = ctx.board.ply;
+        let singular_beta = Wrapping(entry.score - SINGULAR_MARGIN * depth as i32);
+
+        self.stack[ply].excluded_move = hash_move;
+        let score = self.negamax(ctx, singular_beta - Wrapping(1), singular_beta, depth / 2);
+        self.stack[ply].excluded_move = Move::default();
+
+        // the search of the alternatives shares the ply with the node, whose principal variation
+        // starts out empty
+        self.pv_length[ply] = ply;
+
+        (score < singular_beta.0).then_some(hash_move)
+    }
+
+    /// Searches captures until the position is quiet, so positions aren't evaluated in the middle
+    /// of an exchange. The first `check_plies` plies also search quiet checks, and every move is
+    /// searched when the side to move is in check, as standing pat isn't an option then.
     fn quiescence(
         &mut self,
         ctx: &mut SearchContext<'_>,
         mut alpha: Wrapping<i32>,
         beta: Wrapping<i32>,
         depth: u8,
+        check_plies: u8,
     ) -> i32 {
         self.nodes += 1;
+        self.seldepth = self.seldepth.max(ctx.board.ply as u8);
+
+        if self.should_abort(ctx, depth) {
+            return 0;
+        }
+
+        // a capture can leave too little material to mate
+        if ctx.board.is_insufficient_material() {
+            return ctx.draw_score();
+        }
+
+        // Quiescence results are stored with a depth of zero, so they are only ever used by
+        // other quiescence searches
+        let mut best_move = Move::default();
+        let use_tt = ctx.features.contains(SearchFeatures::TRANSPOSITION_TABLE);
+        let score = use_tt
+            .then(|| {
+                ctx.transposition_table.get(
+                    ctx.zobrist.position,
+                    alpha.0,
+                    beta.0,
+                    0,
+                    ctx.board.ply,
+                    &mut best_move,
+                )
+            })
+            .flatten();
+
+        if let Some(score) = score {
+            self.tt_hits += 1;
+            return score;
+        }
+
+        if best_move != Move::default() && !ctx.board.is_pseudo_legal(best_move) {
+            best_move = Move::default();
+        }
 
         let evaluation = evaluate_position(&mut EvalContext {
             board: ctx.board,
@@ -484,30 +1376,54 @@ impl SearchState {
             return evaluation;
         }
 
-        if evaluation >= beta.0 {
-            return beta.0;
-        }
+        let in_check = ctx.board.in_check();
+        let original_alpha = alpha;
 
-        if evaluation > alpha.0 {
-            alpha = Wrapping(evaluation);
+        if !in_check {
+            if evaluation >= beta.0 {
+                return beta.0;
+            }
+
+            if evaluation > alpha.0 {
+                alpha = Wrapping(evaluation);
+            }
         }
 
-        generate_moves(&mut MoveContext {
+        let mut move_ctx = MoveContext {
             zobrist: ctx.zobrist,
             board: ctx.board,
             search: self,
-        });
+        };
+        let move_kind = if in_check {
+            generate_moves(&mut move_ctx, MoveKind::AllMoves);
+            MoveKind::AllMoves
+        } else if check_plies > 0 {
+            generate_captures_and_checks(&mut move_ctx);
+            MoveKind::AllMoves
+        } else {
+            generate_moves(&mut move_ctx, MoveKind::Captures);
+            MoveKind::Captures
+        };
 
-        sort_moves(&mut SortContext {
+        let move_picker = MovePicker::new(&mut SortContext {
             zobrist: ctx.zobrist,
             board: ctx.board,
             search: self,
-            best_move: Move::default(),
+            best_move,
         });
 
-        for piece_move in self.moves.into_iter().take(self.move_count) {
-            ctx.board.ply += 1;
-            ctx.board.record_repetition(ctx.zobrist);
+        // captures losing material can't raise alpha over the stand pat score, unless the
+        // stand pat itself is bogus because the king is attacked
+        let prune_losing_captures = ctx.features.contains(SearchFeatures::SEE_PRUNING) && !in_check;
+        let mut legal_moves = 0;
+
+        for piece_move in move_picker {
+            if prune_losing_captures && ctx.board.static_exchange(piece_move) < 0 {
+                continue;
+            }
+
+            self.stack[ctx.board.ply].current_move = piece_move;
+            ctx.enter_ply();
 
             let legal_move = make_move(
                 &mut MoveContext {
@@ -516,37 +1432,438 @@ impl SearchState {
                     zobrist: ctx.zobrist,
                 },
                 piece_move,
-                MoveKind::Captures,
+                move_kind,
             );
 
             if !legal_move {
-                ctx.board.ply -= 1;
-                ctx.board.repetition_index -= 1;
+                ctx.leave_ply();
                 continue;
             }
 
-            let score = -Wrapping(self.quiescence(ctx, -beta, -alpha, depth));
+            legal_moves += 1;
+            let score = -Wrapping(self.quiescence(
+                ctx,
+                -beta,
+                -alpha,
+                depth,
+                check_plies.saturating_sub(1),
+            ));
 
-            ctx.board.ply -= 1;
-            ctx.board.repetition_index -= 1;
+            ctx.leave_ply();
             ctx.zobrist.position = ctx.board.undo_move();
 
-            if ctx.time_manager.should_stop(TimeManagerContext {
-                depth,
-                nodes: self.nodes,
-            }) {
+            if self.stopped {
                 return 0;
             }
 
             if score > alpha {
                 alpha = score;
+                best_move = piece_move;
 
                 if score >= beta {
+                    if use_tt {
+                        ctx.transposition_table.set(
+                            best_move,
+                            ctx.zobrist.position,
+                            beta.0,
+                            TTFlag::Beta,
+                            0,
+                            ctx.board.ply,
+                        );
+                    }
+
                     return beta.0;
                 }
             }
         }
 
+        // every move was searched, so being in check without legal moves is a checkmate
+        if in_check && legal_moves == 0 {
+            return -MATE_UPPER_BOUND + ctx.board.ply as i32;
+        }
+
+        if use_tt {
+            let flag = if alpha > original_alpha { TTFlag::Exact } else { TTFlag::Alpha };
+
+            ctx.transposition_table.set(
+                best_move,
+                ctx.zobrist.position,
+                alpha.0,
+                flag,
+                0,
+                ctx.board.ply,
+            );
+        }
+
         alpha.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use milky_fen::parse_fen_string;
+
+    use super::*;
+    use crate::Milky;
+    use crate::time_manager::{SearchLimits, TimeControl};
+
+    fn setup(fen: &str) -> (BoardState, Zobrist) {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        milky.load_position(parse_fen_string(fen).unwrap());
+
+        let board = std::mem::take(milky.board_state_mut());
+        let zobrist = std::mem::take(milky.zobrist_mut());
+        (board, zobrist)
+    }
+
+    fn find_move(
+        state: &mut SearchState,
+        board: &mut BoardState,
+        zobrist: &mut Zobrist,
+        uci: &str,
+    ) -> Move {
+        generate_moves(
+            &mut MoveContext {
+                zobrist,
+                board,
+                search: state,
+            },
+            MoveKind::AllMoves,
+        );
+
+        *state.moves().find(|m| m.to_string() == uci).unwrap()
+    }
+
+    #[test]
+    fn test_verify_pv_truncates_illegal_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let (mut board, mut zobrist) = setup(fen);
+        let mut state = SearchState::new();
+        let position = zobrist.position;
+
+        let e2e4 = find_move(&mut state, &mut board, &mut zobrist, "e2e4");
+        let g1f3 = find_move(&mut state, &mut board, &mut zobrist, "g1f3");
+
+        // white moving twice in a row can't be a valid line
+        state.pv_table[0][..3].copy_from_slice(&[e2e4, g1f3, g1f3]);
+        state.pv_length[0] = 3;
+
+        assert_eq!(state.verify_pv(&mut board, &mut zobrist), Some(1));
+        assert_eq!(state.pv_length[0], 1);
+
+        // the board is back to the root position
+        assert_eq!(zobrist.position, position);
+        assert!(board.snapshots.is_empty());
+
+        assert_eq!(state.verify_pv(&mut board, &mut zobrist), None);
+        assert_eq!(state.pv_length[0], 1);
+    }
+
+    #[test]
+    fn test_extend_pv_follows_the_transposition_table() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let (mut board, mut zobrist) = setup(fen);
+        let mut state = SearchState::new();
+        let tt = TranspositionTable::new(1);
+        let position = zobrist.position;
+
+        let e2e4 = find_move(&mut state, &mut board, &mut zobrist, "e2e4");
+        make_move(
+            &mut MoveContext {
+                zobrist: &mut zobrist,
+                board: &mut board,
+                search: &mut state,
+            },
+            e2e4,
+            MoveKind::AllMoves,
+        );
+        let e7e5 = find_move(&mut state, &mut board, &mut zobrist, "e7e5");
+        tt.set(e7e5, zobrist.position, 0, TTFlag::Exact, 1, 0);
+        zobrist.position = board.undo_move();
+
+        state.pv_table[0][0] = e2e4;
+        state.pv_length[0] = 1;
+
+        state.extend_pv(&mut board, &mut zobrist, &tt, 8);
+        assert_eq!(state.principal_variation(), &[e2e4, e7e5]);
+
+        // the board is back to the root position
+        assert_eq!(zobrist.position, position);
+        assert!(board.snapshots.is_empty());
+
+        // never past the requested length
+        state.pv_length[0] = 1;
+        state.extend_pv(&mut board, &mut zobrist, &tt, 1);
+        assert_eq!(state.principal_variation(), &[e2e4]);
+    }
+
+    #[test]
+    fn test_search_stability() {
+        let mut stability = SearchStability::default();
+        stability.record_score(20, false);
+        stability.record_score(35, true);
+        assert!(!stability.is_unstable());
+        assert_eq!(stability.aspiration_window(), 50);
+
+        // a fail followed by a full window search, then another fail
+        stability.record_fail();
+        stability.record_score(-100, false);
+        assert!(!stability.is_unstable());
+        stability.record_fail();
+        assert!(stability.is_unstable());
+        assert!(stability.aspiration_window() > 50);
+
+        // staying inside a narrow window clears the fails, but the score keeps swinging
+        stability.record_score(120, true);
+        stability.record_score(-40, true);
+        assert_eq!(stability.aspiration_fails, 0);
+        assert!(stability.is_unstable());
+
+        stability.record_score(-30, true);
+        assert!(!stability.is_unstable());
+    }
+
+    #[test]
+    fn test_quiescence_sees_mate_after_a_check() {
+        crate::init_static_members();
+        let mut milky = Milky::new();
+        // back rank mate, only visible at depth 1 when quiescence searches the evasions
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1";
+        milky.load_position(parse_fen_string(fen).unwrap());
+        milky.think(crate::time_manager::TimeControl::FixedDepth(1));
+
+        assert_eq!(milky.search_state().best_move().to_string(), "d1d8");
+        assert!(milky.search_state().score > MATE_LOWER_BOUND);
+    }
+
+    #[test]
+    fn test_mate_search() {
+        crate::init_static_members();
+        // Nf6+ gxf6 Bxf7#
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let search = |moves| {
+            let mut milky = Milky::new();
+            milky.load_position(parse_fen_string(fen).unwrap());
+            milky.think(TimeControl::MateIn(moves));
+            let state = milky.search_state();
+            (
+                state.best_move().to_string(),
+                Score::from_search_score(state.score),
+            )
+        };
+
+        assert_eq!(search(2), ("d5f6".to_string(), Score::Mate(2)));
+        assert_eq!(search(3), ("d5f6".to_string(), Score::Mate(2)));
+
+        // there is no mate in one, so the move of the first iteration is kept
+        let (best_move, score) = search(1);
+        assert_ne!(best_move, Move::default().to_string());
+        assert_ne!(score, Score::Mate(1));
+    }
+
+    #[test]
+    fn test_late_move_reductions_grow_with_depth_and_move_number() {
+        crate::init_static_members();
+
+        assert_eq!(late_move_reduction(3, 4), 1);
+        for depth in 3..63 {
+            for moves_searched in 4..63 {
+                let reduction = late_move_reduction(depth, moves_searched);
+                assert!(reduction <= late_move_reduction(depth + 1, moves_searched));
+                assert!(reduction <= late_move_reduction(depth, moves_searched + 1));
+            }
+        }
+        assert!(late_move_reduction(20, 40) > 3);
+    }
+
+    #[test]
+    fn test_futility_pruning_shrinks_the_tree() {
+        crate::init_static_members();
+        // white is a rook down, most quiet moves near the horizon can't get the material back
+        let fen = "r1b1kb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/2BQKB1R w Kkq - 0 1";
+
+        let search = |features: SearchFeatures| {
+            let mut milky = Milky::new();
+            milky.options_mut().search_features = features;
+            milky.load_position(parse_fen_string(fen).unwrap());
+            milky.think(crate::time_manager::TimeControl::FixedDepth(6));
+            milky.search_state().nodes
+        };
+
+        // late move pruning skips most of the same moves, it would hide what futility pruning does
+        let features = SearchFeatures::all() - SearchFeatures::LATE_MOVE_PRUNING;
+        let pruned = search(features);
+        let full = search(features - SearchFeatures::FUTILITY_PRUNING);
+        assert!(pruned < full, "{pruned} nodes with pruning, {full} without");
+    }
+
+    static NO_HISTORY: GameHistory = GameHistory::new();
+
+    fn fixed_depth<'ctx>(
+        transposition_table: &'ctx TranspositionTable,
+        board: &'ctx mut BoardState,
+        zobrist: &'ctx mut Zobrist,
+    ) -> SearchContext<'ctx> {
+        SearchContext {
+            transposition_table,
+            board,
+            zobrist,
+            game_history: &NO_HISTORY,
+            root_moves: &[],
+            search_path: SearchPath::default(),
+            time_manager: TimeManager::new(SearchLimits::new(TimeControl::FixedDepth(6))),
+            features: SearchFeatures::all(),
+            observer: None,
+            debug: false,
+            opening_jitter: None,
+            contempt: 0,
+            start_depth: 1,
+        }
+    }
+
+    #[test]
+    fn test_double_null_move_is_not_a_repetition() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 10 30";
+        let (mut board, mut zobrist) = setup(fen);
+        let transposition_table = TranspositionTable::default();
+        let mut ctx = fixed_depth(&transposition_table, &mut board, &mut zobrist);
+        let root = ctx.zobrist.position;
+
+        for _ in 0..2 {
+            ctx.board.snapshot_board(ctx.zobrist);
+            ctx.enter_ply();
+            ctx.make_null_move();
+        }
+
+        assert_eq!(ctx.zobrist.position, root);
+        assert!(!ctx.is_repetition());
+        assert!(!has_upcoming_repetition(
+            ctx.board,
+            ctx.search_path.keys(),
+            ctx.zobrist.position
+        ));
+
+        for _ in 0..2 {
+            ctx.leave_ply();
+            ctx.zobrist.position = ctx.board.undo_move();
+        }
+        assert_eq!(ctx.zobrist.position, root);
+        assert_eq!(ctx.board.fifty_move_counter, 10);
+    }
+
+    #[test]
+    fn test_extensions_stop_at_the_deepest_ply() {
+        // the check extension keeps the depth of the replies above zero
+        let fen = "k7/pp6/8/8/8/8/1q6/K7 w - - 0 1";
+        let (mut board, mut zobrist) = setup(fen);
+        let transposition_table = TranspositionTable::default();
+        let mut ctx = fixed_depth(&transposition_table, &mut board, &mut zobrist);
+        let mut state = SearchState::new();
+
+        ctx.board.ply = MAX_PLY - 2;
+        state.negamax(&mut ctx, Wrapping(-INFINITY), Wrapping(INFINITY), 2);
+        assert_eq!(ctx.board.ply, MAX_PLY - 2);
+        assert!(state.pv_length[MAX_PLY - 2] <= MAX_PLY);
+    }
+
+    #[test]
+    fn test_easy_move() {
+        let is_easy_move = |fen: &str| {
+            let (mut board, mut zobrist) = setup(fen);
+            let transposition_table = TranspositionTable::default();
+            let mut state = SearchState::new();
+
+            state.search_position(fixed_depth(&transposition_table, &mut board, &mut zobrist));
+            let best_move = state.best_move();
+            let is_easy_move = state.is_easy_move(
+                &mut fixed_depth(&transposition_table, &mut board, &mut zobrist),
+                state.score,
+                3,
+            );
+
+            // the principal variation of the search is left alone
+            assert_eq!(state.best_move(), best_move);
+            is_easy_move
+        };
+
+        // taking the queen is the only move that doesn't leave white a queen down
+        assert!(is_easy_move("4k3/8/8/8/3q4/4P3/8/4K3 w - - 0 1"));
+        assert!(!is_easy_move(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        ));
+    }
+
+    #[test]
+    fn test_singular_move() {
+        let singular_move = |fen: &str| {
+            let (mut board, mut zobrist) = setup(fen);
+            let transposition_table = TranspositionTable::default();
+            let mut state = SearchState::new();
+
+            state.search_position(fixed_depth(&transposition_table, &mut board, &mut zobrist));
+            let best_move = state.best_move();
+            let singular_move = state.singular_move(
+                &mut fixed_depth(&transposition_table, &mut board, &mut zobrist),
+                best_move,
+                SINGULAR_LIMIT,
+            );
+
+            assert_eq!(state.stack[0].excluded_move, Move::default());
+            singular_move.map(|piece_move| piece_move.to_string())
+        };
+
+        // every move but taking the queen leaves white a queen down
+        assert_eq!(
+            singular_move("4k3/8/8/8/3q4/4P3/8/4K3 w - - 0 1").as_deref(),
+            Some("e3d4")
+        );
+        assert_eq!(
+            singular_move("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_root_moves_are_ordered_by_subtree_size() {
+        let (mut board, mut zobrist) = setup("4k3/8/8/8/3q4/4P3/8/4K3 w - - 0 1");
+        let transposition_table = TranspositionTable::default();
+        let mut state = SearchState::new();
+
+        state.search_position(fixed_depth(&transposition_table, &mut board, &mut zobrist));
+        // every legal root move is counted: three king moves and two pawn moves
+        assert_eq!(state.root_move_nodes.len(), 5);
+        let counted = state
+            .root_move_nodes
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum::<u64>();
+        assert!(counted > 0 && counted <= state.nodes);
+
+        generate_moves(
+            &mut MoveContext {
+                board: &mut board,
+                zobrist: &mut zobrist,
+                search: &mut state,
+            },
+            MoveKind::AllMoves,
+        );
+        let mut moves = state.moves().copied().collect::<Vec<_>>();
+        let first = moves[0];
+        state.order_root_moves(&mut moves);
+
+        // the first move is left in place, as it is the hash or PV move after sorting
+        assert_eq!(moves[0], first);
+        let nodes = moves[1..]
+            .iter()
+            .map(|piece_move| {
+                state
+                    .root_move_nodes
+                    .iter()
+                    .find(|(root_move, _)| root_move == piece_move)
+                    .map_or(0, |(_, nodes)| *nodes)
+            })
+            .collect::<Vec<_>>();
+        assert!(nodes.is_sorted_by(|a, b| a >= b), "{nodes:?}");
+    }
+}
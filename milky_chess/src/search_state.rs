@@ -0,0 +1,391 @@
+//! Per-thread state shared by move generation, move ordering and the search: the generated move
+//! list and the tables the search fills while it runs.
+
+use milky_bitboard::Move;
+#[cfg(feature = "search")]
+use milky_bitboard::PieceKind;
+
+use crate::MAX_PLY;
+use crate::pawn_hash::PawnHashTable;
+
+#[cfg(feature = "search")]
+pub static INFINITY: i32 = 50000;
+#[cfg(feature = "search")]
+pub static MATE_UPPER_BOUND: i32 = 49000;
+#[cfg(feature = "search")]
+pub static MATE_LOWER_BOUND: i32 = 48000;
+
+/// Bound of every history score. The history of a quiet move adds up three tables, which keeps it
+/// below the scores of the killer moves.
+#[cfg(feature = "search")]
+pub(crate) const MAX_HISTORY: i32 = 2048;
+
+pub type HistoryMoves = [[i32; 64]; 12];
+/// Scores of captures, indexed by the capturing piece, the target square and the kind of the
+/// captured piece.
+pub type CaptureHistory = [[[i32; 6]; 64]; 12];
+
+/// Scores of quiet moves played right after another move, indexed by the piece and target of the
+/// earlier move, then by the piece and target of the move itself.
+///
+/// It holds a history table for every piece and square, which is too large for the stack.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "search"), allow(dead_code))]
+pub struct ContinuationHistory(Vec<HistoryMoves>);
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        Self(vec![[[0; 64]; 12]; 12 * 64])
+    }
+}
+
+#[cfg(feature = "search")]
+impl ContinuationHistory {
+    fn table(&self, previous: Move) -> &HistoryMoves {
+        &self.0[previous.piece() as usize * 64 + previous.target() as usize]
+    }
+
+    pub fn get(&self, previous: Move, piece_move: Move) -> i32 {
+        self.table(previous)[piece_move.piece()][piece_move.target()]
+    }
+
+    pub fn add(&mut self, previous: Move, piece_move: Move, bonus: i32) {
+        let table = &mut self.0[previous.piece() as usize * 64 + previous.target() as usize];
+        apply_gravity(&mut table[piece_move.piece()][piece_move.target()], bonus);
+    }
+
+    pub fn age(&mut self) {
+        self.0.iter_mut().for_each(age_table);
+    }
+}
+
+/// What the search knows about a ply of the path to the current node.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StackEntry {
+    /// Static evaluation of the node, `-INFINITY` when it is in check.
+    pub static_eval: i32,
+    /// Move played from the node, left empty for null moves.
+    pub current_move: Move,
+    /// Move left out of the search of the node, used to search the alternatives to a move.
+    pub excluded_move: Move,
+    /// Quiet moves that caused the latest beta cutoffs at this ply, newest first.
+    pub killers: [Move; 2],
+}
+
+/// Per ply state of the search, indexed by the ply of the node.
+#[derive(Debug, Clone)]
+pub struct SearchStack([StackEntry; MAX_PLY]);
+
+impl Default for SearchStack {
+    fn default() -> Self {
+        Self([StackEntry::default(); MAX_PLY])
+    }
+}
+
+impl std::ops::Index<usize> for SearchStack {
+    type Output = StackEntry;
+
+    fn index(&self, ply: usize) -> &StackEntry {
+        &self.0[ply]
+    }
+}
+
+impl std::ops::IndexMut<usize> for SearchStack {
+    fn index_mut(&mut self, ply: usize) -> &mut StackEntry {
+        &mut self.0[ply]
+    }
+}
+
+pub struct SearchState {
+    pub nodes: u64,
+    /// Deepest ply reached by the search, quiescence search included.
+    pub seldepth: u8,
+    pub tt_hits: u64,
+    pub score_pv: bool,
+    pub follow_pv: bool,
+    /// What the search knows about every ply of the path to the current node.
+    pub stack: SearchStack,
+    pub history_moves: HistoryMoves,
+    /// How well captures did at causing beta cutoffs during the current search.
+    pub capture_history: CaptureHistory,
+    /// How well quiet moves did after the move played one and two plies earlier.
+    pub continuation_history: [ContinuationHistory; 2],
+    pub pv_table: [[Move; MAX_PLY]; MAX_PLY],
+    pub pv_length: [usize; MAX_PLY],
+    /// Set while a null move cutoff is verified, null moves are left out of the verification.
+    pub verifying_null_move: bool,
+    /// Nodes spent under each root move by the last iteration that searched it, ordering the root
+    /// moves of the next iteration.
+    pub root_move_nodes: Vec<(Move, u64)>,
+
+    pub moves: [Move; 256],
+    pub move_count: usize,
+    /// Whether move generation leaves out rook and bishop promotions, only set while searching.
+    pub skip_underpromotions: bool,
+    /// Set when the limits of the search are hit in the middle of an iteration, every node
+    /// returns right away once it is set so the search unwinds.
+    pub stopped: bool,
+    /// Nodes the search may visit, checked on every node as a node budget is exact, unlike the
+    /// clock which is only read every few thousand nodes.
+    pub node_limit: u64,
+    /// Deepest iteration completed by the last search, and its score.
+    pub completed_depth: u8,
+    pub score: i32,
+    /// Depth of the iteration being searched, the root may search deeper once extended.
+    pub iteration_depth: u8,
+    /// Pawn structures evaluated by this thread, see [`crate::pawn_hash`].
+    pub(crate) pawn_hash: PawnHashTable,
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self {
+            nodes: 0,
+            seldepth: 0,
+            tt_hits: 0,
+            move_count: 0,
+            score_pv: false,
+            follow_pv: false,
+
+            moves: [Move::default(); 256],
+            skip_underpromotions: false,
+            verifying_null_move: false,
+            root_move_nodes: vec![],
+            stopped: false,
+            node_limit: u64::MAX,
+            completed_depth: 0,
+            score: 0,
+            iteration_depth: 0,
+            history_moves: [[0; 64]; 12],
+            capture_history: [[[0; 6]; 64]; 12],
+            continuation_history: Default::default(),
+            stack: SearchStack::default(),
+
+            pv_length: [0; MAX_PLY],
+            pv_table: [[Move::default(); MAX_PLY]; MAX_PLY],
+            pawn_hash: PawnHashTable::default(),
+        }
+    }
+
+    pub fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.moves[..self.move_count].iter()
+    }
+
+    pub fn best_move(&self) -> Move {
+        self.pv_table[0][0]
+    }
+
+    /// Principal variation of the last search, starting with the best move.
+    pub fn principal_variation(&self) -> &[Move] {
+        &self.pv_table[0][..self.pv_length[0]]
+    }
+
+    /// Forgets the result of the last search, for positions that can't be searched.
+    #[cfg(feature = "search")]
+    pub(crate) fn clear_result(&mut self, score: i32) {
+        self.nodes = 0;
+        self.seldepth = 0;
+        self.tt_hits = 0;
+        self.pv_table[0][0] = Move::default();
+        self.pv_length[0] = 0;
+        self.completed_depth = 0;
+        self.score = score;
+    }
+
+    /// Ordering score of a quiet move at `ply`, adding how well it did after the moves played one
+    /// and two plies earlier to its history.
+    #[cfg(feature = "search")]
+    pub(crate) fn quiet_history(&self, ply: usize, piece_move: Move) -> i32 {
+        let mut score = self.history_moves[piece_move.piece()][piece_move.target()];
+
+        for (distance, history) in (1..=ply.min(2)).zip(&self.continuation_history) {
+            let previous = self.stack[ply - distance].current_move;
+            if previous != Move::default() {
+                score += history.get(previous, piece_move);
+            }
+        }
+
+        score
+    }
+
+    /// Halves every history score between searches, so what was learned while searching the
+    /// previous moves of the game still orders moves, without outweighing the current search.
+    #[cfg(feature = "search")]
+    pub(crate) fn age_histories(&mut self) {
+        age_table(&mut self.history_moves);
+        self.capture_history
+            .iter_mut()
+            .flatten()
+            .flatten()
+            .for_each(|score| *score /= 2);
+        for history in &mut self.continuation_history {
+            history.age();
+        }
+    }
+
+    /// Forgets every history score, what was learned in another game doesn't apply to this one.
+    #[cfg(feature = "search")]
+    pub(crate) fn clear_histories(&mut self) {
+        self.history_moves = [[0; 64]; 12];
+        self.capture_history = [[[0; 6]; 64]; 12];
+        self.continuation_history = Default::default();
+    }
+
+    /// Score of a capture taking a piece of kind `victim` in the capture history.
+    #[cfg(feature = "search")]
+    pub(crate) fn capture_history(&self, piece_move: Move, victim: PieceKind) -> i32 {
+        self.capture_history[piece_move.piece() as usize][piece_move.target() as usize]
+            [victim as usize]
+    }
+
+    /// Rewards (or punishes, with a negative bonus) a capture taking a piece of kind `victim`.
+    #[cfg(feature = "search")]
+    pub(crate) fn update_capture_history(
+        &mut self,
+        piece_move: Move,
+        victim: PieceKind,
+        bonus: i32,
+    ) {
+        apply_gravity(
+            &mut self.capture_history[piece_move.piece() as usize][piece_move.target() as usize]
+                [victim as usize],
+            bonus,
+        );
+    }
+
+    /// Rewards (or punishes, with a negative bonus) a quiet move played at `ply`, in the history
+    /// and the continuation histories.
+    #[cfg(feature = "search")]
+    pub(crate) fn update_quiet_history(&mut self, ply: usize, piece_move: Move, bonus: i32) {
+        apply_gravity(
+            &mut self.history_moves[piece_move.piece()][piece_move.target()],
+            bonus,
+        );
+
+        for (distance, history) in (1..=ply.min(2)).zip(&mut self.continuation_history) {
+            let previous = self.stack[ply - distance].current_move;
+            if previous != Move::default() {
+                history.add(previous, piece_move, bonus);
+            }
+        }
+    }
+
+    pub fn enable_pv_scoring(&mut self, game_ply: usize) {
+        self.follow_pv = false;
+
+        for piece_move in self.moves.into_iter().take(self.move_count) {
+            if self.pv_table[0][game_ply] == piece_move {
+                self.score_pv = true;
+                self.follow_pv = true;
+            }
+        }
+    }
+
+    pub fn push_move(&mut self, piece_move: Move) {
+        self.moves[self.move_count] = piece_move;
+        self.move_count += 1;
+    }
+}
+
+/// Bonus (or malus, when negated) of a move depending on the depth it did well (or badly) at.
+#[cfg(feature = "search")]
+pub(crate) fn history_bonus(depth: u8) -> i32 {
+    (depth as i32 * depth as i32).min(MAX_HISTORY / 4)
+}
+
+/// History gravity: the closer a score gets to [`MAX_HISTORY`] the less a bonus moves it, so
+/// scores can never overflow, and a move that stops doing well loses its score as fast as it
+/// earned it.
+#[cfg(feature = "search")]
+fn apply_gravity(score: &mut i32, bonus: i32) {
+    let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+    *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+}
+
+#[cfg(feature = "search")]
+fn age_table(table: &mut HistoryMoves) {
+    table.iter_mut().flatten().for_each(|score| *score /= 2);
+}
+
+#[cfg(all(test, feature = "search"))]
+mod tests {
+    use milky_bitboard::{MoveFlags, Pieces, PromotionPieces, Square};
+
+    use super::*;
+
+    fn quiet(source: Square, target: Square, piece: Pieces) -> Move {
+        Move::new(
+            source,
+            target,
+            piece,
+            PromotionPieces::NoPromotion,
+            MoveFlags::empty(),
+        )
+    }
+
+    #[test]
+    fn test_continuation_history() {
+        let mut state = SearchState::new();
+        let e2e4 = quiet(Square::E2, Square::E4, Pieces::WhitePawn);
+        let e7e5 = quiet(Square::E7, Square::E5, Pieces::BlackPawn);
+        let g1f3 = quiet(Square::G1, Square::F3, Pieces::WhiteKnight);
+
+        state.stack[0].current_move = e2e4;
+        state.stack[1].current_move = e7e5;
+        state.update_quiet_history(2, g1f3, 5);
+        assert_eq!(state.continuation_history[0].get(e7e5, g1f3), 5);
+        assert_eq!(state.continuation_history[1].get(e2e4, g1f3), 5);
+        assert_eq!(state.quiet_history(2, g1f3), 15);
+
+        // after a null move only the move two plies back counts
+        state.stack[1].current_move = Move::default();
+        assert_eq!(state.quiet_history(2, g1f3), 10);
+        assert_eq!(state.quiet_history(0, g1f3), 5);
+    }
+
+    #[test]
+    fn test_capture_history() {
+        let mut state = SearchState::new();
+        let capture = Move::new(
+            Square::E4,
+            Square::D5,
+            Pieces::WhitePawn,
+            PromotionPieces::NoPromotion,
+            MoveFlags::CAPTURE,
+        );
+
+        state.update_capture_history(capture, PieceKind::Knight, 4);
+        state.update_capture_history(capture, PieceKind::Knight, 2);
+        assert_eq!(state.capture_history(capture, PieceKind::Knight), 6);
+        // the same capture taking another piece is scored on its own
+        assert_eq!(state.capture_history(capture, PieceKind::Pawn), 0);
+    }
+
+    #[test]
+    fn test_history_gravity_and_aging() {
+        let mut state = SearchState::new();
+        let g1f3 = quiet(Square::G1, Square::F3, Pieces::WhiteKnight);
+
+        for _ in 0..1000 {
+            state.update_quiet_history(0, g1f3, history_bonus(20));
+        }
+        let score = state.quiet_history(0, g1f3);
+        assert!((MAX_HISTORY * 9 / 10..=MAX_HISTORY).contains(&score));
+
+        // a move failing from then on loses its score just as fast
+        for _ in 0..1000 {
+            state.update_quiet_history(0, g1f3, -history_bonus(20));
+        }
+        let score = state.quiet_history(0, g1f3);
+        assert!((-MAX_HISTORY..=-MAX_HISTORY * 9 / 10).contains(&score));
+
+        state.age_histories();
+        assert_eq!(state.quiet_history(0, g1f3), score / 2);
+    }
+}
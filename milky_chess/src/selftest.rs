@@ -0,0 +1,304 @@
+//! Quick checks of the tables and invariants the engine relies on, meant to be run on a fresh
+//! build before trusting it with games, as a miscompiled or misconfigured build usually breaks
+//! one of them long before it shows in play.
+//!
+//! Every check takes a fraction of a second. [`crate::init_static_members`] must be called before
+//! running them.
+
+use milky_bitboard::{BitBoard, Square};
+
+use crate::board::{BoardState, get_bishop_attacks, get_rook_attacks};
+use crate::magic::MagicNumbers;
+use crate::moves::{MoveContext, MoveKind, generate_moves, make_move};
+use crate::random::Random;
+use crate::search_state::SearchState;
+use crate::zobrist::{GamePosition, Zobrist, ZobristKey};
+use crate::{compute_bishop_attacks, compute_rook_attacks};
+
+/// Random occupancies looked up on every square when checking the attack tables.
+const OCCUPANCIES_PER_SQUARE: usize = 64;
+
+/// Random games played when checking the incremental zobrist keys.
+const ZOBRIST_GAMES: usize = 32;
+
+/// Longest random game played when checking the incremental zobrist keys.
+const ZOBRIST_GAME_PLIES: usize = 120;
+
+/// Perft counts of the positions from the chess programming wiki, shallow enough to run in a
+/// blink.
+static PERFT_SUITE: [(&str, u8, u64); 6] = [
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        3,
+        8902,
+    ),
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        2,
+        2039,
+    ),
+    ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812),
+    (
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        3,
+        9467,
+    ),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        2,
+        1486,
+    ),
+    (
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        2,
+        2079,
+    ),
+];
+
+/// Outcome of the check of a single subsystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestResult {
+    pub subsystem: &'static str,
+    /// Why the check failed, if it did.
+    pub result: Result<(), String>,
+}
+
+impl std::fmt::Display for SelfTestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(()) => write!(f, "{:<20}PASS", self.subsystem),
+            Err(reason) => write!(f, "{:<20}FAIL: {reason}", self.subsystem),
+        }
+    }
+}
+
+/// Runs every check, in the order the subsystems depend on each other.
+pub fn run() -> Vec<SelfTestResult> {
+    #[cfg_attr(not(feature = "search"), allow(unused_mut))]
+    let mut results = vec![
+        SelfTestResult {
+            subsystem: "magic tables",
+            result: check_magic_tables(),
+        },
+        SelfTestResult {
+            subsystem: "zobrist keys",
+            result: check_zobrist_keys(),
+        },
+        SelfTestResult {
+            subsystem: "move generation",
+            result: check_move_generation(),
+        },
+    ];
+
+    #[cfg(feature = "search")]
+    results.push(SelfTestResult {
+        subsystem: "transposition table",
+        result: check_transposition_table(),
+    });
+
+    results
+}
+
+/// Board, keys and move list of a single position, playing the role a [`crate::Milky`] plays in
+/// a game without allocating the tables of a search.
+struct Position {
+    board: BoardState,
+    zobrist: Zobrist,
+    search: Box<SearchState>,
+}
+
+impl Position {
+    fn new(fen: &str) -> Result<Self, String> {
+        let fen_parts = milky_fen::parse_fen_string(fen).map_err(|err| format!("{fen}: {err}"))?;
+        let mut position = Self {
+            board: BoardState::new(),
+            zobrist: Zobrist::new(),
+            search: Box::new(SearchState::new()),
+        };
+
+        position.board.load_fen(&fen_parts);
+        position.zobrist.position = position.hash_from_scratch();
+        position.board.pawn_key = position.zobrist.hash_pawns(&position.board.pieces);
+        Ok(position)
+    }
+
+    fn ctx(&mut self) -> MoveContext<'_> {
+        MoveContext {
+            board: &mut self.board,
+            zobrist: &mut self.zobrist,
+            search: &mut self.search,
+        }
+    }
+
+    fn hash_from_scratch(&self) -> ZobristKey {
+        self.zobrist.hash_position(GamePosition {
+            boards: self.board.pieces,
+            side_to_move: self.board.side_to_move,
+            en_passant: self.board.en_passant,
+            castling_rights: self.board.castling_rights,
+        })
+    }
+}
+
+/// The magic numbers must map every occupancy of a square to its own attacks, and the tables
+/// built from them at startup must hold the attacks a ray walk finds.
+fn check_magic_tables() -> Result<(), String> {
+    MagicNumbers::builtin().verify()?;
+
+    let mut rng = Random::new();
+    for square in Square::iter() {
+        for _ in 0..OCCUPANCIES_PER_SQUARE {
+            // sparse occupancies, like the ones of real positions
+            let occupancy = BitBoard::new(rng.gen_u64() & rng.gen_u64() & rng.gen_u64());
+
+            if get_bishop_attacks(square, occupancy) != compute_bishop_attacks(square, occupancy) {
+                return Err(format!("wrong bishop attacks from {square}"));
+            }
+
+            if get_rook_attacks(square, occupancy) != compute_rook_attacks(square, occupancy) {
+                return Err(format!("wrong rook attacks from {square}"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Plays random games, checking that the keys updated move by move always match the keys of the
+/// position hashed from scratch, including after taking moves back.
+fn check_zobrist_keys() -> Result<(), String> {
+    let mut rng = Random::new();
+
+    for _ in 0..ZOBRIST_GAMES {
+        let mut position = Position::new(PERFT_SUITE[0].0)?;
+
+        for _ in 0..ZOBRIST_GAME_PLIES {
+            let mut ctx = position.ctx();
+            generate_moves(&mut ctx, MoveKind::AllMoves);
+            let moves = ctx.search.moves().copied().collect::<Vec<_>>();
+            let legal_moves = moves
+                .into_iter()
+                .filter(|&piece_move| {
+                    let legal = make_move(&mut ctx, piece_move, MoveKind::AllMoves);
+                    if legal {
+                        ctx.zobrist.position = ctx.board.undo_move();
+                    }
+                    legal
+                })
+                .collect::<Vec<_>>();
+
+            if legal_moves.is_empty() {
+                break;
+            }
+
+            let key_before = position.zobrist.position;
+            let piece_move = legal_moves[rng.gen_u32() as usize % legal_moves.len()];
+            make_move(&mut position.ctx(), piece_move, MoveKind::AllMoves);
+            if position.zobrist.position != position.hash_from_scratch() {
+                return Err(format!("wrong key after {piece_move}"));
+            }
+            if position.board.pawn_key != position.zobrist.hash_pawns(&position.board.pieces) {
+                return Err(format!("wrong pawn key after {piece_move}"));
+            }
+
+            // taking the move back must restore the keys, then the game goes on
+            position.zobrist.position = position.board.undo_move();
+            if position.zobrist.position != key_before
+                || position.board.pawn_key != position.zobrist.hash_pawns(&position.board.pieces)
+            {
+                return Err(format!("wrong key after taking back {piece_move}"));
+            }
+            make_move(&mut position.ctx(), piece_move, MoveKind::AllMoves);
+        }
+    }
+
+    Ok(())
+}
+
+fn check_move_generation() -> Result<(), String> {
+    for (fen, depth, expected) in PERFT_SUITE {
+        let mut position = Position::new(fen)?;
+        let nodes = crate::perft::perft(&mut position.ctx(), depth);
+
+        if nodes != expected {
+            return Err(format!(
+                "perft {depth} of {fen} found {nodes} nodes instead of {expected}"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes entries to a small table and reads them back, mate scores included, as they are stored
+/// relative to the node that found them.
+#[cfg(feature = "search")]
+fn check_transposition_table() -> Result<(), String> {
+    use crate::search_state::MATE_UPPER_BOUND;
+    use crate::transposition_table::{TTFlag, TranspositionTable};
+
+    let mut tt = TranspositionTable::new(1);
+    let mut rng = Random::new();
+    let mut position = Position::new(PERFT_SUITE[1].0)?;
+    let mut ctx = position.ctx();
+    generate_moves(&mut ctx, MoveKind::AllMoves);
+    let moves = ctx.search.moves().copied().collect::<Vec<_>>();
+    let mut keys = vec![];
+
+    for (idx, &piece_move) in moves.iter().enumerate() {
+        let key = ZobristKey::from(rng.gen_u64());
+        let depth = idx as u8 % 16 + 1;
+        let score = match idx % 3 {
+            0 => MATE_UPPER_BOUND - 5,
+            1 => -MATE_UPPER_BOUND + 7,
+            _ => idx as i32 * 10 - 200,
+        };
+        let ply = idx % 10;
+        keys.push(key);
+
+        tt.set(piece_move, key, score, TTFlag::Exact, depth, ply);
+
+        let Some(entry) = tt.probe(key) else {
+            return Err(format!("entry {key} is missing after being written"));
+        };
+        if entry.best_move != piece_move || entry.depth != depth {
+            return Err(format!("entry {key} doesn't hold what was written"));
+        }
+
+        let mut best_move = Default::default();
+        let stored = tt.get(
+            key,
+            -MATE_UPPER_BOUND,
+            MATE_UPPER_BOUND,
+            depth,
+            ply,
+            &mut best_move,
+        );
+        if stored != Some(score) || best_move != piece_move {
+            return Err(format!(
+                "entry {key} read back {stored:?} instead of {score}"
+            ));
+        }
+    }
+
+    tt.clear();
+    if keys.iter().any(|&key| tt.probe(key).is_some()) {
+        return Err("entries are left after clearing the table".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_passes() {
+        crate::init_static_members();
+
+        for result in run() {
+            assert_eq!(result.result, Ok(()), "{}", result.subsystem);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 use milky_bitboard::Side;
@@ -6,6 +8,16 @@ pub trait IntoTimeControl {
     fn into_time_control(self, side_to_move: Side) -> TimeControl;
 }
 
+/// Time left on the clock that is never allocated to a move, covering the communication overhead
+/// with the GUI.
+const SAFETY_MARGIN: Duration = Duration::from_millis(50);
+
+/// Fraction of the allocated time an easy move gets, see [`TimeManager::play_easy_move`].
+const EASY_MOVE_FRACTION: u32 = 4;
+
+/// Time between two progress reports in the middle of an iteration.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct TimeManagerContext {
     pub depth: u8,
     pub nodes: u64,
@@ -28,10 +40,172 @@ pub enum TimeControl {
     MateIn(u8),
 }
 
+impl IntoTimeControl for TimeControl {
+    fn into_time_control(self, _: Side) -> TimeControl {
+        self
+    }
+}
+
+/// Time left and increment of one of the players of a [`Clock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerClock {
+    pub time_left: Duration,
+    pub increment: Duration,
+    /// Moves played by the player so far.
+    pub moves: u32,
+}
+
+/// Both players' clocks over a game, pressed after every move like a chess clock.
+///
+/// Drives the clocks of games played between engines, and lets tests simulate how the time
+/// manager spends a clock over a whole game under different time controls without searching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    white: PlayerClock,
+    black: PlayerClock,
+    /// Moves of each period of a classical control, every player gets the base time added back
+    /// once they played that many moves. `None` plays the whole game on the base time.
+    period_moves: Option<u32>,
+    base_time: Duration,
+}
+
+impl Clock {
+    /// Both players start with `time` and get `increment` after every move.
+    pub fn new(time: Duration, increment: Duration) -> Self {
+        let player = PlayerClock {
+            time_left: time,
+            increment,
+            moves: 0,
+        };
+
+        Self {
+            white: player,
+            black: player,
+            period_moves: None,
+            base_time: time,
+        }
+    }
+
+    /// Classical control where every `moves` moves each player gets `time` added to their clock,
+    /// like 40 moves in 90 minutes.
+    pub fn with_period(mut self, moves: u32) -> Self {
+        self.period_moves = Some(moves.max(1));
+        self
+    }
+
+    pub fn player(&self, side: Side) -> PlayerClock {
+        match side {
+            Side::White => self.white,
+            Side::Black => self.black,
+            _ => unreachable!(),
+        }
+    }
+
+    fn player_mut(&mut self, side: Side) -> &mut PlayerClock {
+        match side {
+            Side::White => &mut self.white,
+            Side::Black => &mut self.black,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Moves the player still has to make before the end of the current period.
+    pub fn moves_to_go(&self, side: Side) -> Option<u32> {
+        let moves = self.player(side).moves;
+        self.period_moves.map(|period| period - moves % period)
+    }
+
+    /// Time control the player searches their next move with, as sent through `go`.
+    pub fn time_control(&self, side: Side) -> TimeControl {
+        let player = self.player(side);
+        TimeControl::Conventional(ConventionalTimeControl {
+            time_left: player.time_left,
+            increment: player.increment,
+            moves_to_go: self.moves_to_go(side),
+        })
+    }
+
+    /// Charges the player with the time their move took, then adds their increment, and the
+    /// base time when the move ends a period.
+    ///
+    /// Returns whether the move was made in time, the clock of a player that flagged stays at
+    /// zero.
+    pub fn record_move(&mut self, side: Side, used: Duration) -> bool {
+        let ends_period = self.moves_to_go(side) == Some(1);
+        let base_time = self.base_time;
+
+        let player = self.player_mut(side);
+        let Some(time_left) = player.time_left.checked_sub(used) else {
+            player.time_left = Duration::ZERO;
+            return false;
+        };
+
+        player.time_left = time_left + player.increment;
+        player.moves += 1;
+        if ends_period {
+            player.time_left += base_time;
+        }
+
+        true
+    }
+}
+
+impl IntoTimeControl for Clock {
+    fn into_time_control(self, side_to_move: Side) -> TimeControl {
+        self.time_control(side_to_move)
+    }
+}
+
+/// Controls a running search from another thread: stops it, in which case it still plays the
+/// best move it found so far, or tells a ponder search that the opponent played the expected
+/// move.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    /// Stops the search, which also ends pondering.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    /// Lowers the flag, a search started while it is raised stops right after its first
+    /// iteration.
+    pub fn reset(&self) {
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Makes the next search ponder: it ignores its time control, searching on the opponent's
+    /// time, until [`StopHandle::ponder_hit`] is called.
+    pub fn start_pondering(&self) {
+        self.pondering.store(true, Ordering::Relaxed);
+    }
+
+    /// The opponent played the pondered move, the search keeps going with the time control it
+    /// was started with, its clock starting now.
+    pub fn ponder_hit(&self) {
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct SearchLimits {
     start_time: Instant,
     time_control: TimeControl,
+    nodes_time: Option<u64>,
+    max_nodes: Option<u64>,
+    stop_handle: Option<StopHandle>,
 }
 
 impl SearchLimits {
@@ -39,73 +213,596 @@ impl SearchLimits {
         Self {
             time_control,
             start_time: Instant::now(),
+            nodes_time: None,
+            max_nodes: None,
+            stop_handle: None,
         }
     }
 
+    /// Treat every millisecond of the allocated time as `nodes_per_ms` nodes, a value of zero
+    /// keeps using the wall clock.
+    pub fn with_nodes_time(mut self, nodes_per_ms: u64) -> Self {
+        self.nodes_time = (nodes_per_ms > 0).then_some(nodes_per_ms);
+        self
+    }
+
+    /// Caps the search at `max_nodes` nodes on top of the time control, whichever runs out first
+    /// stops it.
+    pub fn with_max_nodes(mut self, max_nodes: Option<u64>) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// Stops the search as soon as the handle is stopped, regardless of the time control.
+    pub fn with_stop_handle(mut self, stop_handle: StopHandle) -> Self {
+        self.stop_handle = Some(stop_handle);
+        self
+    }
+
     pub fn start_time(&self) -> Instant {
         self.start_time
     }
 }
 
+/// Time management decisions of a finished search, reported in debug mode to diagnose flagging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimeReport {
+    pub used: Duration,
+    pub allocated: Duration,
+    /// Milliseconds left after the move, negative when the search overstepped.
+    pub margin_ms: i64,
+}
+
+impl std::fmt::Display for TimeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "movetime_used {} allocated {} margin {}",
+            self.used.as_millis(),
+            self.allocated.as_millis(),
+            self.margin_ms
+        )
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct TimeManager {
     search_limits: SearchLimits,
     stop_time: Option<Instant>,
+    stop_nodes: Option<u64>,
+    /// Extra time granted on top of the base allocation, see [`TimeManager::extend`].
+    extension: Duration,
+    /// Set when the base allocation was cut short, see [`TimeManager::play_easy_move`].
+    easy_move: bool,
+    /// Set while searching on the opponent's time, the time control only applies after the
+    /// ponder hit.
+    pondering: bool,
+    /// Elapsed time at which the next progress report is due, see [`TimeManager::progress_due`].
+    next_progress: Duration,
 }
 
 impl TimeManager {
     pub fn new(search_limits: SearchLimits) -> Self {
+        let pondering = search_limits
+            .stop_handle
+            .as_ref()
+            .is_some_and(StopHandle::is_pondering);
+
         let mut time_manager = Self {
             search_limits,
             stop_time: None,
+            stop_nodes: None,
+            extension: Duration::ZERO,
+            easy_move: false,
+            pondering,
+            next_progress: PROGRESS_INTERVAL,
         };
 
-        time_manager.compute_stop_time();
+        time_manager.compute_stop_time(0);
 
         time_manager
     }
 
-    fn compute_stop_time(&mut self) {
+    /// Computes when the search stops, `nodes` being the nodes already searched, which don't
+    /// count towards a node budget.
+    fn compute_stop_time(&mut self, nodes: u64) {
         let start_time = self.search_limits.start_time();
 
+        let Some(allocated_time) = self.allocated_time() else {
+            return;
+        };
+
+        // With `nodestime` enabled the clock budget is converted into a node budget, so the
+        // search stops at the same point regardless of how fast the hardware is.
+        match self.search_limits.nodes_time {
+            Some(nodes_per_ms) => {
+                self.stop_nodes = Some(nodes + allocated_time.as_millis() as u64 * nodes_per_ms);
+            }
+            None => self.stop_time = Some(start_time + allocated_time),
+        }
+    }
+
+    fn allocated_time(&self) -> Option<Duration> {
         match &self.search_limits.time_control {
-            TimeControl::MoveTime(duration) => self.stop_time = Some(start_time + *duration),
+            TimeControl::MoveTime(duration) => Some(*duration),
             TimeControl::Conventional(ConventionalTimeControl {
                 time_left,
                 increment,
                 moves_to_go,
             }) => {
-                let mut time_per_move = *time_left / moves_to_go.unwrap_or(40);
+                let mut time_per_move = *time_left / moves_to_go.unwrap_or(40).max(1);
                 time_per_move += *increment * 3 / 4;
-                let safety_margin = Duration::from_millis(50);
-                let stop_time = start_time + time_per_move - safety_margin;
-                self.stop_time = Some(stop_time);
+
+                let mut allocated = time_per_move.saturating_sub(SAFETY_MARGIN);
+                if self.easy_move {
+                    allocated /= EASY_MOVE_FRACTION;
+                }
+                Some(allocated + self.extension)
             }
             // no fixed stop_time for the following time controls
-            TimeControl::FixedDepth(_) => {}
-            TimeControl::FixedNodes(_) => {}
-            TimeControl::MateIn(_) => {}
-            TimeControl::Infinite => {}
+            TimeControl::FixedDepth(_) => None,
+            TimeControl::FixedNodes(_) => None,
+            TimeControl::MateIn(_) => None,
+            TimeControl::Infinite => None,
         }
     }
 
-    pub fn should_stop(&self, ctx: TimeManagerContext) -> bool {
+    /// Gives the search half of its base allocation again, used when the root score is unstable
+    /// and the move is worth thinking longer about. Only clock based searches are extended, a
+    /// fixed move time is a hard budget, and a search is extended at most once.
+    ///
+    /// Returns whether the allocation was extended.
+    pub fn extend(&mut self) -> bool {
+        let TimeControl::Conventional(control) = self.search_limits.time_control else {
+            return false;
+        };
+
+        if !self.extension.is_zero() {
+            return false;
+        }
+
+        // a search unstable enough to be extended has no easy move
+        self.easy_move = false;
+        let Some(allocated) = self.allocated_time() else {
+            return false;
+        };
+
+        // never allocate more than what is on the clock
+        let available = control.time_left.saturating_sub(SAFETY_MARGIN);
+        self.extension = (allocated / 2).min(available.saturating_sub(allocated));
+        self.compute_stop_time(0);
+
+        !self.extension.is_zero()
+    }
+
+    /// Whether [`TimeManager::play_easy_move`] would shorten the search, which is only the case
+    /// for clock based searches that weren't extended nor shortened yet.
+    pub fn can_play_easy_move(&self) -> bool {
+        matches!(
+            self.search_limits.time_control,
+            TimeControl::Conventional(_)
+        ) && !self.easy_move
+            && self.extension.is_zero()
+    }
+
+    /// Cuts the base allocation down to a fraction of itself, used when the best move is so much
+    /// better than the others that thinking longer won't change it.
+    pub fn play_easy_move(&mut self) {
+        if self.can_play_easy_move() {
+            self.easy_move = true;
+            self.compute_stop_time(0);
+        }
+    }
+
+    /// Time since the search started.
+    pub fn elapsed(&self) -> Duration {
+        self.search_limits.start_time().elapsed()
+    }
+
+    /// Whether the search should report its progress, true at most once every
+    /// [`PROGRESS_INTERVAL`] so long iterations don't go silent.
+    pub fn progress_due(&mut self) -> bool {
+        let elapsed = self.elapsed();
+        if elapsed < self.next_progress {
+            return false;
+        }
+
+        self.next_progress = elapsed + PROGRESS_INTERVAL;
+        true
+    }
+
+    /// How the allocated time was spent, only available for searches with a time budget.
+    pub fn time_report(&self) -> Option<TimeReport> {
+        let allocated = self.allocated_time()?;
+        let used = self.elapsed();
+
+        // for a clock, the margin is what remains of it after this move, otherwise it is what
+        // remains of the budget
+        let remaining = match &self.search_limits.time_control {
+            TimeControl::Conventional(control) => control.time_left,
+            _ => allocated,
+        };
+
+        Some(TimeReport {
+            used,
+            allocated,
+            margin_ms: remaining.as_millis() as i64 - used.as_millis() as i64,
+        })
+    }
+
+    /// Moves within which `go mate` looks for a mate.
+    pub fn mate_in(&self) -> Option<u8> {
+        match self.search_limits.time_control {
+            TimeControl::MateIn(moves) => Some(moves),
+            _ => None,
+        }
+    }
+
+    /// Deepest iteration the search may complete, when it is limited by depth.
+    pub fn max_depth(&self) -> Option<u8> {
+        match self.search_limits.time_control {
+            TimeControl::FixedDepth(max_depth) => Some(max_depth),
+            TimeControl::MateIn(moves) => Some(moves.saturating_mul(2)),
+            _ => None,
+        }
+    }
+
+    /// Nodes the search may visit in total, when it is limited by a node budget. There is no
+    /// budget while pondering, as the time control only applies after the ponder hit.
+    pub fn node_limit(&self) -> Option<u64> {
+        if self.pondering {
+            return None;
+        }
+
+        let node_limit = match self.search_limits.time_control {
+            TimeControl::FixedNodes(max_nodes) => Some(max_nodes),
+            _ => self.stop_nodes,
+        };
+
+        match (node_limit, self.search_limits.max_nodes) {
+            (Some(node_limit), Some(max_nodes)) => Some(node_limit.min(max_nodes)),
+            (node_limit, max_nodes) => node_limit.or(max_nodes),
+        }
+    }
+
+    /// Whether the search was stopped from the outside through its stop handle.
+    pub fn is_stopped(&self) -> bool {
+        self.search_limits
+            .stop_handle
+            .as_ref()
+            .is_some_and(StopHandle::is_stopped)
+    }
+
+    pub fn should_stop(&mut self, ctx: TimeManagerContext) -> bool {
+        if self.is_stopped() {
+            return true;
+        }
+
+        if self.pondering {
+            if self
+                .search_limits
+                .stop_handle
+                .as_ref()
+                .is_some_and(StopHandle::is_pondering)
+            {
+                return false;
+            }
+
+            // ponder hit, the time spent pondering was the opponent's, so the clock of this move
+            // starts now
+            self.pondering = false;
+            self.search_limits.start_time = Instant::now();
+            self.next_progress = PROGRESS_INTERVAL;
+            self.compute_stop_time(ctx.nodes);
+        }
+
+        if self
+            .search_limits
+            .max_nodes
+            .is_some_and(|max_nodes| ctx.nodes >= max_nodes)
+        {
+            return true;
+        }
+
         if let Some(stop_time) = self.stop_time {
             return Instant::now() >= stop_time;
         };
 
-        if let TimeControl::FixedDepth(max_depth) = self.search_limits.time_control {
-            return ctx.depth >= max_depth;
+        if let Some(stop_nodes) = self.stop_nodes {
+            return ctx.nodes >= stop_nodes;
+        };
+
+        if let Some(max_depth) = self.max_depth() {
+            return ctx.depth > max_depth;
         }
 
         if let TimeControl::FixedNodes(max_nodes) = self.search_limits.time_control {
             return ctx.nodes >= max_nodes;
         }
 
-        if let TimeControl::MateIn(mate_depth) = self.search_limits.time_control {
-            return ctx.depth > mate_depth * 2;
-        }
-
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conventional(time_left_ms: u64) -> TimeControl {
+        TimeControl::Conventional(ConventionalTimeControl {
+            time_left: Duration::from_millis(time_left_ms),
+            increment: Duration::ZERO,
+            moves_to_go: Some(10),
+        })
+    }
+
+    #[test]
+    fn test_nodes_time_converts_clock_into_node_budget() {
+        let limits = SearchLimits::new(conventional(10_050)).with_nodes_time(100);
+        let mut time_manager = TimeManager::new(limits);
+
+        // 10050ms over 10 moves gives 1005ms, minus the 50ms safety margin
+        assert_eq!(time_manager.stop_nodes, Some(955 * 100));
+        assert_eq!(time_manager.stop_time, None);
+
+        assert!(!time_manager.should_stop(TimeManagerContext {
+            depth: 1,
+            nodes: 95_499,
+        }));
+        assert!(time_manager.should_stop(TimeManagerContext {
+            depth: 1,
+            nodes: 95_500,
+        }));
+    }
+
+    #[test]
+    fn test_nodes_time_applies_to_move_time() {
+        let limits = SearchLimits::new(TimeControl::MoveTime(Duration::from_millis(20)))
+            .with_nodes_time(1000);
+        let time_manager = TimeManager::new(limits);
+
+        assert_eq!(time_manager.stop_nodes, Some(20_000));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let node_limit = |limits| TimeManager::new(limits).node_limit();
+
+        assert_eq!(
+            node_limit(SearchLimits::new(TimeControl::FixedNodes(10_000))),
+            Some(10_000)
+        );
+        assert_eq!(
+            node_limit(SearchLimits::new(conventional(10_050)).with_nodes_time(100)),
+            Some(955 * 100)
+        );
+        assert_eq!(node_limit(SearchLimits::new(conventional(10_050))), None);
+        assert_eq!(
+            node_limit(SearchLimits::new(TimeControl::FixedDepth(5))),
+            None
+        );
+        assert_eq!(
+            node_limit(
+                SearchLimits::new(TimeControl::FixedNodes(10_000)).with_max_nodes(Some(500))
+            ),
+            Some(500)
+        );
+        assert_eq!(
+            node_limit(SearchLimits::new(conventional(10_050)).with_max_nodes(Some(500))),
+            Some(500)
+        );
+    }
+
+    #[test]
+    fn test_max_nodes_caps_any_time_control() {
+        let limits = SearchLimits::new(TimeControl::Infinite).with_max_nodes(Some(1000));
+        let mut time_manager = TimeManager::new(limits);
+
+        assert!(!time_manager.should_stop(TimeManagerContext {
+            depth: 30,
+            nodes: 999,
+        }));
+        assert!(time_manager.should_stop(TimeManagerContext {
+            depth: 1,
+            nodes: 1000,
+        }));
+    }
+
+    #[test]
+    fn test_zero_nodes_time_uses_the_clock() {
+        let limits = SearchLimits::new(conventional(10_050)).with_nodes_time(0);
+        let time_manager = TimeManager::new(limits);
+
+        assert_eq!(time_manager.stop_nodes, None);
+        assert!(time_manager.stop_time.is_some());
+    }
+
+    #[test]
+    fn test_time_report() {
+        let time_manager = TimeManager::new(SearchLimits::new(conventional(10_050)));
+        let report = time_manager.time_report().unwrap();
+
+        assert_eq!(report.allocated, Duration::from_millis(955));
+        assert!(report.margin_ms <= 10_050 && report.margin_ms > 9_000);
+        assert!(report.to_string().starts_with("movetime_used "));
+
+        let time_manager = TimeManager::new(SearchLimits::new(TimeControl::FixedDepth(4)));
+        assert_eq!(time_manager.time_report(), None);
+    }
+
+    #[test]
+    fn test_extend_only_applies_to_clocks_once() {
+        let mut time_manager = TimeManager::new(SearchLimits::new(conventional(10_050)));
+        let stop_time = time_manager.stop_time.unwrap();
+
+        assert!(time_manager.extend());
+        assert_eq!(
+            time_manager.stop_time.unwrap() - stop_time,
+            Duration::from_micros(477_500)
+        );
+        assert_eq!(
+            time_manager.time_report().unwrap().allocated,
+            Duration::from_micros(1_432_500)
+        );
+        assert!(!time_manager.extend());
+
+        let limits = SearchLimits::new(conventional(10_050)).with_nodes_time(100);
+        let mut time_manager = TimeManager::new(limits);
+        assert!(time_manager.extend());
+        assert_eq!(time_manager.stop_nodes, Some(1432 * 100));
+
+        let limits = SearchLimits::new(TimeControl::MoveTime(Duration::from_millis(100)));
+        assert!(!TimeManager::new(limits).extend());
+    }
+
+    #[test]
+    fn test_extend_never_exceeds_the_clock() {
+        let mut time_manager = TimeManager::new(SearchLimits::new(TimeControl::Conventional(
+            ConventionalTimeControl {
+                time_left: Duration::from_millis(1_050),
+                increment: Duration::ZERO,
+                moves_to_go: Some(1),
+            },
+        )));
+
+        // the whole clock, minus the safety margin, is already allocated
+        assert!(!time_manager.extend());
+        assert_eq!(
+            time_manager.time_report().unwrap().allocated,
+            Duration::from_millis(1_000)
+        );
+    }
+
+    #[test]
+    fn test_stop_handle() {
+        let stop_handle = StopHandle::default();
+        let limits = SearchLimits::new(TimeControl::Infinite).with_stop_handle(stop_handle.clone());
+        let mut time_manager = TimeManager::new(limits);
+        let ctx = || TimeManagerContext { depth: 1, nodes: 0 };
+
+        assert!(!time_manager.should_stop(ctx()));
+        stop_handle.stop();
+        assert!(time_manager.is_stopped());
+        assert!(time_manager.should_stop(ctx()));
+
+        stop_handle.reset();
+        assert!(!time_manager.should_stop(ctx()));
+    }
+
+    #[test]
+    fn test_pondering_ignores_limits_until_ponder_hit() {
+        let stop_handle = StopHandle::default();
+        stop_handle.start_pondering();
+
+        let limits = SearchLimits::new(TimeControl::MoveTime(Duration::ZERO))
+            .with_nodes_time(10)
+            .with_stop_handle(stop_handle.clone());
+        let mut time_manager = TimeManager::new(limits);
+        let ctx = |nodes| TimeManagerContext { depth: 1, nodes };
+
+        assert!(!time_manager.should_stop(ctx(1_000_000)));
+
+        // the node budget is counted from the ponder hit
+        let limits = SearchLimits::new(TimeControl::MoveTime(Duration::from_millis(10)))
+            .with_nodes_time(10)
+            .with_stop_handle(stop_handle.clone());
+        let mut time_manager = TimeManager::new(limits);
+
+        stop_handle.ponder_hit();
+        assert!(!time_manager.should_stop(ctx(1_000)));
+        assert!(!time_manager.should_stop(ctx(1_099)));
+        assert!(time_manager.should_stop(ctx(1_100)));
+
+        // stopping ends pondering as well
+        stop_handle.start_pondering();
+        stop_handle.stop();
+        assert!(!stop_handle.is_pondering());
+    }
+
+    #[test]
+    fn test_easy_move_shortens_clock_searches() {
+        let mut time_manager = TimeManager::new(SearchLimits::new(conventional(10_050)));
+        assert_eq!(
+            time_manager.allocated_time(),
+            Some(Duration::from_millis(955))
+        );
+
+        assert!(time_manager.can_play_easy_move());
+        time_manager.play_easy_move();
+        assert!(!time_manager.can_play_easy_move());
+        assert_eq!(
+            time_manager.allocated_time(),
+            Some(Duration::from_micros(238_750))
+        );
+
+        // becoming unstable afterwards brings the whole allocation back, and then some
+        assert!(time_manager.extend());
+        assert_eq!(
+            time_manager.allocated_time(),
+            Some(Duration::from_micros(1_432_500))
+        );
+
+        let mut time_manager = TimeManager::new(SearchLimits::new(TimeControl::MoveTime(
+            Duration::from_secs(1),
+        )));
+        assert!(!time_manager.can_play_easy_move());
+        time_manager.play_easy_move();
+        assert_eq!(time_manager.allocated_time(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_clock_periods() {
+        let mut clock = Clock::new(Duration::from_secs(60), Duration::ZERO).with_period(2);
+        assert_eq!(clock.moves_to_go(Side::White), Some(2));
+
+        assert!(clock.record_move(Side::White, Duration::from_secs(20)));
+        assert_eq!(clock.moves_to_go(Side::White), Some(1));
+        assert_eq!(clock.moves_to_go(Side::Black), Some(2));
+
+        // the end of the period gives the base time back
+        assert!(clock.record_move(Side::White, Duration::from_secs(30)));
+        assert_eq!(clock.moves_to_go(Side::White), Some(2));
+        assert_eq!(clock.player(Side::White).time_left, Duration::from_secs(70));
+
+        assert!(!clock.record_move(Side::Black, Duration::from_secs(61)));
+        assert_eq!(clock.player(Side::Black).time_left, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_simulated_games_never_flag() {
+        let controls = [
+            (
+                "bullet",
+                Clock::new(Duration::from_secs(60), Duration::ZERO),
+            ),
+            (
+                "blitz",
+                Clock::new(Duration::from_secs(180), Duration::from_secs(2)),
+            ),
+            (
+                "classical",
+                Clock::new(Duration::from_secs(90 * 60), Duration::ZERO).with_period(40),
+            ),
+        ];
+
+        for (name, mut clock) in controls {
+            for _ in 0..150 {
+                for side in [Side::White, Side::Black] {
+                    // every move is an unstable search using all of its extended allocation,
+                    // plus some overhead on top
+                    let mut time_manager =
+                        TimeManager::new(SearchLimits::new(clock.into_time_control(side)));
+                    time_manager.extend();
+                    let used = time_manager.allocated_time().unwrap() + Duration::from_millis(5);
+
+                    assert!(clock.record_move(side, used), "{name} flagged");
+                }
+            }
+
+            assert!(
+                clock.player(Side::White).time_left > SAFETY_MARGIN,
+                "{name}"
+            );
+        }
+    }
+}
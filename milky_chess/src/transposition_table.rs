@@ -1,10 +1,36 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use milky_bitboard::Move;
 
-use crate::search::MATE_LOWER_BOUND;
+use crate::search_state::MATE_LOWER_BOUND;
 use crate::zobrist::ZobristKey;
 
 static ONE_MB: usize = 0x100000;
 
+/// Size of the transposition table, in megabytes, used when no size is requested.
+pub static DEFAULT_HASH_SIZE: usize = 64;
+
+/// Entries sharing a cluster, all of them read with a single cache line.
+const CLUSTER_SIZE: usize = 4;
+
+/// Generations are stored in the bits of a slot the flag leaves free, so they wrap around.
+const GENERATION_MASK: u8 = 0x3F;
+
+/// How many priority points an entry loses per search since it was written, see [`value`].
+const AGE_PENALTY: i32 = 16;
+
+/// First bytes of a saved table, see [`TranspositionTable::save`].
+const FILE_MAGIC: &[u8; 8] = b"MILKYTT\0";
+
+/// Version of the layout of saved tables, bumped whenever the layout of a slot changes.
+const FILE_VERSION: u32 = 1;
+
+/// Bytes of the header of a saved table: magic, version, zobrist fingerprint, cluster count and
+/// generation.
+const FILE_HEADER_SIZE: u64 = 8 + 4 + 8 + 8 + 1;
+
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 #[repr(u8)]
 pub enum TTFlag {
@@ -21,35 +47,325 @@ pub struct TTEntry {
     pub depth: u8,
     pub flag: TTFlag,
     pub best_move: Move,
+    /// Static evaluation of the position, spared from being computed again when the position
+    /// comes back. Evaluations too large to store are left out.
+    pub static_eval: Option<i16>,
+    /// Search that wrote the entry, see [`TranspositionTable::new_search`].
+    pub generation: u8,
+}
+
+/// An entry as stored in the table, packed in 16 bytes so a whole cluster fits in a cache line.
+///
+/// Only the upper half of the key is kept, the lower half is the index of the cluster. An empty
+/// slot has no flag, which is why the flag is stored shifted by one.
+#[derive(Debug, Default, Clone, Copy)]
+struct Slot {
+    key: u32,
+    best_move: Move,
+    score: i32,
+    /// `i16::MIN` when the entry has no static evaluation.
+    static_eval: i16,
+    depth: u8,
+    /// Flag in the lower two bits, generation in the rest.
+    flag_generation: u8,
+}
+
+impl Slot {
+    fn pack(entry: &TTEntry) -> Self {
+        Self {
+            key: slot_key(entry.key),
+            best_move: entry.best_move,
+            score: entry.score,
+            static_eval: entry.static_eval.unwrap_or(i16::MIN),
+            depth: entry.depth,
+            flag_generation: (entry.generation << 2) | (entry.flag as u8 + 1),
+        }
+    }
+
+    fn unpack(&self, key: ZobristKey) -> TTEntry {
+        TTEntry {
+            key,
+            score: self.score,
+            depth: self.depth,
+            flag: self.flag(),
+            best_move: self.best_move,
+            static_eval: (self.static_eval != i16::MIN).then_some(self.static_eval),
+            generation: self.generation(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.flag_generation & 0b11 == 0
+    }
+
+    fn flag(&self) -> TTFlag {
+        match self.flag_generation & 0b11 {
+            1 => TTFlag::Beta,
+            2 => TTFlag::Alpha,
+            _ => TTFlag::Exact,
+        }
+    }
+
+    fn generation(&self) -> u8 {
+        self.flag_generation >> 2
+    }
+}
+
+/// A slot shared by every search thread, read and written without any locking.
+///
+/// Threads writing the same slot at once can leave it with the halves of two different entries.
+/// The key is stored xored with the data, so such a slot reads back with a key no probe is
+/// looking for instead of handing out the score of another position. The best move shares its
+/// word with the key and can still come out wrong, which is why the search checks hash moves
+/// are pseudo legal before trusting them.
+#[derive(Debug, Default)]
+struct SharedSlot {
+    key_move: AtomicU64,
+    data: AtomicU64,
+}
+
+impl SharedSlot {
+    fn load(&self) -> Slot {
+        let data = self.data.load(Ordering::Relaxed);
+        let key_move = self.key_move.load(Ordering::Relaxed) ^ data;
+
+        Slot {
+            key: (key_move >> 32) as u32,
+            best_move: Move::from(key_move as u32),
+            score: data as u32 as i32,
+            static_eval: (data >> 32) as u16 as i16,
+            depth: (data >> 48) as u8,
+            flag_generation: (data >> 56) as u8,
+        }
+    }
+
+    fn store(&self, slot: Slot) {
+        let key_move = ((slot.key as u64) << 32) | *slot.best_move as u64;
+        let data = slot.score as u32 as u64
+            | (slot.static_eval as u16 as u64) << 32
+            | (slot.depth as u64) << 48
+            | (slot.flag_generation as u64) << 56;
+
+        self.key_move.store(key_move ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+#[repr(C, align(64))]
+struct Cluster {
+    slots: [SharedSlot; CLUSTER_SIZE],
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Part of the key stored in a slot, the bits the index of the cluster doesn't come from.
+fn slot_key(key: ZobristKey) -> u32 {
+    (key.inner() >> 32) as u32
+}
+
+impl TTEntry {
+    /// The stored score, when it is deep enough and its bound settles the `alpha`-`beta` window
+    /// of a node `ply` plies from the root.
+    pub fn usable_score(&self, alpha: i32, beta: i32, depth: u8, ply: usize) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+
+        let mut score = self.score;
+        if score < -MATE_LOWER_BOUND {
+            score += ply as i32
+        }
+
+        if score > MATE_LOWER_BOUND {
+            score -= ply as i32;
+        }
+        match self.flag {
+            TTFlag::Exact => Some(score),
+            TTFlag::Alpha if score <= alpha => Some(alpha),
+            TTFlag::Beta if score >= beta => Some(beta),
+            _ => None,
+        }
+    }
 }
 
+/// Entries are grouped in clusters of [`CLUSTER_SIZE`], a position can be stored in any slot of
+/// the cluster its key maps to. A new position pushes out the least valuable entry of the
+/// cluster, by depth, bound and age, instead of whatever entry happened to share its index.
+///
+/// Search threads share the table without locking it, see [`SharedSlot`]. Two threads storing
+/// the same cluster at once may both pick the same slot, one of the entries is then lost, which
+/// costs far less than making every thread wait on each other.
 #[derive(Debug)]
 pub struct TranspositionTable {
-    entries: Vec<TTEntry>,
+    clusters: Vec<Cluster>,
+    /// The cluster count is a power of two, so the index of a key is its lower bits.
+    mask: usize,
+    generation: u8,
 }
 
 impl Default for TranspositionTable {
     fn default() -> Self {
-        Self::new(64)
+        Self::new(DEFAULT_HASH_SIZE)
     }
 }
 
 impl TranspositionTable {
+    /// Creates a table using at most `size` megabytes. The cluster count is rounded down to a
+    /// power of two, which lets probes index the table with a mask instead of a division.
     pub fn new(size: usize) -> Self {
         let tt_size_bytes: usize = ONE_MB * size;
-        let tt_entry_count = tt_size_bytes / std::mem::size_of::<TTEntry>();
+        let tt_cluster_count = (tt_size_bytes / std::mem::size_of::<Cluster>()).max(1);
+        let tt_cluster_count = 1 << tt_cluster_count.ilog2();
 
         Self {
-            entries: vec![TTEntry::default(); tt_entry_count],
+            clusters: (0..tt_cluster_count).map(|_| Cluster::default()).collect(),
+            mask: tt_cluster_count - 1,
+            generation: 0,
         }
     }
 
+    /// Replaces the table by one using at most `size` megabytes, discarding every entry.
+    pub fn resize(&mut self, size: usize) {
+        *self = Self {
+            generation: self.generation,
+            ..Self::new(size)
+        };
+    }
+
+    /// Memory used by the entries, in megabytes, which is at most the size the table was created
+    /// with.
+    pub fn size(&self) -> usize {
+        self.clusters.len() * std::mem::size_of::<Cluster>() / ONE_MB
+    }
+
     fn index(&self, key: ZobristKey) -> usize {
-        key.inner() as usize % self.entries.len()
+        key.inner() as usize & self.mask
     }
 
     pub fn clear(&mut self) {
-        self.entries.fill(TTEntry::default());
+        self.clusters.fill_with(Cluster::default);
+    }
+
+    /// Writes every entry to `path`, along with the `fingerprint` of the zobrist keys they were
+    /// hashed with, so a later session can pick up where this one left off.
+    ///
+    /// Slots are written as stored, two little endian words each, after a header holding
+    /// [`FILE_MAGIC`], [`FILE_VERSION`], the fingerprint, the cluster count and the generation.
+    pub fn save(&self, path: &Path, fingerprint: u64) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&fingerprint.to_le_bytes())?;
+        writer.write_all(&(self.clusters.len() as u64).to_le_bytes())?;
+        writer.write_all(&[self.generation])?;
+
+        for slot in self.clusters.iter().flat_map(|cluster| &cluster.slots) {
+            writer.write_all(&slot.key_move.load(Ordering::Relaxed).to_le_bytes())?;
+            writer.write_all(&slot.data.load(Ordering::Relaxed).to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// Replaces the table by the one saved to `path`, which takes the size it was saved with.
+    ///
+    /// Files of another version, or written with zobrist keys whose fingerprint doesn't match
+    /// `fingerprint`, are rejected and leave the table untouched, as their entries would belong
+    /// to positions no search can reach.
+    pub fn load(&mut self, path: &Path, fingerprint: u64) -> std::io::Result<()> {
+        let invalid = |reason: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {reason}", path.display()),
+            )
+        };
+
+        let file = std::fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid("not a saved transposition table"));
+        }
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        if u32::from_le_bytes(version) != FILE_VERSION {
+            return Err(invalid("saved by an incompatible version"));
+        }
+
+        if read_u64(&mut reader)? != fingerprint {
+            return Err(invalid("saved with different zobrist keys"));
+        }
+
+        // checked against the size of the file before allocating anything
+        let cluster_count = read_u64(&mut reader)?;
+        let slots_size = cluster_count.checked_mul(std::mem::size_of::<Cluster>() as u64);
+        let fits = slots_size.is_some() && slots_size == file_size.checked_sub(FILE_HEADER_SIZE);
+        if !cluster_count.is_power_of_two() || !fits {
+            return Err(invalid("truncated or corrupted"));
+        }
+
+        let mut generation = [0; 1];
+        reader.read_exact(&mut generation)?;
+
+        let clusters = (0..cluster_count)
+            .map(|_| {
+                let cluster = Cluster::default();
+                for slot in &cluster.slots {
+                    slot.key_move
+                        .store(read_u64(&mut reader)?, Ordering::Relaxed);
+                    slot.data.store(read_u64(&mut reader)?, Ordering::Relaxed);
+                }
+                Ok(cluster)
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        *self = Self {
+            mask: clusters.len() - 1,
+            clusters,
+            generation: generation[0] & GENERATION_MASK,
+        };
+        Ok(())
+    }
+
+    /// Starts a new generation, entries written from now on belong to the next search.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1) & GENERATION_MASK;
+    }
+
+    /// How full the table is, in permille, estimated like UCI engines usually do: by sampling
+    /// the first thousand clusters and counting the entries written by the current search.
+    pub fn hashfull(&self) -> u16 {
+        const SAMPLE_SIZE: usize = 1000;
+
+        let sample = &self.clusters[..SAMPLE_SIZE.min(self.clusters.len())];
+        let used = sample
+            .iter()
+            .flat_map(|cluster| &cluster.slots)
+            .map(SharedSlot::load)
+            .filter(|slot| !slot.is_empty() && slot.generation() == self.generation)
+            .count();
+
+        (used * 1000 / (sample.len() * CLUSTER_SIZE)) as u16
+    }
+
+    /// The entry stored for the position, whatever its depth and bound. Mate scores are left
+    /// relative to the position the entry was written at.
+    pub fn probe(&self, key: ZobristKey) -> Option<TTEntry> {
+        self.clusters[self.index(key)]
+            .slots
+            .iter()
+            .map(SharedSlot::load)
+            .find(|slot| !slot.is_empty() && slot.key == slot_key(key))
+            .map(|slot| slot.unpack(key))
     }
 
     pub fn get(
@@ -61,60 +377,429 @@ impl TranspositionTable {
         ply: usize,
         best_move: &mut Move,
     ) -> Option<i32> {
-        let entry = self.entries[self.index(key)];
-
-        if entry.key != key {
-            return None;
-        }
+        let entry = self.probe(key)?;
 
-        if entry.depth < depth {
-            return None;
-        }
-
-        let mut score = entry.score;
-        if score < -MATE_LOWER_BOUND {
-            score += ply as i32
-        }
-
-        if score > MATE_LOWER_BOUND {
-            score -= ply as i32;
-        }
-        match entry.flag {
-            TTFlag::Exact => Some(score),
-            TTFlag::Alpha if score <= alpha => Some(alpha),
-            TTFlag::Beta if score >= beta => Some(beta),
-            _ => {
-                *best_move = entry.best_move;
-                None
-            }
-        }
+        // the best move of a shallower search is still the best guess to try first
+        *best_move = entry.best_move;
+        entry.usable_score(alpha, beta, depth, ply)
     }
 
     pub fn set(
-        &mut self,
+        &self,
         best_move: Move,
         key: ZobristKey,
-        mut score: i32,
+        score: i32,
         flag: TTFlag,
         depth: u8,
         ply: usize,
     ) {
-        let index = self.index(key);
+        self.store(
+            TTEntry {
+                key,
+                score,
+                depth,
+                flag,
+                best_move,
+                ..Default::default()
+            },
+            ply,
+        );
+    }
 
-        if score < -MATE_LOWER_BOUND {
-            score -= ply as i32
+    /// Writes the entry of a node `ply` plies from the root, over the entry of the same position
+    /// unless it is a more valuable one of the current search, or else over the least valuable
+    /// entry of the cluster. The generation of the entry is set by the table.
+    pub fn store(&self, mut entry: TTEntry, ply: usize) {
+        let TTEntry {
+            key, depth, flag, ..
+        } = entry;
+        let generation = self.generation;
+        let cluster = &self.clusters[self.index(key)];
+        let slots = cluster.slots.each_ref().map(SharedSlot::load);
+
+        let same_position = slots
+            .iter()
+            .position(|slot| !slot.is_empty() && slot.key == slot_key(key));
+        let victim = match same_position {
+            Some(victim) => {
+                let existing = slots[victim].unpack(key);
+
+                // Quiescence searches and shallow nodes write far more entries than the rest of
+                // the search, they must not push out the deeper results of the current search
+                let is_current = existing.generation == generation;
+                if is_current && priority(depth, flag) < priority(existing.depth, existing.flag) {
+                    return;
+                }
+
+                // a bound found without a best move or evaluation keeps the ones of the position
+                if entry.best_move == Move::default() {
+                    entry.best_move = existing.best_move;
+                }
+                entry.static_eval = entry.static_eval.or(existing.static_eval);
+                victim
+            }
+            None => (0..CLUSTER_SIZE)
+                .min_by_key(|&idx| value(&slots[idx], generation))
+                .unwrap_or_default(),
+        };
+
+        if entry.score < -MATE_LOWER_BOUND {
+            entry.score -= ply as i32
         }
 
-        if score > MATE_LOWER_BOUND {
-            score += ply as i32;
+        if entry.score > MATE_LOWER_BOUND {
+            entry.score += ply as i32;
         }
 
-        self.entries[index] = TTEntry {
+        entry.generation = generation;
+        cluster.slots[victim].store(Slot::pack(&entry));
+    }
+}
+
+/// How valuable an entry is to keep: deeper results took longer to compute, and exact scores are
+/// usable with any window, so they outrank bounds of the same depth.
+fn priority(depth: u8, flag: TTFlag) -> u16 {
+    depth as u16 * 2 + (flag == TTFlag::Exact) as u16
+}
+
+/// How valuable a slot is to keep when a new position needs room in its cluster. Entries of
+/// earlier searches lose value with every search since, as the game moved away from them, and
+/// empty slots are always used first.
+fn value(slot: &Slot, generation: u8) -> i32 {
+    if slot.is_empty() {
+        return i32::MIN;
+    }
+
+    let age = generation.wrapping_sub(slot.generation()) & GENERATION_MASK;
+    priority(slot.depth, slot.flag()) as i32 - AGE_PENALTY * age as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_count_is_a_power_of_two() {
+        assert_eq!(std::mem::size_of::<SharedSlot>(), 16);
+        assert_eq!(std::mem::size_of::<Cluster>(), 64);
+
+        for size in [0, 1, 3, 64] {
+            let table = TranspositionTable::new(size);
+
+            assert!(table.clusters.len().is_power_of_two());
+            assert!(table.clusters.len() * std::mem::size_of::<Cluster>() <= ONE_MB * size.max(1));
+            assert_eq!(table.mask, table.clusters.len() - 1);
+        }
+
+        let table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        assert_eq!(
+            table.index(key),
+            key.inner() as usize % table.clusters.len()
+        );
+
+        table.set(Move::default(), key, 25, TTFlag::Exact, 3, 0);
+        let mut best_move = Move::default();
+        assert_eq!(table.get(key, -100, 100, 3, 0, &mut best_move), Some(25));
+    }
+
+    #[test]
+    fn test_resize_discards_every_entry() {
+        let mut table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        table.new_search();
+        table.set(Move::default(), key, 25, TTFlag::Exact, 3, 0);
+        assert_eq!(table.size(), 1);
+
+        table.resize(4);
+        assert_eq!(table.size(), 4);
+        assert_eq!(
+            table.clusters.len() * std::mem::size_of::<Cluster>(),
+            ONE_MB * 4
+        );
+        assert_eq!(table.probe(key), None);
+        assert_eq!(table.generation, 1);
+    }
+
+    #[test]
+    fn test_saved_tables_load_back() {
+        let path = std::env::temp_dir().join(format!("milky-tt-{}", std::process::id()));
+        let mut table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        let hash_move = Move::castle_kingside(milky_bitboard::Side::White);
+        table.new_search();
+        table.set(hash_move, key, 25, TTFlag::Alpha, 3, 0);
+        table.save(&path, 7).unwrap();
+
+        let mut loaded = TranspositionTable::new(0);
+        loaded.load(&path, 7).unwrap();
+        assert_eq!(loaded.clusters.len(), table.clusters.len());
+        assert_eq!(loaded.generation, table.generation);
+        assert_eq!(loaded.probe(key), table.probe(key));
+
+        // tables of other zobrist keys, versions or sizes are rejected
+        let mut other = TranspositionTable::new(0);
+        assert!(other.load(&path, 8).is_err());
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8] += 1;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(other.load(&path, 7).is_err());
+
+        bytes[8] -= 1;
+        bytes.truncate(bytes.len() - 16);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(other.load(&path, 7).is_err());
+
+        // a header cut right before the generation, the only field left unread
+        bytes.truncate(FILE_HEADER_SIZE as usize - 1);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(other.load(&path, 7).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(other.probe(key), None);
+    }
+
+    #[test]
+    fn test_hashfull_counts_current_generation() {
+        let mut table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        assert_eq!(table.hashfull(), 0);
+
+        table.new_search();
+        let slot = Slot::pack(&TTEntry {
+            key,
+            generation: table.generation,
+            ..Default::default()
+        });
+        for cluster in &table.clusters {
+            cluster.slots[1].store(slot);
+            cluster.slots[3].store(slot);
+        }
+        assert_eq!(table.hashfull(), 500);
+
+        // only the first clusters are sampled
+        for cluster in &table.clusters[1000..] {
+            cluster.slots[0].store(slot);
+        }
+        assert_eq!(table.hashfull(), 500);
+
+        // entries from previous searches don't count
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_quiescence_entries_keep_deeper_entries() {
+        let mut table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        let mut best_move = Move::default();
+
+        table.set(Move::default(), key, 25, TTFlag::Exact, 3, 0);
+        table.set(Move::default(), key, 40, TTFlag::Exact, 0, 0);
+        assert_eq!(table.get(key, -100, 100, 0, 0, &mut best_move), Some(25));
+
+        // deeper entries of a previous search can be replaced
+        table.new_search();
+        table.set(Move::default(), key, 40, TTFlag::Exact, 0, 0);
+        assert_eq!(table.get(key, -100, 100, 0, 0, &mut best_move), Some(40));
+    }
+
+    #[test]
+    fn test_shallow_entries_still_give_their_best_move() {
+        let table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        let hash_move = Move::castle_kingside(milky_bitboard::Side::White);
+
+        table.set(hash_move, key, 25, TTFlag::Exact, 3, 0);
+        let mut best_move = Move::default();
+        assert_eq!(table.get(key, -100, 100, 5, 0, &mut best_move), None);
+        assert_eq!(best_move, hash_move);
+    }
+
+    #[test]
+    fn test_entries_keep_the_static_eval_of_the_position() {
+        let table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+
+        let entry = TTEntry {
             key,
-            depth,
-            score,
-            flag,
-            best_move,
+            score: 25,
+            depth: 3,
+            flag: TTFlag::Exact,
+            static_eval: Some(-40),
+            ..Default::default()
         };
+        table.store(entry, 0);
+        assert_eq!(table.probe(key).unwrap().static_eval, Some(-40));
+
+        // a deeper result of the same position without an evaluation keeps the stored one
+        table.set(Move::default(), key, 30, TTFlag::Exact, 5, 0);
+        let entry = table.probe(key).unwrap();
+        assert_eq!((entry.score, entry.static_eval), (30, Some(-40)));
+    }
+
+    #[test]
+    fn test_shallow_bounds_keep_deeper_or_exact_entries() {
+        let table = TranspositionTable::new(1);
+        let key = crate::zobrist::Zobrist::new().side_key;
+        let hash_move = Move::castle_kingside(milky_bitboard::Side::White);
+        let mut best_move = Move::default();
+
+        table.set(hash_move, key, 25, TTFlag::Exact, 4, 0);
+        table.set(Move::default(), key, 90, TTFlag::Beta, 4, 0);
+        table.set(Move::default(), key, -90, TTFlag::Alpha, 2, 0);
+        assert_eq!(table.get(key, -100, 100, 4, 0, &mut best_move), Some(25));
+
+        // a deeper bound is worth more than a shallower exact score, and keeps the hash move
+        table.set(Move::default(), key, 90, TTFlag::Beta, 5, 0);
+        assert_eq!(table.get(key, -100, 80, 5, 0, &mut best_move), Some(80));
+        assert_eq!(best_move, hash_move);
+    }
+
+    #[test]
+    fn test_positions_sharing_a_cluster_replace_the_least_valuable() {
+        let mut table = TranspositionTable::new(1);
+        let keys = (1..=CLUSTER_SIZE as u64 + 1)
+            .map(|idx| ZobristKey::from((idx << 32) | 5))
+            .collect::<Vec<_>>();
+        let mut best_move = Move::default();
+
+        // every position of a full cluster is still found
+        for (idx, key) in keys[..CLUSTER_SIZE].iter().enumerate() {
+            table.set(
+                Move::default(),
+                *key,
+                idx as i32,
+                TTFlag::Exact,
+                4 + idx as u8,
+                0,
+            );
+        }
+        for (idx, key) in keys[..CLUSTER_SIZE].iter().enumerate() {
+            assert_eq!(table.probe(*key).unwrap().score, idx as i32);
+        }
+
+        // the shallowest entry makes room for the next position
+        table.set(Move::default(), keys[CLUSTER_SIZE], 9, TTFlag::Beta, 1, 0);
+        assert_eq!(table.probe(keys[0]), None);
+        assert_eq!(
+            table.get(keys[CLUSTER_SIZE], -100, 5, 1, 0, &mut best_move),
+            Some(5)
+        );
+
+        // entries of searches long gone are worth less than the shallow ones of the current search
+        for _ in 0..2 {
+            table.new_search();
+        }
+        table.set(Move::default(), keys[CLUSTER_SIZE], 9, TTFlag::Beta, 1, 0);
+        table.set(Move::default(), keys[0], 3, TTFlag::Exact, 2, 0);
+        assert_eq!(table.probe(keys[CLUSTER_SIZE]).unwrap().score, 9);
+        assert_eq!(table.probe(keys[0]).unwrap().score, 3);
+        assert_eq!(table.probe(keys[1]), None);
+    }
+
+    #[test]
+    fn test_torn_slots_are_not_found() {
+        let table = TranspositionTable::new(1);
+        let key = ZobristKey::from(0x1234_5678_0000_0005);
+        let other_key = ZobristKey::from(0x8765_4321_0000_0005);
+        table.set(Move::default(), key, 25, TTFlag::Exact, 3, 0);
+
+        // the data of another position written over the slot, but not its key
+        let other = Slot::pack(&TTEntry {
+            key: other_key,
+            score: -300,
+            depth: 9,
+            flag: TTFlag::Beta,
+            ..Default::default()
+        });
+        let slot = &table.clusters[table.index(key)].slots[0];
+        let torn = SharedSlot::default();
+        torn.store(other);
+        slot.data.store(torn.data.into_inner(), Ordering::Relaxed);
+
+        assert_eq!(table.probe(key), None);
+        assert_eq!(table.probe(other_key), None);
+    }
+
+    #[test]
+    fn test_threads_share_the_table_without_locking() {
+        let table = TranspositionTable::new(1);
+        let score_of = |key: u64| (key % 1000) as i32;
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let table = &table;
+                scope.spawn(move || {
+                    let mut random = crate::random::Random::new();
+                    for idx in 0..100_000 {
+                        // the same positions on every thread, all of them in a handful of clusters
+                        let key = (random.gen_u64() << 8) | (idx % 16);
+                        let depth = (thread + idx) as u8 % 8;
+                        table.set(
+                            Move::default(),
+                            key.into(),
+                            score_of(key),
+                            TTFlag::Exact,
+                            depth,
+                            0,
+                        );
+
+                        if let Some(entry) = table.probe(key.into()) {
+                            assert_eq!(entry.score, score_of(key));
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_deep_entries_survive_quiescence_traffic() {
+        let mut table = TranspositionTable::new(1);
+        let mut random = crate::random::Random::new();
+        table.new_search();
+
+        // every deep entry gets a slot of its own
+        let pv_keys = (0..64)
+            .map(|idx| ZobristKey::from((random.gen_u64() << 32) | (idx * 97)))
+            .collect::<Vec<_>>();
+        for (idx, key) in pv_keys.iter().enumerate() {
+            table.set(Move::default(), *key, idx as i32, TTFlag::Exact, 8, 0);
+        }
+
+        // far more shallow writes than the table has entries, many of them landing on the slots
+        // of the deep entries
+        for idx in 0..table.clusters.len() * CLUSTER_SIZE * 4 {
+            let key = match idx % 2 {
+                0 => random.gen_u64(),
+                _ => pv_keys[idx % pv_keys.len()].inner() ^ (random.gen_u64() << 32),
+            };
+            let flag = [TTFlag::Alpha, TTFlag::Beta, TTFlag::Exact][idx % 3];
+            table.set(
+                Move::default(),
+                ZobristKey::from(key),
+                0,
+                flag,
+                (idx % 3) as u8,
+                0,
+            );
+        }
+
+        let mut best_move = Move::default();
+        for (idx, key) in pv_keys.iter().enumerate() {
+            assert_eq!(
+                table.get(*key, -100, 100, 8, 0, &mut best_move),
+                Some(idx as i32)
+            );
+        }
+
+        // the next search is free to reuse the slots
+        table.new_search();
+        table.set(Move::default(), pv_keys[0], 7, TTFlag::Beta, 0, 0);
+        assert_eq!(
+            table.get(pv_keys[0], -100, 5, 0, 0, &mut best_move),
+            Some(5)
+        );
     }
 }
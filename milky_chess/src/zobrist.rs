@@ -9,6 +9,12 @@ impl ZobristKey {
     }
 }
 
+impl From<u64> for ZobristKey {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
 impl IntoU64 for ZobristKey {
     fn into(self) -> u64 {
         self.0
@@ -40,7 +46,7 @@ pub struct GamePosition {
     pub castling_rights: CastlingRights,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Zobrist {
     pub pieces_table: [[ZobristKey; 64]; 12],
     pub en_passant: [ZobristKey; 64],
@@ -90,6 +96,31 @@ impl Zobrist {
         self.side_key = ZobristKey(rng.gen_u64());
     }
 
+    /// Digest of every key, telling apart data hashed with keys generated some other way, which
+    /// would have to be thrown away.
+    pub fn fingerprint(&self) -> u64 {
+        self.pieces_table
+            .iter()
+            .flatten()
+            .chain(&self.en_passant)
+            .chain(&self.castling_rights)
+            .chain([&self.side_key])
+            .fold(0, |digest, key| digest.rotate_left(7) ^ key.inner())
+    }
+
+    /// Key of the pawns on `boards` alone, see [`crate::board::BoardState::pawn_key`].
+    pub fn hash_pawns(&self, boards: &[BitBoard; 12]) -> ZobristKey {
+        let mut key = ZobristKey(0);
+
+        for pawn in [Pieces::WhitePawn, Pieces::BlackPawn] {
+            for square in boards[pawn] {
+                key ^= self.pieces_table[pawn][square];
+            }
+        }
+
+        key
+    }
+
     pub fn hash_position(&self, position: GamePosition) -> ZobristKey {
         let mut key = ZobristKey(0);
 
@@ -0,0 +1,32 @@
+//! Embeds the commit the engine is built from, so builds sharing a version can be told apart.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+    println!("cargo:rerun-if-env-changed=MILKY_COMMIT");
+
+    // packagers building outside of a git checkout can provide the commit themselves
+    let commit = std::env::var("MILKY_COMMIT").ok().or_else(git_commit);
+    println!(
+        "cargo:rustc-env=MILKY_COMMIT={}",
+        commit.as_deref().unwrap_or("unknown")
+    );
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=10", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .is_ok_and(|output| !output.stdout.is_empty());
+
+    Some(if dirty { format!("{commit}-dirty") } else { commit })
+}
@@ -0,0 +1,54 @@
+//! `milky bench` searches a fixed set of positions to a fixed depth and prints the total amount of
+//! nodes searched, which acts as a signature of the search.
+//!
+//! ```text
+//! milky bench
+//! ```
+//!
+//! The search is deterministic on a single thread, so two builds reporting different signatures
+//! search differently, which lets testers verify they are running the build they expect.
+
+use std::time::Instant;
+
+use milky_chess::Milky;
+use milky_chess::time_manager::TimeControl;
+
+/// Depth every bench position is searched to.
+const BENCH_DEPTH: u8 = 6;
+
+/// Positions covering an opening, tactical middlegames and endgames.
+const POSITIONS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+/// Total amount of nodes searched over every bench position, each searched on a fresh engine.
+pub fn signature() -> u64 {
+    POSITIONS
+        .iter()
+        .map(|fen| {
+            let mut milky = Milky::new();
+            milky.load_position(milky_fen::parse_fen_string(fen).expect("bench fens are valid"));
+            milky.think(TimeControl::FixedDepth(BENCH_DEPTH));
+            milky.search_state().nodes
+        })
+        .sum()
+}
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let nodes = signature();
+    let elapsed = start.elapsed();
+
+    println!("nodes {nodes}");
+    println!("time {}ms", elapsed.as_millis());
+    println!(
+        "nps {}",
+        (nodes as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+    );
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use milky_uci::command::{IdCommand, InfoCommand, UciCommand};
+
+/// Name sent in `id name`, carrying the version and commit so tournament operators can tell
+/// builds apart.
+pub const ENGINE_NAME: &str = concat!(
+    "milky ",
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("MILKY_COMMIT"),
+    ")"
+);
+
+pub fn id() -> UciCommand {
+    UciCommand::Id(IdCommand {
+        name: ENGINE_NAME,
+        ..Default::default()
+    })
+}
+
+/// Collects the information describing this build of the engine, sent as `info string` lines
+/// after `uci` so users can include it when reporting performance issues.
+pub fn build_info() -> Vec<UciCommand> {
+    let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+
+    let lines = [
+        format!(
+            "milky {} commit {} ({profile} build)",
+            env!("CARGO_PKG_VERSION"),
+            env!("MILKY_COMMIT")
+        ),
+        format!("compiled with {}", compiled_features()),
+        format!("cpu supports {}", detected_features()),
+        format!(
+            "defaults threads {} hash {}",
+            milky_chess::DEFAULT_THREADS,
+            milky_chess::DEFAULT_HASH_SIZE
+        ),
+    ];
+
+    lines
+        .into_iter()
+        .map(|line| {
+            UciCommand::Info(InfoCommand {
+                string: Some(line),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Search signature of this build, see [`crate::bench`]. Only sent when debugging as it searches
+/// a few positions.
+pub fn bench_info() -> UciCommand {
+    UciCommand::Info(InfoCommand {
+        string: Some(format!("bench signature {}", crate::bench::signature())),
+        ..Default::default()
+    })
+}
+
+/// Target features the binary was compiled with, which determine the code paths being used.
+fn compiled_features() -> String {
+    let features = [
+        ("popcnt", cfg!(target_feature = "popcnt")),
+        ("bmi2", cfg!(target_feature = "bmi2")),
+    ];
+
+    format_features(&features)
+}
+
+/// Features available on the CPU running the engine, if they are not enabled at compile time
+/// the engine is leaving performance on the table.
+#[cfg(target_arch = "x86_64")]
+fn detected_features() -> String {
+    let features = [
+        ("popcnt", std::arch::is_x86_feature_detected!("popcnt")),
+        ("bmi2", std::arch::is_x86_feature_detected!("bmi2")),
+    ];
+
+    format_features(&features)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn detected_features() -> String {
+    String::from("unknown")
+}
+
+fn format_features(features: &[(&str, bool)]) -> String {
+    let enabled = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+    match enabled.is_empty() {
+        true => String::from("none"),
+        false => enabled.join(" "),
+    }
+}
@@ -0,0 +1,356 @@
+//! `milky compare` runs milky and an external UCI engine on the same position with the same time
+//! budget, and prints both results side by side, making it easy to spot where their opinions
+//! diverge.
+//!
+//! ```text
+//! milky compare --engine /usr/bin/stockfish --movetime 2000 <fen>
+//! ```
+//!
+//! The external engine can also be configured through the `MILKY_COMPARE_ENGINE` environment
+//! variable.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use milky_uci::command::InfoCommand;
+
+/// Scores further apart than this (in centipawns) are highlighted as a divergence.
+const SCORE_DIVERGENCE: u32 = 50;
+
+const DEFAULT_MOVE_TIME: u64 = 1000;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CompareArgs {
+    engine: String,
+    move_time: u64,
+    fen: String,
+}
+
+impl CompareArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut engine = std::env::var("MILKY_COMPARE_ENGINE").ok();
+        let mut move_time = DEFAULT_MOVE_TIME;
+        let mut fen = vec![];
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--engine" => engine = args.next(),
+                "--movetime" => {
+                    let value = args.next().ok_or("expected a number after `--movetime`")?;
+                    move_time = value.parse()?;
+                }
+                _ => fen.push(arg),
+            }
+        }
+
+        let Some(engine) = engine else {
+            return Err("no engine to compare against, use `--engine <path>`".into());
+        };
+
+        if fen.is_empty() {
+            return Err("usage: milky compare [--engine <path>] [--movetime <ms>] <fen>".into());
+        }
+
+        Ok(Self {
+            engine,
+            move_time,
+            fen: fen.join(" "),
+        })
+    }
+}
+
+/// What an engine reported at the end of its search.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct SearchReport {
+    name: String,
+    depth: Option<u32>,
+    score: Option<Score>,
+    pv: Vec<String>,
+    best_move: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Centipawns(score) => write!(f, "cp {score}"),
+            Score::Mate(moves) => write!(f, "mate {moves}"),
+        }
+    }
+}
+
+impl SearchReport {
+    /// Keeps the depth, score and pv of an `info` line, lines without a pv (like `info string`)
+    /// are ignored so the report holds the last completed iteration.
+    fn update(&mut self, line: &str) {
+        // free form text could contain any of the keywords below
+        let (fields, _) = InfoCommand::split_string(line);
+        let tokens = fields.split_whitespace().collect::<Vec<_>>();
+        if tokens.first() != Some(&"info") || !tokens.contains(&"pv") {
+            return;
+        }
+
+        let value_after = |keyword: &str| {
+            tokens
+                .iter()
+                .position(|&token| token == keyword)
+                .and_then(|idx| tokens.get(idx + 1))
+        };
+
+        self.depth = value_after("depth").and_then(|depth| depth.parse().ok());
+        self.score = match (value_after("cp"), value_after("mate")) {
+            (Some(score), _) => score.parse().ok().map(Score::Centipawns),
+            (_, Some(moves)) => moves.parse().ok().map(Score::Mate),
+            _ => None,
+        };
+
+        let pv_start = tokens.iter().position(|&token| token == "pv").unwrap() + 1;
+        self.pv = tokens[pv_start..].iter().map(|mv| mv.to_string()).collect();
+    }
+}
+
+struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciEngine {
+    fn spawn(path: &str) -> Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("failed to start `{path}`: {error}"))?;
+
+        let stdin = child.stdin.take().ok_or("engine stdin is unavailable")?;
+        let stdout = child.stdout.take().ok_or("engine stdout is unavailable")?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    fn send(&mut self, command: &str) -> Result<()> {
+        writeln!(self.stdin, "{command}")?;
+        Ok(self.stdin.flush()?)
+    }
+
+    /// Reads lines until one starts with `prefix`, handing every line read to `on_line`.
+    fn read_until(&mut self, prefix: &str, mut on_line: impl FnMut(&str)) -> Result<String> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(format!("engine exited before sending `{prefix}`").into());
+            }
+
+            let line = line.trim();
+            on_line(line);
+
+            if line.starts_with(prefix) {
+                return Ok(line.to_string());
+            }
+        }
+    }
+
+    fn search(&mut self, fen: &str, move_time: u64) -> Result<SearchReport> {
+        let mut report = SearchReport::default();
+
+        self.send("uci")?;
+        self.read_until("uciok", |line| {
+            if let Some(name) = line.strip_prefix("id name ") {
+                report.name = name.to_string();
+            }
+        })?;
+
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.read_until("readyok", |_| {})?;
+
+        self.send(&format!("position fen {fen}"))?;
+        self.send(&format!("go movetime {move_time}"))?;
+        let best_move = self.read_until("bestmove", |line| report.update(line))?;
+
+        report.best_move = best_move
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(report)
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        _ = self.send("quit");
+        _ = self.child.wait();
+    }
+}
+
+/// Describes how two reports disagree, an empty list means the engines agree.
+fn divergences(ours: &SearchReport, theirs: &SearchReport) -> Vec<String> {
+    let mut divergences = vec![];
+
+    if ours.best_move != theirs.best_move {
+        divergences.push(format!(
+            "best move differs: {} vs {}",
+            ours.best_move, theirs.best_move
+        ));
+    }
+
+    match (ours.score, theirs.score) {
+        (Some(Score::Centipawns(ours)), Some(Score::Centipawns(theirs)))
+            if ours.abs_diff(theirs) > SCORE_DIVERGENCE =>
+        {
+            divergences.push(format!(
+                "scores are {} centipawns apart",
+                ours.abs_diff(theirs)
+            ));
+        }
+        (Some(ours), Some(theirs)) if ours != theirs && !matches!(ours, Score::Centipawns(_)) => {
+            divergences.push(format!("score differs: {ours} vs {theirs}"));
+        }
+        (Some(Score::Centipawns(_)), Some(theirs @ Score::Mate(_))) => {
+            divergences.push(format!("only the other engine sees {theirs}"));
+        }
+        _ => {}
+    }
+
+    let shared = ours
+        .pv
+        .iter()
+        .zip(&theirs.pv)
+        .take_while(|(ours, theirs)| ours == theirs)
+        .count();
+
+    if shared < ours.pv.len().min(theirs.pv.len()) && shared > 0 {
+        divergences.push(format!("principal variations split at ply {}", shared + 1));
+    }
+
+    divergences
+}
+
+fn print_reports(ours: &SearchReport, theirs: &SearchReport) {
+    let width = 12 + ours.name.len().max(8);
+    let display = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+
+    println!("{:<10}{:<width$}{}", "", ours.name, theirs.name);
+    println!(
+        "{:<10}{:<width$}{}",
+        "depth",
+        display(ours.depth.map(|depth| depth.to_string())),
+        display(theirs.depth.map(|depth| depth.to_string())),
+    );
+    println!(
+        "{:<10}{:<width$}{}",
+        "score",
+        display(ours.score.map(|score| score.to_string())),
+        display(theirs.score.map(|score| score.to_string())),
+    );
+    println!(
+        "{:<10}{:<width$}{}",
+        "bestmove", ours.best_move, theirs.best_move
+    );
+
+    let plies = ours.pv.len().max(theirs.pv.len());
+    for ply in 0..plies {
+        let label = if ply == 0 { "pv" } else { "" };
+        let ours = ours.pv.get(ply).map(String::as_str).unwrap_or_default();
+        let theirs = theirs.pv.get(ply).map(String::as_str).unwrap_or_default();
+        let marker = if ours != theirs { "  *" } else { "" };
+
+        println!("{label:<10}{ours:<width$}{theirs}{marker}");
+    }
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let args = CompareArgs::parse(args)?;
+    milky_fen::parse_fen_string(&args.fen)?;
+
+    // Both engines are driven through UCI, and one after the other, so they get the exact same
+    // treatment and don't compete for the CPU.
+    let milky_path = std::env::current_exe()?;
+    let ours =
+        UciEngine::spawn(&milky_path.to_string_lossy())?.search(&args.fen, args.move_time)?;
+    let theirs = UciEngine::spawn(&args.engine)?.search(&args.fen, args.move_time)?;
+
+    print_reports(&ours, &theirs);
+    println!();
+
+    let divergences = divergences(&ours, &theirs);
+    match divergences.is_empty() {
+        true => println!("engines agree"),
+        false => divergences
+            .iter()
+            .for_each(|divergence| println!("* {divergence}")),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(lines: &[&str], best_move: &str) -> SearchReport {
+        let mut report = SearchReport::default();
+        lines.iter().for_each(|line| report.update(line));
+        report.best_move = best_move.to_string();
+        report
+    }
+
+    #[test]
+    fn test_report_keeps_last_iteration() {
+        let report = report(
+            &[
+                "info score cp 20 depth 1 nodes 20 pv e2e4",
+                "info depth 2 seldepth 4 score cp 35 nodes 400 pv e2e4 e7e5",
+                "info string some debugging output",
+                "info depth 9 string depth 12 score cp 90 pv d2d4",
+            ],
+            "e2e4",
+        );
+
+        assert_eq!(report.depth, Some(2));
+        assert_eq!(report.score, Some(Score::Centipawns(35)));
+        assert_eq!(report.pv, ["e2e4", "e7e5"]);
+    }
+
+    #[test]
+    fn test_divergences() {
+        let ours = report(&["info depth 8 score cp 10 pv e2e4 e7e5 g1f3"], "e2e4");
+        let agreeing = report(&["info depth 20 score cp 40 pv e2e4 e7e5 g1f3"], "e2e4");
+        assert!(divergences(&ours, &agreeing).is_empty());
+
+        let theirs = report(&["info depth 20 score mate 3 pv d2d4 d7d5"], "d2d4");
+        assert_eq!(
+            divergences(&ours, &theirs),
+            [
+                "best move differs: e2e4 vs d2d4",
+                "only the other engine sees mate 3",
+            ]
+        );
+
+        let theirs = report(&["info depth 20 score cp 200 pv e2e4 c7c5"], "e2e4");
+        assert_eq!(
+            divergences(&ours, &theirs),
+            [
+                "scores are 190 centipawns apart",
+                "principal variations split at ply 2",
+            ]
+        );
+    }
+}
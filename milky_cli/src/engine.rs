@@ -0,0 +1,268 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use milky_bitboard::Move;
+use milky_chess::Milky;
+use milky_chess::observer::{IterationInfo, ProgressInfo, RootMoveInfo, SearchObserver};
+use milky_chess::time_manager::StopHandle;
+use milky_uci::command::{BestMoveCommand, GoCommand, InfoCommand};
+
+/// Shallowest iteration whose root moves are reported with `currmove`, the earlier ones are over
+/// too quickly for a GUI to show them.
+const CURRMOVE_DEPTH: u8 = 8;
+
+/// Command waiting for the running search to finish before it can use the engine.
+type PendingCommand = Box<dyn FnOnce(&mut Milky) + Send>;
+
+/// Reports the progress of the searches to the GUI as `info` lines.
+struct UciReporter {
+    /// Set through UCI `debug on`. The engine always sends its diagnostics and they are dropped
+    /// here, as `debug` can arrive while the engine, which owns its options, is searching.
+    debug: Arc<AtomicBool>,
+}
+
+impl SearchObserver for UciReporter {
+    fn on_iteration_complete(&mut self, info: &IterationInfo) {
+        let info = InfoCommand {
+            depth: Some(info.depth),
+            selective_depth: Some(info.seldepth),
+            time: Some(info.elapsed.as_millis() as u64),
+            nodes: Some(info.nodes),
+            pv: Some(info.pv.clone()),
+            score: Some(info.score.into()),
+            hashfull: Some(info.hashfull),
+            nodes_per_second: Some(u32::try_from(info.nodes_per_second()).unwrap_or(u32::MAX)),
+            ..Default::default()
+        };
+        println!("{info}");
+    }
+
+    fn on_progress(&mut self, info: &ProgressInfo) {
+        let info = InfoCommand {
+            time: Some(info.elapsed.as_millis() as u64),
+            nodes: Some(info.nodes),
+            hashfull: Some(info.hashfull),
+            nodes_per_second: Some(u32::try_from(info.nodes_per_second()).unwrap_or(u32::MAX)),
+            ..Default::default()
+        };
+        println!("{info}");
+    }
+
+    /// Reports the root move being searched, so GUIs can show how far a long iteration got.
+    fn on_root_move(&mut self, info: &RootMoveInfo) {
+        if info.depth < CURRMOVE_DEPTH {
+            return;
+        }
+
+        let info = InfoCommand {
+            depth: Some(info.depth),
+            current_move: Some(info.piece_move),
+            current_move_number: Some(info.move_number),
+            ..Default::default()
+        };
+        println!("{info}");
+    }
+
+    fn on_debug(&mut self, text: &str) {
+        if self.debug.load(Ordering::Relaxed) {
+            milky_uci::info_string!("{text}");
+        }
+    }
+}
+
+/// Owns the engine between searches and hands it to a dedicated thread while searching, so the
+/// UCI loop keeps reading commands like `stop` and `isready` while the engine thinks.
+pub struct Engine {
+    milky: Option<Milky>,
+    search: Option<JoinHandle<Milky>>,
+    stop_handle: StopHandle,
+    /// Keeps the running search from printing its best move, set when a ponder search is
+    /// abandoned.
+    discard: Arc<AtomicBool>,
+    /// Set while an infinite search runs, UCI only lets it print its best move after `stop`,
+    /// even when it runs out of depth before that.
+    infinite: Arc<AtomicBool>,
+    debug: Arc<AtomicBool>,
+    /// Commands sent while searching, run in order by the search thread once its best move is
+    /// printed. `None` once the search thread stopped taking them.
+    pending: Arc<Mutex<Option<Vec<PendingCommand>>>>,
+}
+
+impl Engine {
+    pub fn new(mut milky: Milky) -> Self {
+        let debug = Arc::<AtomicBool>::default();
+        milky.options_mut().debug = true;
+        milky.set_observer(UciReporter {
+            debug: debug.clone(),
+        });
+
+        Self {
+            stop_handle: milky.stop_handle(),
+            milky: Some(milky),
+            search: None,
+            discard: Arc::default(),
+            infinite: Arc::default(),
+            debug,
+            pending: Arc::default(),
+        }
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug.load(Ordering::Relaxed)
+    }
+
+    /// Turns the diagnostics on or off, right away even while searching.
+    pub fn set_debug(&self, debug: bool) {
+        self.debug.store(debug, Ordering::Relaxed);
+    }
+
+    /// Runs `command` on the engine, or once the running search finishes, without waiting for
+    /// it, so the UCI loop can still read the `stop` that ends an infinite search.
+    pub fn run(&mut self, command: impl FnOnce(&mut Milky) + Send + 'static) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.push(Box::new(command));
+            return;
+        }
+
+        command(self.milky());
+    }
+
+    /// The engine, waiting for the running search to finish if there is one.
+    pub fn milky(&mut self) -> &mut Milky {
+        if let Some(search) = self.search.take() {
+            self.milky = Some(search.join().expect("search thread panicked"));
+        }
+
+        self.milky
+            .as_mut()
+            .expect("the engine is only moved out while a search is running")
+    }
+
+    /// Starts searching on a separate thread, the best move is printed once the search halts.
+    ///
+    /// A ponder search only prints its best move after `ponderhit` or `stop`, and an infinite
+    /// search after `stop`, even when they finish on their own before that.
+    pub fn go(&mut self, mut go_command: GoCommand) {
+        self.milky();
+        let mut milky = self.milky.take().expect("no search is running");
+
+        // a `stop` sent after the previous search ended must not cut this one short
+        self.stop_handle.reset();
+        if go_command.ponder && milky.options().ponder {
+            self.stop_handle.start_pondering();
+        }
+
+        let stop_handle = self.stop_handle.clone();
+        self.discard = Arc::default();
+        let discard = self.discard.clone();
+        self.infinite = Arc::new(AtomicBool::new(go_command.infinite));
+        let infinite = self.infinite.clone();
+        self.pending = Arc::new(Mutex::new(Some(vec![])));
+        let pending = self.pending.clone();
+
+        self.search = Some(std::thread::spawn(move || {
+            let search_moves = go_command.search_moves.take().unwrap_or_default();
+            let result = milky.think_with_moves(go_command, &search_moves);
+
+            while stop_handle.is_pondering() || infinite.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            if !discard.load(Ordering::Relaxed) {
+                // no move is left when the game is already over
+                let best_move = match result.best_move {
+                    best_move if best_move == Move::default() => String::from("(none)"),
+                    best_move => best_move.to_string(),
+                };
+                // the reply the search expects, so the GUI can ponder on it
+                let best_move = BestMoveCommand {
+                    best_move,
+                    ponder: result.ponder.map(|ponder| ponder.to_string()),
+                };
+                println!("{best_move}");
+            }
+
+            // the queue is only closed once it is empty, so no command sent meanwhile is lost
+            loop {
+                let mut queue = pending.lock().unwrap();
+                let commands = match queue.as_mut() {
+                    Some(commands) if !commands.is_empty() => std::mem::take(commands),
+                    _ => {
+                        *queue = None;
+                        break;
+                    }
+                };
+                drop(queue);
+                commands.into_iter().for_each(|command| command(&mut milky));
+            }
+
+            milky
+        }));
+    }
+
+    /// The opponent played the pondered move, the ponder search becomes a regular search.
+    pub fn ponder_hit(&self) {
+        self.stop_handle.ponder_hit();
+    }
+
+    /// Abandons a running ponder search without printing its best move, used when the GUI sends
+    /// a new position while pondering, meaning the opponent didn't play the expected move.
+    pub fn abort_ponder(&mut self) {
+        if self.search.is_some() && self.stop_handle.is_pondering() {
+            self.discard.store(true, Ordering::Relaxed);
+            self.stop();
+        }
+    }
+
+    /// Stops the running search, if any, and waits for its best move to be printed.
+    pub fn stop(&mut self) {
+        if self.search.is_some() {
+            self.infinite.store(false, Ordering::Relaxed);
+            self.stop_handle.stop();
+            self.milky();
+        }
+    }
+
+    /// The GUI closed the input, so nothing can stop an infinite search anymore, it is stopped
+    /// right away. Other searches still finish and print their best move.
+    pub fn close_input(&mut self) {
+        if self.infinite.load(Ordering::Relaxed) {
+            self.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Engine {
+        fn is_searching(&self) -> bool {
+            self.search
+                .as_ref()
+                .is_some_and(|search| !search.is_finished())
+        }
+    }
+
+    #[test]
+    fn test_infinite_search_waits_for_stop() {
+        milky_chess::init_static_members();
+        let mut engine = Engine::new(Milky::new());
+        let fen = milky_fen::parse_fen_string("7k/8/5K2/8/8/8/8/1Q6 w - - 0 1").unwrap();
+        engine.milky().load_position(fen);
+
+        // a mate in two runs out of depth within milliseconds
+        engine.go(GoCommand {
+            infinite: true,
+            ..Default::default()
+        });
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(engine.is_searching());
+
+        engine.stop();
+        assert!(!engine.is_searching());
+        assert!(engine.search.is_none());
+    }
+}
@@ -0,0 +1,366 @@
+//! `milky gen-data` plays fast self-play games and records the positions along with their search
+//! score and the game result, as training data for evaluation networks.
+//!
+//! ```text
+//! milky gen-data --games 1000 --depth 6 --threads 8 --output data.csv
+//! ```
+//!
+//! Every line of the output is `fen,score,result`, with the score in centipawns and the result
+//! (`1`, `0.5` or `0`) both from white's point of view, which is the text format most NNUE
+//! trainers read. Positions seen before, in any game, are only written once.
+
+use std::collections::HashSet;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use milky_bitboard::geometry::{Board8x8, BoardGeometry};
+use milky_bitboard::{Move, Pieces, Side, Square};
+use milky_chess::observer::{IterationInfo, Score, SearchObserver};
+use milky_chess::time_manager::TimeControl;
+use milky_chess::{BoardState, Milky};
+
+/// Games still going after this many plies are adjudicated as draws.
+const MAX_GAME_PLIES: usize = 400;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GenDataArgs {
+    games: usize,
+    depth: u8,
+    threads: usize,
+    random_plies: usize,
+    seed: u64,
+    output: String,
+}
+
+impl Default for GenDataArgs {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            depth: 6,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            random_plies: 8,
+            seed: 0,
+            output: String::from("data.csv"),
+        }
+    }
+}
+
+impl GenDataArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("expected a value after `{arg}`"))
+            };
+
+            match arg.as_str() {
+                "--games" => parsed.games = value()?.parse()?,
+                "--depth" => parsed.depth = value()?.parse()?,
+                "--threads" => parsed.threads = value()?.parse::<usize>()?.max(1),
+                "--random-plies" => parsed.random_plies = value()?.parse()?,
+                "--seed" => parsed.seed = value()?.parse()?,
+                "--output" => parsed.output = value()?,
+                other => {
+                    return Err(format!(
+                        "unknown argument `{other}`, usage: milky gen-data [--games <n>] \
+                         [--depth <n>] [--threads <n>] [--random-plies <n>] [--seed <n>] \
+                         [--output <path>]"
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Small splitmix64 generator, so every game can be replayed from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+}
+
+/// Keeps the score of the last completed iteration.
+struct LastScore(Arc<Mutex<Option<Score>>>);
+
+impl SearchObserver for LastScore {
+    fn on_iteration_complete(&mut self, info: &IterationInfo) {
+        *self.0.lock().unwrap() = Some(info.score);
+    }
+}
+
+/// A recorded position, with the score from the side to move point of view.
+struct Sample {
+    fen: String,
+    side_to_move: Side,
+    score: i32,
+}
+
+fn position_fen(board: &BoardState, full_moves: usize) -> String {
+    let mut fen = String::new();
+
+    for rank in 0..Board8x8::RANKS {
+        let mut empty = 0;
+
+        for file in 0..Board8x8::FILES {
+            let square = Square::from_rank_file(rank, file);
+            let piece = Pieces::iter().find(|&piece| board.pieces[piece].get_bit(square).is_set());
+
+            match piece {
+                Some(piece) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push_str(&piece.to_string());
+                }
+                None => empty += 1,
+            }
+        }
+
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+
+        if rank < 7 {
+            fen.push('/');
+        }
+    }
+
+    let side = match board.side_to_move {
+        Side::White => "w",
+        _ => "b",
+    };
+
+    let castling = board.castling_rights.to_string().replace('-', "");
+    let castling = if castling.is_empty() { String::from("-") } else { castling };
+
+    let en_passant = match board.en_passant {
+        Square::OffBoard => String::from("-"),
+        square => square.to_string(),
+    };
+
+    format!(
+        "{fen} {side} {castling} {en_passant} {} {full_moves}",
+        board.fifty_move_counter
+    )
+}
+
+fn is_in_check(board: &BoardState) -> bool {
+    let king = match board.side_to_move {
+        Side::White => Pieces::WhiteKing,
+        _ => Pieces::BlackKing,
+    };
+
+    board.is_square_attacked(
+        board.pieces[king].trailing_zeros(),
+        board.side_to_move.enemy(),
+    )
+}
+
+/// Plays a game from the starting position, opening with random moves so games differ, and
+/// returns the recorded positions with the result of the game for white.
+fn play_game(milky: &mut Milky, args: &GenDataArgs, rng: &mut Rng) -> (Vec<Sample>, f32) {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let score = Arc::new(Mutex::new(None));
+
+    milky.new_game();
+    milky.load_position(milky_fen::parse_fen_string(start).unwrap());
+    milky.set_observer(LastScore(score.clone()));
+
+    let mut samples = vec![];
+
+    for ply in 0..MAX_GAME_PLIES {
+        if milky.board_state().fifty_move_counter >= 100 || milky.is_repetition() {
+            return (samples, 0.5);
+        }
+
+        let legal_moves = milky.legal_moves();
+        if legal_moves.is_empty() {
+            let result = match (
+                is_in_check(milky.board_state()),
+                milky.board_state().side_to_move,
+            ) {
+                (false, _) => 0.5,
+                (true, Side::White) => 0.0,
+                (true, _) => 1.0,
+            };
+            return (samples, result);
+        }
+
+        let piece_move: Move = if ply < args.random_plies {
+            let index = rng.next() as usize % legal_moves.len();
+            legal_moves[index].piece_move
+        } else {
+            *score.lock().unwrap() = None;
+            milky.think(TimeControl::FixedDepth(args.depth));
+
+            // mate scores and positions in check make for noisy labels, they are played but not
+            // recorded
+            let board = milky.board_state();
+            if let (Some(Score::Centipawns(score)), false) =
+                (*score.lock().unwrap(), is_in_check(board))
+            {
+                samples.push(Sample {
+                    fen: position_fen(board, ply / 2 + 1),
+                    side_to_move: board.side_to_move,
+                    score,
+                });
+            }
+
+            let best_move = milky.search_state().best_move();
+            if !legal_moves
+                .iter()
+                .any(|legal| legal.piece_move == best_move)
+            {
+                // a search too shallow to complete an iteration has no move to play
+                return (samples, 0.5);
+            }
+
+            best_move
+        };
+
+        milky.load_moves(std::iter::once(piece_move));
+    }
+
+    (samples, 0.5)
+}
+
+fn worker(args: &GenDataArgs, next_game: &AtomicUsize, samples: Sender<(Vec<Sample>, f32)>) {
+    let mut milky = Milky::new();
+
+    loop {
+        let game = next_game.fetch_add(1, Ordering::Relaxed);
+        if game >= args.games {
+            break;
+        }
+
+        let mut rng = Rng(args.seed ^ (game as u64).wrapping_mul(0x2545_F491_4F6C_DD1D));
+        if samples.send(play_game(&mut milky, args, &mut rng)).is_err() {
+            break;
+        }
+    }
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let args = GenDataArgs::parse(args)?;
+    let mut output = BufWriter::new(std::fs::File::create(&args.output)?);
+
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    let mut seen = HashSet::new();
+    let mut written = 0;
+    let mut games = 0;
+
+    std::thread::scope(|scope| -> Result<()> {
+        for _ in 0..args.threads {
+            let sender = sender.clone();
+            scope.spawn(|| worker(&args, &next_game, sender));
+        }
+        drop(sender);
+
+        for (samples, result) in receiver {
+            games += 1;
+
+            for sample in samples {
+                // the clocks are left out, the same position reached at a different move is
+                // still a duplicate
+                let key = sample
+                    .fen
+                    .splitn(5, ' ')
+                    .take(4)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if !seen.insert(key) {
+                    continue;
+                }
+
+                let score = match sample.side_to_move {
+                    Side::White => sample.score,
+                    _ => -sample.score,
+                };
+                writeln!(output, "{},{score},{result}", sample.fen)?;
+                written += 1;
+            }
+
+            eprintln!("game {games}/{}: {written} positions written", args.games);
+        }
+
+        Ok(())
+    })?;
+
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<GenDataArgs> {
+        GenDataArgs::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = args(&["--games", "10", "--depth", "4", "--output", "out.csv"]).unwrap();
+        assert_eq!(parsed.games, 10);
+        assert_eq!(parsed.depth, 4);
+        assert_eq!(parsed.output, "out.csv");
+        assert_eq!(parsed.random_plies, GenDataArgs::default().random_plies);
+
+        assert!(args(&["--games"]).is_err());
+        assert!(args(&["--gmaes", "10"]).is_err());
+    }
+
+    #[test]
+    fn test_position_fen_round_trips() {
+        milky_chess::init_static_members();
+        let mut milky = Milky::new();
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 1",
+            "rnbqkb1r/pp1p1ppp/5n2/2pPp3/8/8/PPP1PPPP/RNBQKBNR w KQkq e6 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            milky.load_position(milky_fen::parse_fen_string(fen).unwrap());
+            assert_eq!(position_fen(milky.board_state(), 1), fen);
+        }
+    }
+
+    #[test]
+    fn test_play_game_records_positions() {
+        milky_chess::init_static_members();
+        let mut milky = Milky::new();
+
+        let args = GenDataArgs {
+            depth: 2,
+            ..Default::default()
+        };
+        let (samples, result) = play_game(&mut milky, &args, &mut Rng(7));
+
+        assert!([0.0, 0.5, 1.0].contains(&result));
+        assert!(!samples.is_empty());
+        assert!(
+            samples
+                .iter()
+                .all(|sample| milky_fen::parse_fen_string(&sample.fen).is_ok())
+        );
+    }
+}
@@ -1,11 +1,44 @@
+mod bench;
+mod build_info;
+mod compare;
+mod engine;
+mod gen_data;
+mod options;
+mod perft;
+mod selftest;
+
 use std::io::BufRead;
 
+use engine::Engine;
 use milky_chess::Milky;
-use milky_uci::command::{BestMoveCommand, GoCommand, PositionCommand, UciCommand};
+use milky_uci::command::{CopyProtectionCommand, PositionCommand, RegistrationCommand, UciCommand};
+use milky_uci::info_string;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("bench") => {
+            milky_chess::init_static_members();
+            return bench::run();
+        }
+        Some("compare") => return compare::run(args),
+        Some("perft") => {
+            milky_chess::init_static_members();
+            return perft::run(args);
+        }
+        Some("selftest") => {
+            milky_chess::init_static_members();
+            return selftest::run();
+        }
+        Some("gen-data") => {
+            milky_chess::init_static_members();
+            return gen_data::run(args);
+        }
+        _ => {}
+    }
+
     milky_chess::init_static_members();
-    let mut milky = Milky::new();
+    let mut engine = Engine::new(Milky::new());
     let mut uci = milky_uci::Uci;
 
     // let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ";
@@ -21,7 +54,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     loop {
         line.clear();
-        handle.read_line(&mut line)?;
+        if handle.read_line(&mut line)? == 0 {
+            // input was closed, let a running search print its best move before exiting
+            engine.close_input();
+            finish_game(engine.milky());
+            break;
+        }
 
         let Some(command) = uci.parse_command(&line)? else {
             continue;
@@ -29,22 +67,47 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         match command {
             UciCommand::Uci => {
-                println!("{}", UciCommand::Id(Default::default()));
+                println!("{}", build_info::id());
+                build_info::build_info()
+                    .iter()
+                    .for_each(|info| println!("{info}"));
+                if engine.debug() {
+                    println!("{}", build_info::bench_info());
+                }
+                options::option_commands()
+                    .into_iter()
+                    .for_each(|option| println!("{}", UciCommand::Option(option)));
                 println!("{}", UciCommand::UciOk);
+                print_copy_protection();
+                print_registration();
             }
-            UciCommand::Debug(_) => continue,
+            UciCommand::Debug(debug) => engine.set_debug(debug),
             UciCommand::IsReady => println!("{}", UciCommand::ReadyOk),
 
-            UciCommand::SetOption(_) => continue,
-            UciCommand::Register(_) => continue,
-            UciCommand::UciNewgame => continue,
+            UciCommand::SetOption(option) => {
+                engine.run(move |milky| options::set_option(milky, option));
+            }
+            UciCommand::Register(_) => print_registration(),
+            UciCommand::UciNewgame => engine.run(|milky| {
+                finish_game(milky);
+                milky.new_game();
+            }),
 
-            UciCommand::Position(position) => load_position(&mut milky, position),
-            UciCommand::Go(go) => println!("{}", handle_go_command(&mut milky, go)),
+            UciCommand::Position(position) => {
+                engine.abort_ponder();
+                let debug = engine.debug();
+                engine.run(move |milky| load_position(milky, position, debug));
+            }
+            UciCommand::Go(go) => engine.go(go),
 
-            UciCommand::Stop => continue,
-            UciCommand::PonderHit => continue,
-            UciCommand::Quit => break,
+            UciCommand::Stop => engine.stop(),
+            UciCommand::PonderHit => engine.ponder_hit(),
+            UciCommand::Quit => {
+                engine.stop();
+                finish_game(engine.milky());
+                break;
+            }
+            UciCommand::Moves => engine.run(print_legal_moves),
 
             // This set of commands are only sent from the engine to the GUI
             UciCommand::Id(_) => unreachable!(),
@@ -61,17 +124,61 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn load_position(milky: &mut Milky, position: PositionCommand) {
-    milky.new_game();
+fn finish_game(milky: &mut Milky) {
+    if let Err(err) = milky.finish_game() {
+        info_string!(
+            "failed to save learning file {}: {err}",
+            milky.options().learning_file.display()
+        );
+    }
+}
+
+/// Milky is free, so there is no copy protection to check. Some GUIs still wait for the check to
+/// finish before going on.
+fn print_copy_protection() {
+    println!(
+        "{}",
+        UciCommand::CopyProtection(CopyProtectionCommand::Checking)
+    );
+    println!("{}", UciCommand::CopyProtection(CopyProtectionCommand::Ok));
+}
+
+/// Milky doesn't need registering, so any registration, or no registration at all, is accepted.
+fn print_registration() {
+    println!(
+        "{}",
+        UciCommand::Registration(RegistrationCommand::Checking)
+    );
+    println!("{}", UciCommand::Registration(RegistrationCommand::Ok));
+}
+
+fn load_position(milky: &mut Milky, position: PositionCommand, debug: bool) {
     milky.load_position(position.fen);
-    milky.load_moves(position.moves.into_iter());
+    let status = milky.load_moves(position.moves.into_iter());
+    if status.is_over() && debug {
+        info_string!("the game is over: {status}");
+    }
 }
 
-fn handle_go_command(milky: &mut Milky, go_command: GoCommand) -> BestMoveCommand {
-    milky.think(go_command);
+fn print_legal_moves(milky: &mut Milky) {
+    for legal_move in milky.legal_moves() {
+        let piece_move = legal_move.piece_move;
+        let flags = [
+            (piece_move.is_capture(), "capture"),
+            (legal_move.check && !legal_move.mate, "check"),
+            (legal_move.mate, "mate"),
+            (piece_move.is_castling(), "castle"),
+            (piece_move.is_en_passant(), "ep"),
+        ]
+        .into_iter()
+        .filter_map(|(is_set, flag)| is_set.then_some(flag))
+        .collect::<Vec<_>>();
 
-    BestMoveCommand {
-        best_move: milky.search_state().best_move().to_string(),
-        ponder: None,
+        println!(
+            "{:<7}{:<10}{}",
+            piece_move.to_string(),
+            legal_move.san,
+            flags.join(" ")
+        );
     }
 }
@@ -0,0 +1,241 @@
+use milky_chess::Milky;
+use milky_chess::options::{EngineOptions, OpeningJitter, SearchFeatures, StrengthLimit};
+use milky_uci::command::{OptionCommand, OptionType, SetOptionCommand};
+use milky_uci::info_string;
+
+const MAX_THREADS: usize = 256;
+const MAX_HASH_SIZE: usize = 32768;
+const MAX_CONTEMPT: i32 = 100;
+
+/// Every option the engine advertises to the GUI after `uci`.
+pub fn option_commands() -> Vec<OptionCommand> {
+    let jitter = OpeningJitter::default();
+    let engine_options = EngineOptions::default();
+
+    vec![
+        OptionCommand {
+            name: "nodestime".into(),
+            option_type: OptionType::Spin {
+                default: 0,
+                min: 0,
+                max: 10000,
+            },
+        },
+        OptionCommand {
+            name: "Ponder".into(),
+            option_type: OptionType::Check { default: false },
+        },
+        OptionCommand {
+            name: "Hash".into(),
+            option_type: OptionType::Spin {
+                default: milky_chess::DEFAULT_HASH_SIZE as i32,
+                min: 1,
+                max: MAX_HASH_SIZE as i32,
+            },
+        },
+        OptionCommand {
+            name: "Clear Hash".into(),
+            option_type: OptionType::Button,
+        },
+        OptionCommand {
+            name: "HashFile".into(),
+            option_type: OptionType::String {
+                default: engine_options.hash_file.display().to_string(),
+            },
+        },
+        OptionCommand {
+            name: "Save Hash".into(),
+            option_type: OptionType::Button,
+        },
+        OptionCommand {
+            name: "Load Hash".into(),
+            option_type: OptionType::Button,
+        },
+        OptionCommand {
+            name: "Threads".into(),
+            option_type: OptionType::Spin {
+                default: milky_chess::DEFAULT_THREADS as i32,
+                min: 1,
+                max: MAX_THREADS as i32,
+            },
+        },
+        OptionCommand {
+            name: "Contempt".into(),
+            option_type: OptionType::Spin {
+                default: engine_options.contempt,
+                min: -MAX_CONTEMPT,
+                max: MAX_CONTEMPT,
+            },
+        },
+        OptionCommand {
+            name: "OpeningJitter".into(),
+            option_type: OptionType::Spin {
+                default: jitter.max_score,
+                min: 0,
+                max: 100,
+            },
+        },
+        OptionCommand {
+            name: "OpeningJitterMoves".into(),
+            option_type: OptionType::Spin {
+                default: jitter.moves as i32,
+                min: 0,
+                max: 100,
+            },
+        },
+        OptionCommand {
+            name: "OpeningJitterSeed".into(),
+            option_type: OptionType::Spin {
+                default: jitter.seed as i32,
+                min: 0,
+                max: i32::MAX,
+            },
+        },
+        OptionCommand {
+            name: "UCI_LimitStrength".into(),
+            option_type: OptionType::Check { default: false },
+        },
+        OptionCommand {
+            name: "UCI_Elo".into(),
+            option_type: OptionType::Spin {
+                default: engine_options.strength_limit.elo as i32,
+                min: StrengthLimit::MIN_ELO as i32,
+                max: StrengthLimit::MAX_ELO as i32,
+            },
+        },
+        OptionCommand {
+            name: "Deterministic".into(),
+            option_type: OptionType::Check { default: false },
+        },
+        OptionCommand {
+            name: "Learning".into(),
+            option_type: OptionType::Check { default: false },
+        },
+        OptionCommand {
+            name: "LearningFile".into(),
+            option_type: OptionType::String {
+                default: engine_options.learning_file.display().to_string(),
+            },
+        },
+    ]
+}
+
+/// Applies a `setoption` sent by the GUI, unknown options and malformed values are ignored as
+/// the protocol has no way to report them back.
+///
+/// Besides the advertised options, every [`SearchFeatures`] can be toggled through a hidden check
+/// option (e.g. `setoption name nullmove value false`), which is meant for ablation testing and
+/// not for regular play.
+pub fn set_option(milky: &mut Milky, option: SetOptionCommand) {
+    let value = option.value.as_deref().unwrap_or_default();
+
+    if let Some(feature) = SearchFeatures::from_option_name(&option.name) {
+        if let Ok(enabled) = value.parse() {
+            milky.options_mut().search_features.set(feature, enabled);
+        }
+        return;
+    }
+
+    let options = milky.options_mut();
+    match option.name.to_ascii_lowercase().as_str() {
+        "nodestime" => {
+            if let Ok(nodes_time) = value.parse() {
+                options.nodes_time = nodes_time;
+            }
+        }
+        "ponder" => {
+            if let Ok(ponder) = value.parse() {
+                options.ponder = ponder;
+            }
+        }
+        "hash" => {
+            if let Ok(size) = value.parse::<usize>() {
+                milky.set_hash_size(size.clamp(1, MAX_HASH_SIZE));
+            }
+        }
+        "clear hash" => milky.clear_hash(),
+        "hashfile" => options.hash_file = value.into(),
+        "save hash" => {
+            if let Err(err) = milky.save_hash() {
+                info_string!(
+                    "failed to save hash file {}: {err}",
+                    milky.options().hash_file.display()
+                );
+            }
+        }
+        "load hash" => {
+            if let Err(err) = milky.load_hash() {
+                info_string!(
+                    "failed to load hash file {}: {err}",
+                    milky.options().hash_file.display()
+                );
+            }
+        }
+        "threads" => {
+            if let Ok(threads) = value.parse::<usize>() {
+                options.threads = threads.clamp(1, MAX_THREADS);
+            }
+        }
+        "contempt" => {
+            if let Ok(contempt) = value.parse::<i32>() {
+                options.contempt = contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT);
+            }
+        }
+        "openingjitter" => {
+            if let Ok(max_score) = value.parse::<i32>() {
+                options.opening_jitter.max_score = max_score.clamp(0, 100);
+            }
+        }
+        "openingjittermoves" => {
+            if let Ok(moves) = value.parse() {
+                options.opening_jitter.moves = moves;
+            }
+        }
+        "openingjitterseed" => {
+            if let Ok(seed) = value.parse() {
+                options.opening_jitter.seed = seed;
+            }
+        }
+        "uci_limitstrength" => {
+            if let Ok(enabled) = value.parse() {
+                options.strength_limit.enabled = enabled;
+                // a different line of mistakes every session
+                options.strength_limit.seed = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+            }
+        }
+        "uci_elo" => {
+            if let Ok(elo) = value.parse::<u32>() {
+                options.strength_limit.elo =
+                    elo.clamp(StrengthLimit::MIN_ELO, StrengthLimit::MAX_ELO);
+            }
+        }
+        "deterministic" => {
+            if let Ok(deterministic) = value.parse() {
+                options.deterministic = deterministic;
+            }
+        }
+        "learning" => {
+            if let Ok(enabled) = value.parse() {
+                set_learning(milky, enabled);
+            }
+        }
+        "learningfile" => {
+            options.learning_file = value.into();
+            // reload the positions learned on the new file
+            let enabled = milky.learned_positions().is_some();
+            set_learning(milky, enabled);
+        }
+        _ => {}
+    }
+}
+
+fn set_learning(milky: &mut Milky, enabled: bool) {
+    if let Err(err) = milky.set_learning(enabled) {
+        info_string!(
+            "failed to load learning file {}: {err}",
+            milky.options().learning_file.display()
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! `milky perft` counts the leaves of every move of a position, the usual way of tracking down
+//! move generation bugs by diffing the counts against another engine.
+//!
+//! ```text
+//! milky perft 4 [--stats] [fen]
+//! ```
+//!
+//! With `--stats` every count is broken down into captures, en passants, castles, promotions,
+//! checks and checkmates, like the reference tables on the chess programming wiki. The position
+//! defaults to the initial one.
+
+use std::time::Instant;
+
+use milky_chess::Milky;
+use milky_chess::perft::PerftStats;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const INITIAL_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let usage = "usage: milky perft <depth> [--stats] [fen]";
+    let depth = args.next().ok_or(usage)?.parse::<u8>().map_err(|_| usage)?;
+    if depth == 0 {
+        return Err(usage.into());
+    }
+
+    let mut stats = false;
+    let mut fen = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--stats" => stats = true,
+            _ => fen.push(arg),
+        }
+    }
+
+    let fen = match fen.is_empty() {
+        true => INITIAL_POSITION.to_string(),
+        false => fen.join(" "),
+    };
+
+    let mut milky = Milky::new();
+    milky.load_position(milky_fen::parse_fen_string(&fen)?);
+
+    let start = Instant::now();
+    let divided = milky.perft_divide(depth);
+    let elapsed = start.elapsed();
+
+    let mut total = PerftStats::default();
+    for (piece_move, move_stats) in divided {
+        match stats {
+            true => println!("{piece_move}: {move_stats}"),
+            false => println!("{piece_move}: {}", move_stats.nodes),
+        }
+        total += move_stats;
+    }
+
+    println!();
+    match stats {
+        true => println!("{total}"),
+        false => println!("nodes {}", total.nodes),
+    }
+    println!("time {}ms", elapsed.as_millis());
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+//! `milky selftest` runs quick checks of the tables and invariants the engine relies on, and
+//! prints whether each subsystem passed.
+//!
+//! ```text
+//! milky selftest
+//! ```
+//!
+//! Meant for verifying a build before using it in tournaments, the command fails when any of the
+//! checks does.
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let results = milky_chess::selftest::run();
+    results.iter().for_each(|result| println!("{result}"));
+
+    match results.iter().all(|result| result.result.is_ok()) {
+        true => Ok(()),
+        false => Err("the self test failed".into()),
+    }
+}
@@ -106,7 +106,7 @@ fn parse_position(position: &str) -> [BitBoard; 12] {
     let (mut rank, mut file) = (0, 0);
 
     for ch in position.chars() {
-        let square = Square::from_u64_unchecked(rank * 8 + file);
+        let square = Square::from_rank_file(rank, file);
         let mut skip = 1;
 
         match ch {
@@ -122,7 +122,7 @@ fn parse_position(position: &str) -> [BitBoard; 12] {
             'Q' => boards[Pieces::WhiteQueen].set_bit(square),
             'K' => boards[Pieces::WhiteKing].set_bit(square),
             'P' => boards[Pieces::WhitePawn].set_bit(square),
-            '1'..='8' => skip = ch.to_digit(10).unwrap() as u64,
+            '1'..='8' => skip = ch.to_digit(10).unwrap() as usize,
             '/' => {
                 rank += 1;
                 file = 0;
@@ -223,6 +223,8 @@ fn get_occupancy(positions: [BitBoard; 12], side: Side) -> BitBoard {
 mod tests {
     use std::fmt::{Display, Write};
 
+    use milky_bitboard::geometry::{Board8x8, BoardGeometry};
+
     use super::*;
 
     static INITIAL_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -289,12 +291,12 @@ mod tests {
         let mut buffer = String::new();
         writeln!(buffer).unwrap();
 
-        for rank in 0..8 {
+        for rank in 0..Board8x8::RANKS {
             let mut line = String::with_capacity(20);
-            line.push_str(&format!("  {} ", 8 - rank));
+            line.push_str(&format!("  {} ", Board8x8::RANKS - rank));
 
-            for file in 0..8 {
-                let square = Square::from_u64_unchecked(rank * 8 + file);
+            for file in 0..Board8x8::FILES {
+                let square = Square::from_rank_file(rank, file);
                 let mut piece = String::from(".");
 
                 for (idx, &board) in boards.iter().enumerate() {
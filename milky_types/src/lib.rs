@@ -0,0 +1,45 @@
+//! The public API of milky, gathered from the crates that implement it.
+//!
+//! Embedders should depend on this crate instead of `milky_bitboard`, `milky_fen` and
+//! `milky_chess` directly. The items here follow semver, while the internals of the other crates
+//! are free to move around between releases.
+//!
+//! Turning off the default `search` feature leaves out the engine's search, making this a rules
+//! library: positions, legal moves and FEN parsing.
+//!
+//! ```
+//! use milky_types::{Engine, TimeControl, parse_fen};
+//!
+//! milky_types::init();
+//!
+//! let mut engine = Engine::new();
+//! engine.load_position(parse_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+//! assert!(engine.legal_moves().is_empty());
+//!
+//! engine.load_position(parse_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap());
+//! engine.think(TimeControl::FixedDepth(3));
+//! assert_eq!(engine.search_state().best_move().to_string(), "a1a8");
+//! ```
+
+pub use milky_bitboard::{
+    BitBoard, CastlingRights, Move, MoveBuilder, Pieces as Piece, PromotionPieces, Side as Color,
+    Square,
+};
+pub use milky_chess::game_status::GameStatus;
+#[cfg(feature = "search")]
+pub use milky_chess::observer::{
+    IterationInfo, ProgressInfo, RootMoveInfo, Score, SearchObserver, SearchResult, StopInfo,
+};
+#[cfg(feature = "search")]
+pub use milky_chess::options::{EngineOptions, SearchFeatures};
+pub use milky_chess::san::LegalMove;
+#[cfg(feature = "search")]
+pub use milky_chess::time_manager::{Clock, ConventionalTimeControl, StopHandle, TimeControl};
+pub use milky_chess::{BoardState as Position, Milky as Engine};
+pub use milky_fen::{Error as FenError, FenParts as Fen, parse_fen_string as parse_fen};
+
+/// Builds the lookup tables used by the engine, must be called before creating an [`Engine`].
+/// Calling it more than once is cheap.
+pub fn init() {
+    milky_chess::init_static_members();
+}
@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use milky_bitboard::{Move, PromotionPieces, Side, Square};
 use milky_chess::moves::Movable;
+use milky_chess::observer::Score;
 use milky_chess::time_manager::{ConventionalTimeControl, IntoTimeControl, TimeControl};
 use milky_fen::FenParts;
 
@@ -10,6 +11,37 @@ use super::parser::parse_uci_command;
 
 pub static START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
+/// Well known test positions (the perft positions from the chess programming wiki), which can be
+/// set up with `position name <name>` instead of typing their FEN.
+pub static NAMED_POSITIONS: [(&str, &str); 6] = [
+    ("startpos", START_POSITION),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    ("position3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+    (
+        "position4",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ),
+    (
+        "position5",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ),
+    (
+        "position6",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    ),
+];
+
+/// Finds the FEN of a named test position, ignoring case.
+pub fn named_position(name: &str) -> Option<&'static str> {
+    NAMED_POSITIONS
+        .iter()
+        .find(|(position, _)| position.eq_ignore_ascii_case(name))
+        .map(|(_, fen)| *fen)
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum UciCommand {
     /// Tell the engine to use the UCI (Universal Chess Interface), this will be sent once as a
@@ -76,6 +108,9 @@ pub enum UciCommand {
     PonderHit,
     /// Quit the program as soon as possible
     Quit,
+    /// Not part of UCI. Lists every legal move in the current position, to check how the engine
+    /// understands a position when debugging.
+    Moves,
 
     /// This must be sent after receiving the uci command to identify the engine
     Id(IdCommand),
@@ -136,6 +171,7 @@ impl std::fmt::Display for UciCommand {
             UciCommand::Stop => write!(f, "stop"),
             UciCommand::PonderHit => write!(f, "ponderhit"),
             UciCommand::Quit => write!(f, "quit"),
+            UciCommand::Moves => write!(f, "moves"),
 
             UciCommand::Id(id_command) => write!(f, "{id_command}"),
             UciCommand::UciOk => write!(f, "uciok"),
@@ -308,7 +344,7 @@ pub struct GoCommand {
     pub depth: Option<u8>,
     /// Search only X number of nodes.
     pub nodes: Option<u64>,
-    /// Search for a forced mate in N plies.
+    /// Search for a forced mate in N moves.
     pub mate: Option<u8>,
     /// Search for this exact amount of time.
     pub move_time: Option<u64>,
@@ -511,6 +547,15 @@ pub enum ScoreInfo {
     UpperBound,
 }
 
+impl From<Score> for ScoreInfo {
+    fn from(score: Score) -> Self {
+        match score {
+            Score::Centipawns(score) => Self::Cp(score),
+            Score::Mate(moves) => Self::Mate(moves),
+        }
+    }
+}
+
 impl std::fmt::Display for ScoreInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -546,7 +591,7 @@ impl std::fmt::Display for CurrentLineInfo {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct InfoCommand {
     /// Search depth in plies.
     pub depth: Option<u8>,
@@ -627,14 +672,6 @@ impl std::fmt::Display for InfoCommand {
             line.push_str(&format!(" nodes {nodes}"));
         }
 
-        if let Some(pv) = &self.pv {
-            line.push_str(" pv");
-
-            for mv in pv.iter() {
-                line.push_str(&format!(" {mv}"));
-            }
-        }
-
         if let Some(multi_pv) = self.multi_pv {
             line.push_str(&format!(" multipv {multi_pv}"));
         }
@@ -671,10 +708,6 @@ impl std::fmt::Display for InfoCommand {
             line.push_str(&format!(" cpuload {cpu_load}"));
         }
 
-        if let Some(string) = &self.string {
-            line.push_str(&format!(" string {string}"));
-        }
-
         if let Some(refutation) = &self.refutation {
             line.push_str(" refutation");
 
@@ -687,10 +720,47 @@ impl std::fmt::Display for InfoCommand {
             line.push_str(&format!(" {curr_line}"));
         }
 
+        // GUIs read the moves of the pv up to the end of the line, so it comes after every other
+        // field but the string
+        if let Some(pv) = &self.pv {
+            line.push_str(" pv");
+
+            for mv in pv.iter() {
+                line.push_str(&format!(" {mv}"));
+            }
+        }
+
+        // the string runs until the end of the line, so it must come last and can't span lines
+        if let Some(string) = &self.string {
+            line.push_str(" string ");
+            line.push_str(&string.replace(['\r', '\n'], " "));
+        }
+
         write!(f, "{line}")
     }
 }
 
+impl InfoCommand {
+    /// Splits an `info` line into its structured fields and the text of its `string` field.
+    ///
+    /// The text runs until the end of the line and can hold anything, keywords like `pv` or
+    /// `depth` included, so it has to be taken out before the fields are read.
+    pub fn split_string(line: &str) -> (&str, Option<&str>) {
+        let mut offset = 0;
+
+        for token in line.split_whitespace() {
+            let start = offset + line[offset..].find(token).unwrap_or_default();
+            offset = start + token.len();
+
+            if token == "string" {
+                return (line[..start].trim_end(), Some(line[offset..].trim()));
+            }
+        }
+
+        (line.trim_end(), None)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OptionType {
     Check { default: bool },
@@ -711,8 +781,9 @@ pub enum OptionType {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct OptionCommand {
     /// Name of the option.
-    name: String,
-    option_type: OptionType,
+    pub name: String,
+    /// Kind of the option, alongside its default value and bounds.
+    pub option_type: OptionType,
 }
 
 impl std::fmt::Display for OptionCommand {
@@ -749,6 +820,7 @@ impl std::fmt::Display for OptionCommand {
 
 #[cfg(test)]
 mod tests {
+    use milky_bitboard::{MoveBuilder, Pieces};
     use milky_fen::parse_fen_string;
 
     use super::*;
@@ -785,6 +857,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_square_type_is_shared_across_crates() {
+        // Compile-time guard: every crate must expose `milky_bitboard::Square` rather than a
+        // local duplicate, otherwise these calls would fail to type check.
+        fn assert_bitboard_square(_: milky_bitboard::Square) {}
+
+        let fen = parse_fen_string(START_POSITION).unwrap();
+        assert_bitboard_square(fen.en_passant);
+
+        let mov = make_move("e2e4");
+        assert_bitboard_square(Movable::source(&mov));
+        assert_bitboard_square(Movable::target(&mov));
+    }
+
     #[test]
     fn test_position_command_print() {
         let command = PositionCommand {
@@ -822,4 +908,62 @@ mod tests {
             "position fen 8/8/8/8/8/8/8/8 w KQkq - 0 1 moves e2e4 e7e5"
         );
     }
+
+    #[test]
+    fn test_info_command_print() {
+        let command = InfoCommand {
+            depth: Some(4),
+            score: Some(ScoreInfo::Cp(25)),
+            string: Some("first line\nsecond line".into()),
+            current_line: Some(CurrentLineInfo {
+                cpu_number: 0,
+                line: vec![],
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            command.to_string(),
+            "info depth 4 score cp 25 currline string first line second line"
+        );
+    }
+
+    #[test]
+    fn test_info_command_prints_pv_last() {
+        let command = InfoCommand {
+            depth: Some(2),
+            nodes: Some(40),
+            pv: Some(vec![
+                MoveBuilder::new(Square::E2, Square::E4)
+                    .piece(Pieces::WhitePawn)
+                    .build(),
+                MoveBuilder::new(Square::E7, Square::E5)
+                    .piece(Pieces::BlackPawn)
+                    .build(),
+            ]),
+            score: Some(Score::Mate(-3).into()),
+            hashfull: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            command.to_string(),
+            "info depth 2 nodes 40 score mate -3 hashfull 1 pv e2e4 e7e5"
+        );
+    }
+
+    #[test]
+    fn test_split_info_string() {
+        assert_eq!(
+            InfoCommand::split_string("info depth 2 string pv depth 3"),
+            ("info depth 2", Some("pv depth 3"))
+        );
+        // only a whole `string` token starts the text
+        assert_eq!(
+            InfoCommand::split_string("info strings depth 2"),
+            ("info strings depth 2", None)
+        );
+        assert_eq!(
+            InfoCommand::split_string("info string  lots   of  space "),
+            ("info", Some("lots   of  space"))
+        );
+    }
 }
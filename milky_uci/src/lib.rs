@@ -4,6 +4,8 @@ mod parser;
 
 use command::UciCommand;
 use error::Result;
+/// Prints engine diagnostics as `info string` lines, see [`milky_chess::info_string`].
+pub use milky_chess::info_string;
 
 pub struct Uci;
 
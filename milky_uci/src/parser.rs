@@ -3,7 +3,8 @@ use std::str::FromStr;
 use milky_bitboard::{PromotionPieces, Square};
 
 use super::command::{
-    GoCommand, PartialMove, PositionCommand, RegisterCommand, SetOptionCommand, UciCommand,
+    GoCommand, InfoCommand, NAMED_POSITIONS, PartialMove, PositionCommand, RegisterCommand,
+    ScoreInfo, SetOptionCommand, UciCommand, named_position,
 };
 use super::error::{Error, Result};
 
@@ -27,6 +28,8 @@ pub fn parse_uci_command(line: &str) -> Result<Option<UciCommand>> {
             "stop" => return Ok(Some(UciCommand::Stop)),
             "ponderhit" => return Ok(Some(UciCommand::PonderHit)),
             "quit" => return Ok(Some(UciCommand::Quit)),
+            "moves" => return Ok(Some(UciCommand::Moves)),
+            "info" => return parse_info_command(line),
             _ => continue,
         }
     }
@@ -52,7 +55,7 @@ fn parse_position_command<'a>(
 ) -> Result<Option<UciCommand>> {
     let Some(next) = split.next() else {
         return Err(Error::InsufficientCommand(
-            "Position command must specify `startpos` or `fen`".into(),
+            "Position command must specify `startpos`, `fen` or `name`".into(),
         ));
     };
 
@@ -69,9 +72,24 @@ fn parse_position_command<'a>(
                 start_position: false,
             }
         }
+        "name" => {
+            let name = split.next().unwrap_or_default();
+            let Some(fen) = named_position(name) else {
+                let names = NAMED_POSITIONS.map(|(name, _)| name).join(", ");
+                return Err(Error::InvalidCommand(format!(
+                    "Unknown position `{name}`, expected one of: {names}"
+                )));
+            };
+
+            PositionCommand {
+                fen: milky_fen::parse_fen_string(fen)?,
+                moves: vec![],
+                start_position: false,
+            }
+        }
         other => {
             return Err(Error::InvalidCommand(format!(
-                "Expected `startpos` or `fen`, got: `{other}`"
+                "Expected `startpos`, `fen` or `name`, got: `{other}`"
             )));
         }
     };
@@ -150,14 +168,14 @@ fn parse_go_command<'a>(mut split: impl Iterator<Item = &'a str>) -> Result<Opti
             }
             "ponder" => command.ponder = true,
             "depth" => command.depth = Some(parse_number(&mut split, "depth")?),
-            "wtime" => command.white_time = Some(parse_number(&mut split, next)?),
-            "btime" => command.black_time = Some(parse_number(&mut split, next)?),
-            "winc" => command.white_inc = Some(parse_number(&mut split, next)?),
-            "binc" => command.black_inc = Some(parse_number(&mut split, next)?),
+            "wtime" => command.white_time = Some(parse_time(&mut split, next)?),
+            "btime" => command.black_time = Some(parse_time(&mut split, next)?),
+            "winc" => command.white_inc = Some(parse_time(&mut split, next)?),
+            "binc" => command.black_inc = Some(parse_time(&mut split, next)?),
             "movestogo" => command.moves_to_go = Some(parse_number(&mut split, next)?),
             "nodes" => command.nodes = Some(parse_number(&mut split, next)?),
             "mate" => command.mate = Some(parse_number(&mut split, next)?),
-            "movetime" => command.move_time = Some(parse_number(&mut split, next)?),
+            "movetime" => command.move_time = Some(parse_time(&mut split, next)?),
             "infinite" => command.infinite = true,
             other => {
                 return Err(Error::InvalidCommand(format!(
@@ -170,6 +188,51 @@ fn parse_go_command<'a>(mut split: impl Iterator<Item = &'a str>) -> Result<Opti
     Ok(Some(UciCommand::Go(command)))
 }
 
+/// Parses the fields of an `info` line that can be read without knowing the position, moves
+/// (`pv`, `currmove`, ...) are skipped.
+///
+/// Everything after `string` is kept as is, so text like `info string go depth 5` is never
+/// mistaken for structured fields or another command.
+fn parse_info_command(line: &str) -> Result<Option<UciCommand>> {
+    let (fields, string) = InfoCommand::split_string(line);
+    let mut command = InfoCommand {
+        string: string.map(String::from),
+        ..Default::default()
+    };
+
+    let mut split = fields
+        .split_whitespace()
+        .skip_while(|&token| token != "info")
+        .skip(1);
+    while let Some(next) = split.next() {
+        match next {
+            "depth" => command.depth = Some(parse_number(&mut split, next)?),
+            "seldepth" => command.selective_depth = Some(parse_number(&mut split, next)?),
+            "time" => command.time = Some(parse_number(&mut split, next)?),
+            "nodes" => command.nodes = Some(parse_number(&mut split, next)?),
+            "multipv" => command.multi_pv = Some(parse_number(&mut split, next)?),
+            "currmovenumber" => command.current_move_number = Some(parse_number(&mut split, next)?),
+            "hashfull" => command.hashfull = Some(parse_number(&mut split, next)?),
+            "nps" => command.nodes_per_second = Some(parse_number(&mut split, next)?),
+            "tbhits" => command.table_base_hits = Some(parse_number(&mut split, next)?),
+            "sbhits" => command.shredder_base_hits = Some(parse_number(&mut split, next)?),
+            "cpuload" => command.cpu_load = Some(parse_number(&mut split, next)?),
+            "cp" => command.score = Some(ScoreInfo::Cp(parse_number(&mut split, next)?)),
+            "mate" => command.score = Some(ScoreInfo::Mate(parse_number(&mut split, next)?)),
+            // a bound only replaces the score when no value came with it
+            "lowerbound" => {
+                command.score.get_or_insert(ScoreInfo::LowerBound);
+            }
+            "upperbound" => {
+                command.score.get_or_insert(ScoreInfo::UpperBound);
+            }
+            _ => continue,
+        }
+    }
+
+    Ok(Some(UciCommand::Info(command)))
+}
+
 fn parse_number<'a, T: FromStr>(
     mut split: impl Iterator<Item = &'a str>,
     keyword: &str,
@@ -185,6 +248,12 @@ fn parse_number<'a, T: FromStr>(
         .map_err(|_| Error::InvalidCommand(format!("Invalid number for `{keyword}`")))
 }
 
+/// Parses a time in milliseconds. GUIs may send a negative clock when the engine is already out
+/// of time, which is treated as no time left.
+fn parse_time<'a>(split: impl Iterator<Item = &'a str>, keyword: &str) -> Result<u64> {
+    parse_number::<i64>(split, keyword).map(|time| time.max(0) as u64)
+}
+
 fn parse_set_option_command<'a>(
     mut split: impl Iterator<Item = &'a str>,
 ) -> Result<Option<UciCommand>> {
@@ -485,6 +554,23 @@ mod tests {
         assert!(matches!(result, Err(Error::InvalidCommand(_))));
     }
 
+    #[test]
+    fn test_go_negative_time_is_zero() {
+        let cmd = "go wtime -120 btime 3000 winc -1 movetime 0";
+        let result = parse_uci_command(cmd).unwrap().unwrap();
+
+        assert_eq!(
+            result,
+            UciCommand::Go(GoCommand {
+                white_time: Some(0),
+                black_time: Some(3000),
+                white_inc: Some(0),
+                move_time: Some(0),
+                ..Default::default()
+            })
+        );
+    }
+
     #[test]
     fn test_go_empty() {
         let cmd = "go";
@@ -602,4 +688,85 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_parse_moves_command() {
+        let command = "moves";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        assert_eq!(result, UciCommand::Moves);
+
+        // the `moves` keyword of a position command is not a command on its own
+        let command = "position startpos moves e2e4";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        assert!(matches!(result, UciCommand::Position(_)));
+    }
+
+    #[test]
+    fn test_parse_position_name_command() {
+        let command = "position name Kiwipete moves e2a6";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        let UciCommand::Position(position) = result else {
+            panic!("expected a position command, got: {result:?}");
+        };
+        assert_eq!(
+            position.fen,
+            milky_fen::parse_fen_string(NAMED_POSITIONS[1].1).unwrap()
+        );
+        assert_eq!(position.moves.len(), 1);
+
+        for (name, fen) in NAMED_POSITIONS {
+            let result = parse_uci_command(&format!("position name {name}")).unwrap();
+            assert!(milky_fen::parse_fen_string(fen).is_ok(), "{name}");
+            assert!(matches!(result, Some(UciCommand::Position(_))), "{name}");
+        }
+
+        let command = "position name nowhere";
+        let result = parse_uci_command(command).unwrap_err();
+        assert!(matches!(result, Error::InvalidCommand(_)));
+    }
+
+    #[test]
+    fn test_parse_info_command() {
+        let command = "info depth 5 seldepth 9 score cp -31 nodes 1200 nps 60000 pv e2e4 e7e5";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        let expected = InfoCommand {
+            depth: Some(5),
+            selective_depth: Some(9),
+            score: Some(ScoreInfo::Cp(-31)),
+            nodes: Some(1200),
+            nodes_per_second: Some(60000),
+            ..Default::default()
+        };
+        assert_eq!(result, UciCommand::Info(expected));
+
+        let command = "info score mate -3 upperbound hashfull 12";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        let expected = InfoCommand {
+            score: Some(ScoreInfo::Mate(-3)),
+            hashfull: Some(12),
+            ..Default::default()
+        };
+        assert_eq!(result, UciCommand::Info(expected));
+    }
+
+    #[test]
+    fn test_parse_info_string_command() {
+        // keywords inside the string are text, not fields or commands
+        let command = "info depth 3 string go depth 5   pv e2e4 ";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        let expected = InfoCommand {
+            depth: Some(3),
+            string: Some("go depth 5   pv e2e4".into()),
+            ..Default::default()
+        };
+        assert_eq!(result, UciCommand::Info(expected));
+
+        let command = "info string";
+        let result = parse_uci_command(command).unwrap().unwrap();
+        let expected = InfoCommand {
+            string: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(result, UciCommand::Info(expected));
+    }
 }